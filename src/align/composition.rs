@@ -0,0 +1,211 @@
+// Composition-based score adjustment, in the style of BLAST's `-comp_based_stats`.
+//
+// A biased query scored against a similarly biased target collects high scores just
+// from sharing the bias. For each query-target pair the matrix is rescaled to their
+// residue frequencies:
+//   - Scale: multiply the matrix by lambda'/lambda, where lambda' is the matrix's
+//     lambda under the pair's compositions (Schaffer et al., 2001).
+//   - Full: matrix adjustment (Yu & Altschul, 2005). The matrix's implied target
+//     frequencies q_ij = p_i p_j exp(lambda s_ij) are fitted by iterative proportional
+//     fitting to row sums = query composition and column sums = target composition,
+//     which is the closest joint distribution in relative entropy, then turned back
+//     into scores with the standard lambda.
+//   - Conditional: Full when the compositions make it worthwhile, Scale otherwise.
+//
+// Only the 20 standard residues are rescaled; B, Z, X and * keep their matrix scores.
+use std::borrow::Cow;
+use crate::align::matrix::{ScoreMatrix, BACKGROUND, STANDARD_RESIDUES, MATRIX_SIZE};
+use crate::align::ungapped::Scoring;
+use crate::core::alphabet::matrix_index;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CompAdjust {
+    /// Use the matrix as is
+    #[default]
+    None,
+    /// Rescale the whole matrix by lambda
+    Scale,
+    /// Matrix adjustment when compositions warrant it, scaling otherwise (BLAST mode 2)
+    Conditional,
+    /// Always adjust the matrix (BLAST mode 3)
+    Full,
+}
+
+/// Weight of the background prior mixed into observed counts
+const PSEUDOCOUNTS: f64 = 20.0;
+/// Conditional mode: shorter sequences give too noisy a composition to fit
+const MIN_LENGTH: usize = 50;
+/// Conditional mode: compositions further apart than this (degrees) are only scaled
+const MAX_ANGLE: f64 = 70.0;
+/// Conditional mode: adjust only if one composition is this far (nats) from background
+const MIN_RELATIVE_ENTROPY: f64 = 0.08;
+
+/// Standard-residue counts of one sequence, case-insensitive.
+#[derive(Debug, Clone, Copy)]
+pub struct Composition {
+    pub counts: [u32; STANDARD_RESIDUES],
+    pub total: u32,
+}
+
+impl Composition {
+    pub fn of(seq: &[u8]) -> Self {
+        let mut counts = [0u32; STANDARD_RESIDUES];
+        for &b in seq {
+            let i = matrix_index(b);
+            if i < STANDARD_RESIDUES {
+                counts[i] += 1;
+            }
+        }
+        Self { counts, total: counts.iter().sum() }
+    }
+
+    /// Observed frequencies blended with the background.
+    pub fn frequencies(&self) -> [f64; STANDARD_RESIDUES] {
+        let bg = background();
+        let mut freqs = [0.0; STANDARD_RESIDUES];
+        let denom = self.total as f64 + PSEUDOCOUNTS;
+        for (i, f) in freqs.iter_mut().enumerate() {
+            *f = (self.counts[i] as f64 + PSEUDOCOUNTS * bg[i]) / denom;
+        }
+        freqs
+    }
+}
+
+/// `BACKGROUND` normalized to sum to 1.
+pub fn background() -> [f64; STANDARD_RESIDUES] {
+    let sum: f64 = BACKGROUND.iter().sum();
+    BACKGROUND.map(|p| p / sum)
+}
+
+/// Lambda of `matrix` under background frequencies (about 0.318 for BLOSUM62).
+pub fn standard_lambda(matrix: &ScoreMatrix) -> Option<f64> {
+    let bg = background();
+    matrix.lambda(&bg, &bg)
+}
+
+fn relative_entropy(p: &[f64; STANDARD_RESIDUES], q: &[f64; STANDARD_RESIDUES]) -> f64 {
+    p.iter().zip(q).map(|(&a, &b)| if a > 0.0 { a * (a / b).ln() } else { 0.0 }).sum()
+}
+
+fn angle_degrees(p: &[f64; STANDARD_RESIDUES], q: &[f64; STANDARD_RESIDUES]) -> f64 {
+    let dot: f64 = p.iter().zip(q).map(|(a, b)| a * b).sum();
+    let norm = |v: &[f64; STANDARD_RESIDUES]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    (dot / (norm(p) * norm(q))).clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// `matrix` multiplied by `factor` on the standard residues, rounded.
+pub fn scale_matrix(matrix: &ScoreMatrix, factor: f64) -> ScoreMatrix {
+    let mut scaled = matrix.clone();
+    for i in 0..STANDARD_RESIDUES {
+        for j in 0..STANDARD_RESIDUES {
+            scaled.scores[i * MATRIX_SIZE + j] = (matrix.get(i, j) as f64 * factor).round() as i32;
+        }
+    }
+    scaled
+}
+
+/// Matrix adjusted so its target frequencies have row sums `p` and column sums `q`.
+/// `lambda` is the matrix's lambda under the background.
+pub fn adjust_matrix(
+    matrix: &ScoreMatrix,
+    lambda: f64,
+    p: &[f64; STANDARD_RESIDUES],
+    q: &[f64; STANDARD_RESIDUES],
+) -> ScoreMatrix {
+    const N: usize = STANDARD_RESIDUES;
+    let bg = background();
+    let mut target = [[0.0f64; N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            target[i][j] = bg[i] * bg[j] * (lambda * matrix.get(i, j) as f64).exp();
+        }
+    }
+    // Iterative proportional fitting: alternately match row and column marginals
+    for _ in 0..200 {
+        for (i, row) in target.iter_mut().enumerate() {
+            let sum: f64 = row.iter().sum();
+            row.iter_mut().for_each(|x| *x *= p[i] / sum);
+        }
+        let mut max_error: f64 = 0.0;
+        for j in 0..N {
+            let sum: f64 = target.iter().map(|row| row[j]).sum();
+            max_error = max_error.max((sum - q[j]).abs());
+            target.iter_mut().for_each(|row| row[j] *= q[j] / sum);
+        }
+        if max_error < 1e-10 {
+            break;
+        }
+    }
+    let mut adjusted = matrix.clone();
+    for i in 0..N {
+        for j in 0..N {
+            adjusted.scores[i * MATRIX_SIZE + j] = ((target[i][j] / (p[i] * q[j])).ln() / lambda).round() as i32;
+        }
+    }
+    adjusted
+}
+
+/// Per-query state for scoring each target with its own adjusted matrix.
+pub struct PairScoring {
+    base: Scoring,
+    mode: CompAdjust,
+    query: Composition,
+    query_freqs: [f64; STANDARD_RESIDUES],
+    /// Standard lambda of the base matrix; None when no adjustment applies
+    lambda: Option<f64>,
+}
+
+impl PairScoring {
+    pub fn new(base: Scoring, mode: CompAdjust, query: Composition) -> Self {
+        let lambda = match (&base.matrix, mode) {
+            (Some(matrix), m) if m != CompAdjust::None => standard_lambda(matrix),
+            _ => None,
+        };
+        Self { base, mode, query, query_freqs: query.frequencies(), lambda }
+    }
+
+    /// Scoring used for targets that get no adjusted matrix.
+    pub fn base(&self) -> &Scoring {
+        &self.base
+    }
+
+    /// Whether targets get their own scoring at all.
+    pub fn is_active(&self) -> bool {
+        self.lambda.is_some()
+    }
+
+    pub fn for_target(&self, target: &[u8]) -> Cow<'_, Scoring> {
+        if !self.is_active() {
+            return Cow::Borrowed(&self.base);
+        }
+        self.for_composition(&Composition::of(target))
+    }
+
+    pub fn for_composition(&self, target: &Composition) -> Cow<'_, Scoring> {
+        let (Some(matrix), Some(lambda)) = (self.base.matrix.as_deref(), self.lambda) else {
+            return Cow::Borrowed(&self.base);
+        };
+        let target_freqs = target.frequencies();
+        let full = match self.mode {
+            CompAdjust::Full => true,
+            CompAdjust::Conditional => {
+                let bg = background();
+                self.query.total as usize >= MIN_LENGTH
+                    && target.total as usize >= MIN_LENGTH
+                    && angle_degrees(&self.query_freqs, &target_freqs) <= MAX_ANGLE
+                    && relative_entropy(&self.query_freqs, &bg).max(relative_entropy(&target_freqs, &bg))
+                        >= MIN_RELATIVE_ENTROPY
+            }
+            _ => false,
+        };
+        let adjusted = if full {
+            adjust_matrix(matrix, lambda, &self.query_freqs, &target_freqs)
+        } else {
+            match matrix.lambda(&self.query_freqs, &target_freqs) {
+                Some(pair_lambda) => scale_matrix(matrix, pair_lambda / lambda),
+                None => return Cow::Borrowed(&self.base),
+            }
+        };
+        Cow::Owned(Scoring { matrix: Some(Box::new(adjusted)), ..self.base.clone() })
+    }
+}
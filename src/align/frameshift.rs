@@ -0,0 +1,161 @@
+// Frameshift-aware local alignment of one nucleotide strand against a protein, after
+// DIAMOND's `-F` mode (Zhang et al., 1997, three-frame alignment).
+//
+// The strand is seen through its three frame translations: the codon starting at strand
+// offset p is `frames[p % 3][p / 3]`. A cell (u, v) is "strand up to u, protein up to v".
+// A codon normally follows the previous one 3 nt later; a frameshift lets it follow
+// 2 nt later (a base read twice, shift -1) or 4 nt later (a base skipped, shift +1), at
+// a fixed penalty. Gaps are affine as in `Scoring`, one codon or one residue per step.
+use std::ops::Range;
+use crate::align::ungapped::Scoring;
+
+/// A local three-frame alignment; coordinates are 0-based, half-open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameshiftAlignment {
+    pub score: i32,
+    /// Aligned part of the strand, in strand offsets
+    pub strand_start: usize,
+    pub strand_end: usize,
+    pub protein_start: usize,
+    pub protein_end: usize,
+    /// (strand offset of the first codon after the shift, shift): +1 if a base was
+    /// skipped, -1 if one was read twice; in strand order
+    pub frameshifts: Vec<(usize, i8)>,
+}
+
+const NEG: i32 = i32::MIN / 4;
+
+// Traceback byte: bits 0-2 the H source, bit 3 E extended, bit 4 F extended
+const FROM_ZERO: u8 = 0;
+const FROM_CODON: u8 = 1;
+const FROM_SHIFT_BACK: u8 = 2;
+const FROM_SHIFT_FORWARD: u8 = 3;
+const FROM_E: u8 = 4;
+const FROM_F: u8 = 5;
+const E_EXTEND: u8 = 8;
+const F_EXTEND: u8 = 16;
+
+/// Best local alignment of `strand` (offsets into the strand the `frames` translate)
+/// against `protein[protein_range]`, with `penalty` (positive) per frameshift.
+pub fn align_frameshift(
+    frames: [&[u8]; 3],
+    strand: Range<usize>,
+    protein: &[u8],
+    protein_range: Range<usize>,
+    scoring: &Scoring,
+    penalty: i32,
+) -> FrameshiftAlignment {
+    let a = strand.start;
+    let rows = strand.len() + 1;
+    let target = &protein[protein_range.clone()];
+    let cols = target.len() + 1;
+    let codon = |p: usize| frames[p % 3].get(p / 3).copied();
+    let (open, extend) = (scoring.gap_open, scoring.gap_extend);
+
+    // Scores only ever look back 4 rows
+    let mut h = vec![vec![0i32; cols]; 5];
+    let mut f = vec![vec![NEG; cols]; 5];
+    let mut e = vec![NEG; cols];
+    let mut trace = vec![0u8; rows * cols];
+    let (mut best, mut best_u, mut best_v) = (0, 0, 0);
+
+    for u in 1..rows {
+        let r = u % 5;
+        // Codon ending at strand offset a + u, if the strand has one there
+        let aa = if u >= 3 { codon(a + u - 3) } else { None };
+        e[0] = NEG;
+        h[r][0] = 0;
+        f[r][0] = NEG;
+        for v in 1..cols {
+            let mut tb = 0u8;
+
+            let e_open = h[r][v - 1] + open + extend;
+            let e_ext = e[v - 1] + extend;
+            e[v] = if e_ext > e_open { tb |= E_EXTEND; e_ext } else { e_open };
+
+            f[r][v] = NEG;
+            if u >= 3 {
+                let f_open = h[(u - 3) % 5][v] + open + extend;
+                let f_ext = f[(u - 3) % 5][v] + extend;
+                f[r][v] = if f_ext > f_open { tb |= F_EXTEND; f_ext } else { f_open };
+            }
+
+            let mut score = 0;
+            let mut source = FROM_ZERO;
+            if let Some(aa) = aa {
+                let s = scoring.score(aa, target[v - 1]);
+                let candidates = [
+                    (FROM_CODON, h[(u - 3) % 5][v - 1] + s),
+                    (FROM_SHIFT_BACK, h[(u - 2) % 5][v - 1] + s - penalty),
+                    (FROM_SHIFT_FORWARD, if u >= 4 { h[(u - 4) % 5][v - 1] + s - penalty } else { NEG }),
+                ];
+                for (from, value) in candidates {
+                    if value > score {
+                        score = value;
+                        source = from;
+                    }
+                }
+            }
+            if e[v] > score {
+                score = e[v];
+                source = FROM_E;
+            }
+            if f[r][v] > score {
+                score = f[r][v];
+                source = FROM_F;
+            }
+            h[r][v] = score;
+            trace[u * cols + v] = tb | source;
+            if score > best {
+                (best, best_u, best_v) = (score, u, v);
+            }
+        }
+    }
+
+    // Walk back from the best cell to where the score started from zero
+    let (mut u, mut v) = (best_u, best_v);
+    let mut frameshifts = Vec::new();
+    let mut state = FROM_CODON;
+    loop {
+        let tb = trace[u * cols + v];
+        match state {
+            FROM_E => {
+                state = if tb & E_EXTEND != 0 { FROM_E } else { FROM_CODON };
+                v -= 1;
+                continue;
+            }
+            FROM_F => {
+                state = if tb & F_EXTEND != 0 { FROM_F } else { FROM_CODON };
+                u -= 3;
+                continue;
+            }
+            _ => {}
+        }
+        match tb & 7 {
+            FROM_ZERO => break,
+            FROM_CODON => u -= 3,
+            FROM_SHIFT_BACK => {
+                frameshifts.push((a + u - 3, -1));
+                u -= 2;
+            }
+            FROM_SHIFT_FORWARD => {
+                frameshifts.push((a + u - 3, 1));
+                u -= 4;
+            }
+            source => {
+                state = source;
+                continue;
+            }
+        }
+        v -= 1;
+    }
+    frameshifts.reverse();
+    FrameshiftAlignment {
+        score: best,
+        strand_start: a + u,
+        strand_end: a + best_u,
+        protein_start: protein_range.start + v,
+        protein_end: protein_range.start + best_v,
+        frameshifts,
+    }
+}
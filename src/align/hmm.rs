@@ -0,0 +1,558 @@
+// HMMER3 profile HMMs (hmmbuild's ASCII save files, e.g. Pfam-A.hmm), scored the way
+// hmmsearch scores them in its default local multihit mode:
+//   - SSV: the best ungapped diagonal segment of match scores. A cheap filter, run with
+//     AVX2 where available, whose score gets a P-value from the model's
+//     "STATS LOCAL MSV" Gumbel, as HMMER does.
+//   - Viterbi: the best Plan7 path, with traceback. Every pass through B..E is a
+//     domain. Sequence and domain P-values come from "STATS LOCAL VITERBI".
+// Scores are log-odds against HMMER's amino acid background and a null model of the
+// target's length, in bits. hmmsearch reports Forward scores with a composition bias
+// correction and domain envelopes, so its numbers differ somewhat from these.
+//
+// Only amino acid models are read. Residues outside the 20 standard ones score 0.
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::core::alphabet::{AA_TO_INT, INVALID_AA};
+use crate::core::fasta::open_input;
+
+/// Residues of an amino acid model, in file (and `INT_TO_AA`) order
+pub const HMM_RESIDUES: usize = 20;
+
+/// HMMER's amino acid background (Swiss-Prot 34 composition), ACDEFGHIKLMNPQRSTVWY
+const BACKGROUND: [f32; HMM_RESIDUES] = [
+    0.075520, 0.016973, 0.053029, 0.063204, 0.040762, 0.068448, 0.022406, 0.057284, 0.059398, 0.093399,
+    0.023569, 0.045293, 0.049262, 0.040231, 0.051573, 0.072214, 0.057454, 0.065252, 0.012513, 0.031985,
+];
+
+/// Units per nat of the SSV filter's 16-bit scores
+const SSV_SCALE: f32 = 8.0;
+/// nats HMMER's MSV filter charges for the N, C and J loops, ~L ln(L/(L+3))
+const SSV_LOOP_COST: f32 = 3.0;
+
+// Transitions out of a node, in file order
+const MM: usize = 0;
+const MI: usize = 1;
+const MD: usize = 2;
+const IM: usize = 3;
+const II: usize = 4;
+const DM: usize = 5;
+const DD: usize = 6;
+
+/// A Gumbel distribution of scores in bits, from a "STATS LOCAL" line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gumbel {
+    pub mu: f32,
+    pub lambda: f32,
+}
+
+impl Gumbel {
+    /// P(S >= bits)
+    pub fn pvalue(&self, bits: f32) -> f64 {
+        let y = (-(self.lambda as f64) * (bits as f64 - self.mu as f64)).exp();
+        -(-y).exp_m1()
+    }
+}
+
+/// A model hit on one target, from the Viterbi traceback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Domain {
+    /// Aligned target residues, 1-based inclusive
+    pub seq_from: usize,
+    pub seq_to: usize,
+    /// Aligned model nodes, 1-based inclusive
+    pub hmm_from: usize,
+    pub hmm_to: usize,
+    /// Score of the target with this domain alone, in bits
+    pub bits: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViterbiResult {
+    /// Score of the best path, in bits
+    pub bits: f32,
+    /// Domains of the best path, in target order
+    pub domains: Vec<Domain>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hmm {
+    pub name: String,
+    pub accession: Option<String>,
+    pub description: Option<String>,
+    /// Pfam gathering thresholds (sequence, domain), in bits
+    pub gathering: Option<(f32, f32)>,
+    pub msv: Gumbel,
+    pub viterbi: Gumbel,
+    /// Match emission log-odds in nats, per node (index k - 1) and residue
+    match_scores: Vec<[f32; HMM_RESIDUES]>,
+    /// ln probabilities of the transitions out of node k, 0..=M
+    transitions: Vec<[f32; 7]>,
+    /// ln B->Mk of local mode (index k - 1), weighted by match occupancy like HMMER's
+    entry: Vec<f32>,
+    /// `match_scores` for SSV: a row per residue plus one of zeros for the rest, each
+    /// padded to a multiple of 16 nodes
+    ssv: Vec<i16>,
+    ssv_width: usize,
+}
+
+fn residue_index(b: u8) -> usize {
+    match AA_TO_INT[b.to_ascii_uppercase() as usize] {
+        INVALID_AA => HMM_RESIDUES,
+        i => i as usize,
+    }
+}
+
+/// ln probabilities of the N, C and J loops and of leaving them, for a target of `len`
+fn length_transitions(len: usize) -> (f32, f32) {
+    let len = len as f32;
+    ((len / (len + 3.0)).ln(), (3.0 / (len + 3.0)).ln())
+}
+
+/// Null model score of a target of `len` (emissions cancel against the background)
+fn null_score(len: usize) -> f32 {
+    let len = len as f32;
+    len * (len / (len + 1.0)).ln() + (1.0 / (len + 1.0)).ln()
+}
+
+struct Lines<R> {
+    inner: std::io::Lines<R>,
+    number: usize,
+    source: String,
+}
+
+impl<R: BufRead> Lines<R> {
+    /// Next non-blank line, trimmed
+    fn next(&mut self) -> Result<Option<String>> {
+        for line in self.inner.by_ref() {
+            self.number += 1;
+            let line = line.with_context(|| format!("Failed to read {}", self.source))?;
+            if !line.trim().is_empty() {
+                return Ok(Some(line.trim().to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<String> {
+        match self.next()? {
+            Some(line) => Ok(line),
+            None => bail!("{}: unexpected end of file, expected {}", self.source, what),
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> anyhow::Error {
+        anyhow::anyhow!("{}:{}: {}", self.source, self.number, message)
+    }
+
+    /// `count` -ln probabilities ('*' is probability 0) as ln probabilities
+    fn log_probs<'a>(&self, fields: impl Iterator<Item = &'a str>, count: usize) -> Result<Vec<f32>> {
+        let values = fields.take(count)
+            .map(|f| match f {
+                "*" => Ok(f32::NEG_INFINITY),
+                f => f.parse::<f32>().map(|v| -v).map_err(|_| self.error(format!("invalid value {:?}", f))),
+            })
+            .collect::<Result<Vec<f32>>>()?;
+        if values.len() != count {
+            return Err(self.error(format!("expected {} values", count)));
+        }
+        Ok(values)
+    }
+}
+
+/// Models of a HMMER3 file, read one at a time.
+pub struct HmmReader<R> {
+    lines: Lines<R>,
+}
+
+impl<R: BufRead> HmmReader<R> {
+    pub fn new(reader: R, source: &str) -> Self {
+        Self { lines: Lines { inner: reader.lines(), number: 0, source: source.to_string() } }
+    }
+}
+
+impl<R: BufRead> Iterator for HmmReader<R> {
+    type Item = Result<Hmm>;
+
+    fn next(&mut self) -> Option<Result<Hmm>> {
+        match self.lines.next() {
+            Ok(None) => None,
+            Ok(Some(header)) if !header.starts_with("HMMER3") => {
+                Some(Err(self.lines.error("expected a HMMER3 header (HMMER2 files need hmmconvert)")))
+            }
+            Ok(Some(_)) => Some(Hmm::read_model(&mut self.lines)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl Hmm {
+    /// Opens a plain or compressed HMMER3 file, or stdin for "-".
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<HmmReader<BufReader<Box<dyn Read>>>> {
+        let path = path.as_ref();
+        Ok(HmmReader::new(BufReader::new(open_input(path)?), &path.display().to_string()))
+    }
+
+    pub fn read_all<R: BufRead>(reader: R, source: &str) -> Result<Vec<Self>> {
+        HmmReader::new(reader, source).collect()
+    }
+
+    fn read_model<R: BufRead>(lines: &mut Lines<R>) -> Result<Self> {
+        let mut name = None;
+        let mut accession = None;
+        let mut description = None;
+        let mut len = None;
+        let mut gathering = None;
+        let mut msv = None;
+        let mut viterbi = None;
+        loop {
+            let line = lines.expect("the HMM section")?;
+            let (tag, rest) = line.split_once(char::is_whitespace).unwrap_or((&line, ""));
+            let rest = rest.trim();
+            let float = |s: &str| s.trim_end_matches(';').parse::<f32>().map_err(|_| lines.error(format!("invalid number {:?}", s)));
+            match tag {
+                "NAME" => name = Some(rest.to_string()),
+                "ACC" => accession = Some(rest.to_string()),
+                "DESC" => description = Some(rest.to_string()),
+                "LENG" => len = Some(rest.parse::<usize>().map_err(|_| lines.error("invalid LENG"))?),
+                "ALPH" if !rest.eq_ignore_ascii_case("amino") => {
+                    return Err(lines.error(format!("{} models are not supported, only amino", rest)));
+                }
+                "GA" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    if fields.len() < 2 {
+                        return Err(lines.error("GA needs a sequence and a domain threshold"));
+                    }
+                    gathering = Some((float(fields[0])?, float(fields[1])?));
+                }
+                "STATS" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    if fields.len() < 4 {
+                        return Err(lines.error("STATS needs a mode, a distribution and two parameters"));
+                    }
+                    let gumbel = Gumbel { mu: float(fields[2])?, lambda: float(fields[3])? };
+                    match fields[1] {
+                        "MSV" => msv = Some(gumbel),
+                        "VITERBI" => viterbi = Some(gumbel),
+                        _ => {}
+                    }
+                }
+                "HMM" => break,
+                _ => {}
+            }
+        }
+        let name = name.ok_or_else(|| lines.error("model has no NAME"))?;
+        let len = len.filter(|&m| m > 0).ok_or_else(|| lines.error(format!("{} has no LENG", name)))?;
+        let (Some(msv), Some(viterbi)) = (msv, viterbi) else {
+            return Err(lines.error(format!("{} has no STATS LOCAL MSV/VITERBI lines; calibrate it with hmmbuild", name)));
+        };
+
+        // Transition names, then an optional COMPO line and node 0's insert emissions
+        lines.expect("transition names")?;
+        if lines.expect("node 0")?.starts_with("COMPO") {
+            lines.expect("node 0 insert emissions")?;
+        }
+        let node0 = lines.expect("node 0 transitions")?;
+        let mut transitions = vec![lines.log_probs(node0.split_whitespace(), 7)?.try_into().unwrap()];
+        let mut match_scores = Vec::with_capacity(len);
+        for k in 1..=len {
+            let line = lines.expect("a match emission line")?;
+            let mut fields = line.split_whitespace();
+            if fields.next() != Some(k.to_string().as_str()) {
+                return Err(lines.error(format!("expected node {} of {}", k, name)));
+            }
+            let emissions = lines.log_probs(fields, HMM_RESIDUES)?;
+            let mut scores = [0.0; HMM_RESIDUES];
+            for (a, score) in scores.iter_mut().enumerate() {
+                *score = emissions[a] - BACKGROUND[a].ln();
+            }
+            match_scores.push(scores);
+            lines.expect("an insert emission line")?;
+            let line = lines.expect("a transition line")?;
+            let values = lines.log_probs(line.split_whitespace(), 7)?;
+            transitions.push(values.try_into().unwrap());
+        }
+        if lines.expect("//")? != "//" {
+            return Err(lines.error(format!("expected // after the {} nodes of {}", len, name)));
+        }
+
+        let mut hmm = Self {
+            name,
+            accession,
+            description,
+            gathering,
+            msv,
+            viterbi,
+            match_scores,
+            transitions,
+            entry: Vec::new(),
+            ssv: Vec::new(),
+            ssv_width: 0,
+        };
+        hmm.entry = hmm.local_entry();
+        hmm.build_ssv();
+        Ok(hmm)
+    }
+
+    /// Number of match nodes (M)
+    pub fn len(&self) -> usize {
+        self.match_scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.match_scores.is_empty()
+    }
+
+    /// Match emission log-odds of `residue` at node `k` (1-based), in nats
+    pub fn match_score(&self, k: usize, residue: u8) -> f32 {
+        match residue_index(residue) {
+            HMM_RESIDUES => 0.0,
+            a => self.match_scores[k - 1][a],
+        }
+    }
+
+    /// HMMER's local entry: B->Mk proportional to the occupancy of Mk, spread over the
+    /// M - k + 1 possible ends
+    fn local_entry(&self) -> Vec<f32> {
+        let m = self.len();
+        let t = |k: usize, i: usize| self.transitions[k][i].exp();
+        let mut occupancy = vec![0.0f32; m + 1];
+        occupancy[1] = t(0, MI) + t(0, MM);
+        for k in 2..=m {
+            occupancy[k] = occupancy[k - 1] * (t(k - 1, MM) + t(k - 1, MI)) + (1.0 - occupancy[k - 1]) * t(k - 1, DM);
+        }
+        let z: f32 = (1..=m).map(|k| occupancy[k] * (m - k + 1) as f32).sum();
+        (1..=m).map(|k| (occupancy[k] / z).ln()).collect()
+    }
+
+    fn build_ssv(&mut self) {
+        let width = self.len().div_ceil(16) * 16;
+        let mut ssv = vec![i16::MIN; (HMM_RESIDUES + 1) * width];
+        for (k, scores) in self.match_scores.iter().enumerate() {
+            for (a, &score) in scores.iter().enumerate() {
+                let scaled = (score * SSV_SCALE).round();
+                ssv[a * width + k] = scaled.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+            ssv[HMM_RESIDUES * width + k] = 0;
+        }
+        self.ssv = ssv;
+        self.ssv_width = width;
+    }
+
+    /// SSV score of `seq` in bits: the best ungapped segment with MSV's entry, exit and
+    /// approximate loop costs.
+    pub fn ssv_bits(&self, seq: &[u8]) -> f32 {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            return self.segment_bits(seq.len(), unsafe { ssv_avx2(&self.ssv, self.ssv_width, seq) });
+        }
+        self.ssv_bits_scalar(seq)
+    }
+
+    /// `ssv_bits` without SIMD.
+    pub fn ssv_bits_scalar(&self, seq: &[u8]) -> f32 {
+        self.segment_bits(seq.len(), ssv_scalar(&self.ssv, self.ssv_width, seq))
+    }
+
+    fn segment_bits(&self, len: usize, segment: i16) -> f32 {
+        let m = self.len() as f32;
+        let (_, leave) = length_transitions(len);
+        let nats = segment as f32 / SSV_SCALE + 2.0 * leave + (2.0 / (m * (m + 1.0))).ln() + 0.5f32.ln() - SSV_LOOP_COST;
+        (nats - null_score(len)) / std::f32::consts::LN_2
+    }
+
+    /// Local multihit Viterbi of `seq` with its domains.
+    pub fn viterbi(&self, seq: &[u8]) -> ViterbiResult {
+        let (m, l) = (self.len(), seq.len());
+        if l == 0 {
+            return ViterbiResult { bits: f32::NEG_INFINITY, domains: Vec::new() };
+        }
+        let (stay, leave) = length_transitions(l);
+        let half = 0.5f32.ln();
+        let neg = f32::NEG_INFINITY;
+        let w = m + 1;
+        // Per cell: the M state's source (0 M, 1 I, 2 D, 3 B) in bits 0-1, then a bit for
+        // I coming from I and one for D coming from D
+        let mut traceback = vec![0u8; (l + 1) * w];
+        let (mut prev_m, mut prev_i, mut prev_d) = (vec![neg; w], vec![neg; w], vec![neg; w]);
+        let (mut cur_m, mut cur_i, mut cur_d) = (vec![neg; w], vec![neg; w], vec![neg; w]);
+        let mut e_row = vec![neg; l + 1];
+        let mut b_row = vec![neg; l + 1];
+        // E's source node and whether it is a D state; J, C and B left their loop or N
+        let mut e_from = vec![(0usize, false); l + 1];
+        let mut j_from_e = vec![false; l + 1];
+        let mut c_from_e = vec![false; l + 1];
+        let mut b_from_j = vec![false; l + 1];
+        let (mut n, mut j, mut c) = (0.0f32, neg, neg);
+        b_row[0] = leave;
+
+        for i in 1..=l {
+            let x = residue_index(seq[i - 1]);
+            let b_prev = b_row[i - 1];
+            let row = &mut traceback[i * w..(i + 1) * w];
+            let mut e = neg;
+            for k in 1..=m {
+                let from = &self.transitions[k - 1];
+                let to = &self.transitions[k];
+                let sources = [
+                    prev_m[k - 1] + from[MM],
+                    prev_i[k - 1] + from[IM],
+                    prev_d[k - 1] + from[DM],
+                    b_prev + self.entry[k - 1],
+                ];
+                let mut src = 0;
+                for (s, &v) in sources.iter().enumerate().skip(1) {
+                    if v > sources[src] {
+                        src = s;
+                    }
+                }
+                let emission = if x == HMM_RESIDUES { 0.0 } else { self.match_scores[k - 1][x] };
+                cur_m[k] = sources[src] + emission;
+                let mut bits = src as u8;
+
+                cur_i[k] = neg;
+                if k < m {
+                    let (from_m, from_i) = (prev_m[k] + to[MI], prev_i[k] + to[II]);
+                    cur_i[k] = from_m.max(from_i);
+                    bits |= ((from_i > from_m) as u8) << 2;
+                }
+                cur_d[k] = neg;
+                if k > 1 {
+                    let (from_m, from_d) = (cur_m[k - 1] + from[MD], cur_d[k - 1] + from[DD]);
+                    cur_d[k] = from_m.max(from_d);
+                    bits |= ((from_d > from_m) as u8) << 3;
+                }
+                row[k] = bits;
+                if cur_m[k] > e {
+                    e = cur_m[k];
+                    e_from[i] = (k, false);
+                }
+                if cur_d[k] > e {
+                    e = cur_d[k];
+                    e_from[i] = (k, true);
+                }
+            }
+            e_row[i] = e;
+            j_from_e[i] = e + half > j + stay;
+            j = (j + stay).max(e + half);
+            c_from_e[i] = e + half > c + stay;
+            c = (c + stay).max(e + half);
+            n += stay;
+            b_from_j[i] = j + leave > n + leave;
+            b_row[i] = (n + leave).max(j + leave);
+            std::mem::swap(&mut prev_m, &mut cur_m);
+            std::mem::swap(&mut prev_i, &mut cur_i);
+            std::mem::swap(&mut prev_d, &mut cur_d);
+        }
+        let null = null_score(l);
+        let bits = (c + leave - null) / std::f32::consts::LN_2;
+
+        #[derive(Clone, Copy)]
+        enum State { C, E, M, I, D, B, J }
+        let mut domains = Vec::new();
+        let (mut state, mut i, mut k) = (State::C, l, 0);
+        let (mut seq_to, mut hmm_to) = (0, None);
+        loop {
+            state = match state {
+                State::C if c_from_e[i] => State::E,
+                State::C => {
+                    i -= 1;
+                    State::C
+                }
+                State::E => {
+                    let (node, from_d) = e_from[i];
+                    (k, seq_to, hmm_to) = (node, i, None);
+                    if from_d { State::D } else { State::M }
+                }
+                State::M => {
+                    hmm_to.get_or_insert(k);
+                    let src = traceback[i * w + k] & 3;
+                    if src == 3 {
+                        let segment = e_row[seq_to] - b_row[i - 1];
+                        let outside = (l - (seq_to - i + 1)) as f32 * stay;
+                        let alone = segment + 2.0 * leave + half + outside;
+                        domains.push(Domain {
+                            seq_from: i,
+                            seq_to,
+                            hmm_from: k,
+                            hmm_to: hmm_to.unwrap(),
+                            bits: (alone - null) / std::f32::consts::LN_2,
+                        });
+                        i -= 1;
+                        State::B
+                    } else {
+                        i -= 1;
+                        k -= 1;
+                        [State::M, State::I, State::D][src as usize]
+                    }
+                }
+                State::I => {
+                    i -= 1;
+                    if traceback[(i + 1) * w + k] & 4 != 0 { State::I } else { State::M }
+                }
+                State::D => {
+                    k -= 1;
+                    if traceback[i * w + k + 1] & 8 != 0 { State::D } else { State::M }
+                }
+                State::B if b_from_j[i] => State::J,
+                State::B => break,
+                State::J if j_from_e[i] => State::E,
+                State::J => {
+                    i -= 1;
+                    State::J
+                }
+            };
+        }
+        domains.reverse();
+        ViterbiResult { bits, domains }
+    }
+}
+
+/// Best ungapped segment over all diagonals, in `SSV_SCALE` units (saturating).
+fn ssv_scalar(scores: &[i16], width: usize, seq: &[u8]) -> i16 {
+    let mut dp = vec![0i16; width];
+    let mut best = 0;
+    for &b in seq {
+        let row = &scores[residue_index(b) * width..][..width];
+        // Backwards, so dp[k - 1] still holds the previous residue's value
+        for k in (0..width).rev() {
+            let diagonal = if k == 0 { 0 } else { dp[k - 1] };
+            dp[k] = diagonal.saturating_add(row[k]).max(0);
+            best = best.max(dp[k]);
+        }
+    }
+    best
+}
+
+/// `ssv_scalar` 16 nodes at a time. Moving one node down the diagonal shifts every
+/// vector up a lane, the top lane carrying into the next vector.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn ssv_avx2(scores: &[i16], width: usize, seq: &[u8]) -> i16 {
+    let chunks = width / 16;
+    let zero = _mm256_setzero_si256();
+    let mut dp = vec![zero; chunks];
+    let mut best = zero;
+    for &b in seq {
+        let row = scores[residue_index(b) * width..][..width].as_ptr();
+        let mut carry = zero;
+        for (c, cell) in dp.iter_mut().enumerate() {
+            let old = *cell;
+            // [carry[15], old[0..15]]: lanes 7 and 15 cross the 128-bit halves
+            let halves = _mm256_permute2x128_si256(old, carry, 0x03);
+            let shifted = _mm256_alignr_epi8(old, halves, 14);
+            carry = old;
+            let s = unsafe { _mm256_loadu_si256(row.add(16 * c) as *const __m256i) };
+            let v = _mm256_max_epi16(_mm256_adds_epi16(shifted, s), zero);
+            *cell = v;
+            best = _mm256_max_epi16(best, v);
+        }
+    }
+    let mut lanes = [0i16; 16];
+    unsafe { _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, best) };
+    lanes.into_iter().max().unwrap()
+}
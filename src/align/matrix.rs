@@ -0,0 +1,85 @@
+// Substitution matrices and their Karlin-Altschul lambda.
+//
+// Matrices are 24x24 in `BLOSUM62` order: the 20 standard residues
+// (ARNDCQEGHILKMFPSTWYV) followed by B, Z, X and *.
+use crate::core::alphabet::{matrix_index, BLOSUM62};
+
+pub const MATRIX_SIZE: usize = 24;
+pub const STANDARD_RESIDUES: usize = 20;
+/// The standard residues in matrix order
+pub const RESIDUES: &[u8; STANDARD_RESIDUES] = b"ARNDCQEGHILKMFPSTWYV";
+
+/// Robinson & Robinson (1991) background frequencies, the BLAST standard, in matrix order.
+pub const BACKGROUND: [f64; STANDARD_RESIDUES] = [
+    0.07805, 0.05129, 0.04487, 0.05364, 0.01925, 0.04264, 0.06295, 0.07377, 0.02199, 0.05142,
+    0.09019, 0.05744, 0.02243, 0.03856, 0.05203, 0.07120, 0.05841, 0.01330, 0.03216, 0.06441,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreMatrix {
+    pub scores: [i32; MATRIX_SIZE * MATRIX_SIZE],
+}
+
+impl ScoreMatrix {
+    pub fn blosum62() -> Self {
+        let mut scores = [0; MATRIX_SIZE * MATRIX_SIZE];
+        for (s, &b) in scores.iter_mut().zip(BLOSUM62.iter()) {
+            *s = b as i32;
+        }
+        Self { scores }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, i: usize, j: usize) -> i32 {
+        self.scores[i * MATRIX_SIZE + j]
+    }
+
+    /// Score of two residue bytes; case-insensitive, unknown bytes score as X.
+    #[inline(always)]
+    pub fn score(&self, a: u8, b: u8) -> i32 {
+        self.get(matrix_index(a), matrix_index(b))
+    }
+
+    /// Ungapped lambda for residue frequencies `p` (rows) and `q` (columns):
+    /// the positive root of sum_ij p_i q_j exp(lambda s_ij) = 1.
+    /// None if the expected score is not negative or no score is positive.
+    pub fn lambda(&self, p: &[f64; STANDARD_RESIDUES], q: &[f64; STANDARD_RESIDUES]) -> Option<f64> {
+        let f = |lambda: f64| {
+            let mut sum = 0.0;
+            for (i, &pi) in p.iter().enumerate() {
+                for (j, &qj) in q.iter().enumerate() {
+                    sum += pi * qj * (lambda * self.get(i, j) as f64).exp();
+                }
+            }
+            sum - 1.0
+        };
+        let mut expected = 0.0;
+        let mut any_positive = false;
+        for (i, &pi) in p.iter().enumerate() {
+            for (j, &qj) in q.iter().enumerate() {
+                expected += pi * qj * self.get(i, j) as f64;
+                any_positive |= pi * qj > 0.0 && self.get(i, j) > 0;
+            }
+        }
+        if expected >= 0.0 || !any_positive {
+            return None;
+        }
+        // f(0) = 0 and f dips below zero before rising: bracket the positive root, then bisect
+        let mut hi = 0.5;
+        while f(hi) <= 0.0 {
+            hi *= 2.0;
+            if hi > 1e3 {
+                return None;
+            }
+        }
+        let mut lo = hi / 2.0;
+        while f(lo) > 0.0 && lo > 1e-9 {
+            lo /= 2.0;
+        }
+        for _ in 0..60 {
+            let mid = 0.5 * (lo + hi);
+            if f(mid) > 0.0 { hi = mid } else { lo = mid }
+        }
+        Some(0.5 * (lo + hi))
+    }
+}
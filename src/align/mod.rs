@@ -1,3 +1,10 @@
 pub mod ungapped;
 pub mod smith_waterman;
-pub mod simd;
\ No newline at end of file
+pub mod simd;
+pub mod matrix;
+pub mod composition;
+pub mod frameshift;
+pub mod profile;
+pub mod msa;
+pub mod hmm;
+pub mod swipe;
@@ -0,0 +1,187 @@
+// Multiple sequence alignments used as queries.
+//
+// Three formats, told apart by content:
+//   - Stockholm: starts with "# STOCKHOLM"; "name sequence" lines, possibly in
+//     interleaved blocks, up to "//". Markup lines ("#=GF", "#=GC", ...) are skipped.
+//   - Aligned FASTA: every row has the same length and no lowercase letters.
+//   - A3M (HH-suite): a leading "#A3M#" line, lowercase letters or rows of different
+//     lengths. Lowercase letters are insertions relative to the match columns and are
+//     dropped, as are '.'; what remains must line up. Leading '#' lines are skipped.
+// Gaps may be written '-', '.' or '~' and are stored as '-'; residues are upper-cased.
+//
+// Columns enter the profile when at least half of the rows spanning them have a
+// residue there (a row spans the columns from its first to its last residue, so
+// fragments do not turn their missing ends into gaps). The rest are insert columns.
+use std::io::{Cursor, Read};
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use rustc_hash::FxHashMap;
+
+use crate::align::matrix::{RESIDUES, STANDARD_RESIDUES};
+use crate::align::profile::GAP;
+use crate::core::alphabet::matrix_index;
+use crate::core::fasta::{open_input, FastaReader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsaFormat {
+    AlignedFasta,
+    A3m,
+    Stockholm,
+}
+
+#[derive(Debug, Clone)]
+pub struct Msa {
+    pub format: MsaFormat,
+    pub names: Vec<String>,
+    /// Aligned rows, all `columns()` long
+    pub rows: Vec<Vec<u8>>,
+}
+
+/// An MSA reduced to its profile columns, ready for `Pssm::from_rows`.
+#[derive(Debug, Clone)]
+pub struct MsaProfile {
+    /// Most common standard residue of each profile column
+    pub consensus: Vec<u8>,
+    /// Per sequence and profile column: residue, `GAP`, or None outside the row's span
+    pub rows: Vec<Vec<Option<u8>>>,
+    /// 0-based MSA column of each profile column
+    pub columns: Vec<usize>,
+}
+
+fn is_gap(b: u8) -> bool {
+    matches!(b, b'-' | b'.' | b'~')
+}
+
+fn normalize(b: u8) -> u8 {
+    if is_gap(b) { GAP } else { b.to_ascii_uppercase() }
+}
+
+impl Msa {
+    /// Reads a plain or compressed alignment file, or stdin for "-".
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let source = path.display().to_string();
+        let mut text = Vec::new();
+        open_input(path)?.read_to_end(&mut text).with_context(|| format!("Failed to read {:?}", path))?;
+        let msa = if text.trim_ascii_start().starts_with(b"# STOCKHOLM") {
+            Self::parse_stockholm(&text, &source)?
+        } else {
+            Self::parse_fasta(&text, &source)?
+        };
+        if msa.rows.is_empty() || msa.columns() == 0 {
+            bail!("{}: no aligned sequences", source);
+        }
+        Ok(msa)
+    }
+
+    pub fn parse_stockholm(text: &[u8], source: &str) -> Result<Self> {
+        let mut names: Vec<String> = Vec::new();
+        let mut rows: Vec<Vec<u8>> = Vec::new();
+        let mut index: FxHashMap<String, usize> = FxHashMap::default();
+        for line in text.split(|&b| b == b'\n') {
+            let line = line.trim_ascii();
+            if line.starts_with(b"//") {
+                break;
+            }
+            if line.is_empty() || line[0] == b'#' {
+                continue;
+            }
+            let line = String::from_utf8_lossy(line);
+            let mut fields = line.split_whitespace();
+            let (Some(name), Some(seq)) = (fields.next(), fields.next()) else { continue };
+            let i = *index.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                rows.push(Vec::new());
+                rows.len() - 1
+            });
+            rows[i].extend(seq.bytes().map(normalize));
+        }
+        check_lengths(&names, &rows, source)?;
+        Ok(Self { format: MsaFormat::Stockholm, names, rows })
+    }
+
+    pub fn parse_fasta(text: &[u8], source: &str) -> Result<Self> {
+        // Skip A3M's leading '#' lines; the FASTA reader only knows ';' comments
+        let mut start = 0;
+        let mut marked = false;
+        for line in text.split_inclusive(|&b| b == b'\n') {
+            let line_text = line.trim_ascii_start();
+            if !line_text.starts_with(b"#") {
+                break;
+            }
+            marked |= line_text.starts_with(b"#A3M#");
+            start += line.len();
+        }
+        let mut reader = FastaReader::from_reader(Box::new(Cursor::new(text[start..].to_vec())), source);
+        let mut names = Vec::new();
+        let mut rows = Vec::new();
+        let mut seq = Vec::new();
+        while let Some(header) = reader.read_record(&mut seq)? {
+            names.push(header.split_whitespace().next().unwrap_or("unknown").to_string());
+            rows.push(std::mem::take(&mut seq));
+        }
+        let aligned = rows.iter().all(|r| r.len() == rows.first().map_or(0, Vec::len))
+            && !rows.iter().flatten().any(u8::is_ascii_lowercase);
+        let format = if aligned && !marked {
+            MsaFormat::AlignedFasta
+        } else {
+            for row in rows.iter_mut() {
+                row.retain(|&b| !(b.is_ascii_lowercase() || b == b'.'));
+            }
+            MsaFormat::A3m
+        };
+        for row in rows.iter_mut() {
+            row.iter_mut().for_each(|b| *b = normalize(*b));
+        }
+        check_lengths(&names, &rows, source)?;
+        Ok(Self { format, names, rows })
+    }
+
+    pub fn columns(&self) -> usize {
+        self.rows.first().map_or(0, Vec::len)
+    }
+
+    pub fn profile(&self) -> MsaProfile {
+        let spans: Vec<Option<(usize, usize)>> = self.rows.iter()
+            .map(|row| {
+                let first = row.iter().position(|&b| b != GAP)?;
+                let last = row.iter().rposition(|&b| b != GAP)?;
+                Some((first, last))
+            })
+            .collect();
+        let spans_column = |span: &Option<(usize, usize)>, c: usize| matches!(span, Some((a, b)) if (*a..=*b).contains(&c));
+        let mut profile = MsaProfile { consensus: Vec::new(), rows: vec![Vec::new(); self.rows.len()], columns: Vec::new() };
+        for c in 0..self.columns() {
+            let residues = self.rows.iter().filter(|row| row[c] != GAP).count();
+            if residues == 0 || 2 * residues < spans.iter().filter(|s| spans_column(s, c)).count() {
+                continue;
+            }
+            let mut counts = [0usize; STANDARD_RESIDUES];
+            for row in &self.rows {
+                if matrix_index(row[c]) < STANDARD_RESIDUES {
+                    counts[matrix_index(row[c])] += 1;
+                }
+            }
+            let consensus = match (0..STANDARD_RESIDUES).filter(|&i| counts[i] > 0).max_by_key(|&i| (counts[i], std::cmp::Reverse(i))) {
+                Some(i) => RESIDUES[i],
+                None => *self.rows.iter().map(|row| &row[c]).find(|&&b| b != GAP).unwrap(),
+            };
+            profile.consensus.push(consensus);
+            profile.columns.push(c);
+            for ((out, row), span) in profile.rows.iter_mut().zip(&self.rows).zip(&spans) {
+                out.push(spans_column(span, c).then_some(row[c]));
+            }
+        }
+        profile
+    }
+}
+
+fn check_lengths(names: &[String], rows: &[Vec<u8>], source: &str) -> Result<()> {
+    let Some(first) = rows.first() else { return Ok(()) };
+    for (name, row) in names.iter().zip(rows) {
+        if row.len() != first.len() {
+            bail!("{}: {} has {} aligned columns, {} has {}", source, name, row.len(), names[0], first.len());
+        }
+    }
+    Ok(())
+}
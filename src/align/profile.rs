@@ -0,0 +1,385 @@
+// Position-specific scoring matrices (PSSMs) for iterative profile search, after
+// PSI-BLAST (Altschul et al., 1997).
+//
+// A PSSM has one row of scores per query position. Before any hits are known, each row
+// is the query residue's row of BLOSUM62. After a round, the hits that pass the inclusion
+// threshold are stacked under the query as a query-anchored alignment:
+//   - Sequence weights are Henikoff & Henikoff (1994) position-based weights, so a
+//     cluster of near-identical hits counts about as much as one sequence.
+//   - Each column's weighted residue frequencies f are mixed with pseudocounts
+//     g_i = sum_j f_j q_ij / p_j from the BLOSUM62 target frequencies q_ij, as
+//     Q_i = (alpha f_i + beta g_i) / (alpha + beta), with beta = 10 and alpha one less
+//     than the mean number of distinct residues per column.
+//   - Scores are ln(Q_i / p_i) / lambda in BLOSUM62 units, so the matrix's gap costs and
+//     gapped Karlin-Altschul parameters still apply.
+// Columns no hit reaches keep their BLOSUM62 row. B, Z, X and * always score as in
+// BLOSUM62. A multiple alignment given as the query is weighted and mixed the same
+// way, with all its sequences on an equal footing (`from_rows`).
+use std::io::{self, Write};
+use crate::align::composition::{background, standard_lambda};
+use crate::align::matrix::{ScoreMatrix, MATRIX_SIZE, RESIDUES, STANDARD_RESIDUES};
+use crate::align::ungapped::ExtensionResult;
+use crate::core::alphabet::{encode_kmer, matrix_index, INT_TO_AA};
+
+/// Gapped Karlin-Altschul parameters for BLOSUM62 with 11/1 gaps (BLAST's table)
+pub const GAPPED_LAMBDA: f64 = 0.267;
+pub const GAPPED_K: f64 = 0.041;
+/// Pseudocount weight
+const BETA: f64 = 10.0;
+/// Marks a gap in an alignment row
+pub const GAP: u8 = b'-';
+
+/// Expected number of chance hits scoring at least `score` (BLOSUM62, gaps 11/1).
+pub fn evalue(score: i32, query_len: usize, db_residues: usize) -> f64 {
+    GAPPED_K * query_len as f64 * db_residues as f64 * (-GAPPED_LAMBDA * score as f64).exp()
+}
+
+#[derive(Debug, Clone)]
+pub struct Pssm {
+    /// Query the rows belong to (soft-masked residues stay lowercase)
+    pub query: Vec<u8>,
+    /// One row per query position, indexed by `matrix_index`
+    pub scores: Vec<[i32; MATRIX_SIZE]>,
+    /// Weighted observed frequencies of the standard residues per position
+    pub frequencies: Vec<[f64; STANDARD_RESIDUES]>,
+    /// Relative entropy of each row's target frequencies to the background, in bits
+    pub information: Vec<f64>,
+    /// alpha / (alpha + beta) per position: 0 where the row is plain BLOSUM62
+    pub relative_weight: Vec<f64>,
+}
+
+impl Pssm {
+    /// The query's BLOSUM62 rows: a first-round profile.
+    pub fn from_query(query: &[u8]) -> Self {
+        Self::build(query, &[])
+    }
+
+    /// Profile of `query` and the hits aligned to it. Each row of `alignment` has one
+    /// entry per query position: the hit's residue, `GAP`, or None outside its alignment.
+    pub fn build(query: &[u8], alignment: &[Vec<Option<u8>>]) -> Self {
+        let mut rows = Vec::with_capacity(alignment.len() + 1);
+        rows.push(query.iter().map(|&b| Some(b)).collect());
+        rows.extend_from_slice(alignment);
+        Self::from_rows(query, &rows)
+    }
+
+    /// Profile of the columns of `rows`, in the same form as for `build` but with no
+    /// sequence singled out. `query` has one residue per column and supplies the
+    /// BLOSUM62 row where fewer than two rows have a standard residue.
+    pub fn from_rows(query: &[u8], rows: &[Vec<Option<u8>>]) -> Self {
+        let matrix = ScoreMatrix::blosum62();
+        let lambda = standard_lambda(&matrix).expect("BLOSUM62 has a lambda");
+        let bg = background();
+        let symbol = |b: u8| if b == GAP { MATRIX_SIZE } else { matrix_index(b) };
+        let column = |c: usize| rows.iter().filter_map(move |row| row[c]);
+
+        // Position-based weights: in each column a sequence gets 1 / (r * n), where r is
+        // the number of distinct symbols there and n how many sequences share its symbol
+        let mut weights = vec![0.0; rows.len()];
+        let mut distinct_sum = 0.0;
+        let mut covered = 0;
+        for c in 0..query.len() {
+            if column(c).nth(1).is_none() {
+                continue;
+            }
+            let mut counts = [0usize; MATRIX_SIZE + 1];
+            column(c).for_each(|b| counts[symbol(b)] += 1);
+            let distinct = counts.iter().filter(|&&n| n > 0).count();
+            distinct_sum += distinct as f64;
+            covered += 1;
+            for (w, row) in weights.iter_mut().zip(rows) {
+                if let Some(b) = row[c] {
+                    *w += 1.0 / (distinct * counts[symbol(b)]) as f64;
+                }
+            }
+        }
+        let alpha = if covered > 0 { distinct_sum / covered as f64 - 1.0 } else { 0.0 };
+
+        let mut pssm = Self {
+            query: query.to_vec(),
+            scores: Vec::with_capacity(query.len()),
+            frequencies: Vec::with_capacity(query.len()),
+            information: Vec::with_capacity(query.len()),
+            relative_weight: Vec::with_capacity(query.len()),
+        };
+        for (c, &q) in query.iter().enumerate() {
+            let qi = matrix_index(q);
+            let mut row = [0; MATRIX_SIZE];
+            for (j, s) in row.iter_mut().enumerate() {
+                *s = matrix.get(qi, j);
+            }
+            let mut f = [0.0; STANDARD_RESIDUES];
+            for (w, row) in weights.iter().zip(rows) {
+                if let Some(b) = row[c] && matrix_index(b) < STANDARD_RESIDUES {
+                    f[matrix_index(b)] += w;
+                }
+            }
+            let total: f64 = f.iter().sum();
+            let aligned = column(c).filter(|&b| matrix_index(b) < STANDARD_RESIDUES).nth(1).is_some();
+            let mut relative_weight = 0.0;
+            let mut target = [0.0; STANDARD_RESIDUES];
+            if total > 0.0 {
+                f.iter_mut().for_each(|x| *x /= total);
+            }
+            if aligned && alpha > 0.0 {
+                relative_weight = alpha / (alpha + BETA);
+                for (i, t) in target.iter_mut().enumerate() {
+                    // g_i = sum_j f_j q_ij / p_j, with q_ij / p_j = p_i exp(lambda s_ij)
+                    let g: f64 = f.iter().enumerate()
+                        .map(|(j, &fj)| fj * bg[i] * (lambda * matrix.get(i, j) as f64).exp())
+                        .sum();
+                    *t = (alpha * f[i] + BETA * g) / (alpha + BETA);
+                    row[i] = ((*t / bg[i]).ln() / lambda).round() as i32;
+                }
+            } else {
+                // The matrix row's own target frequencies
+                for (i, t) in target.iter_mut().enumerate() {
+                    *t = if qi < STANDARD_RESIDUES { bg[i] * (lambda * matrix.get(qi, i) as f64).exp() } else { bg[i] };
+                }
+            }
+            let sum: f64 = target.iter().sum();
+            let information = target.iter().zip(&bg)
+                .map(|(&t, &p)| if t > 0.0 { t / sum * (t / sum / p).log2() } else { 0.0 })
+                .sum();
+            pssm.scores.push(row);
+            pssm.frequencies.push(f);
+            pssm.information.push(information);
+            pssm.relative_weight.push(relative_weight);
+        }
+        pssm
+    }
+
+    pub fn len(&self) -> usize {
+        self.query.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.query.is_empty()
+    }
+
+    /// Score of `residue` (case-insensitive) at query position `pos`.
+    #[inline(always)]
+    pub fn score(&self, pos: usize, residue: u8) -> i32 {
+        self.scores[pos][matrix_index(residue)]
+    }
+
+    /// Seeds for `k`-mer lookup: (query position, encoded word) for every word scoring at
+    /// least `threshold` against the profile, plus the query's own words. Windows the
+    /// query itself could not seed (soft-masked or non-standard residues) are skipped.
+    pub fn neighborhood_words(&self, k: usize, threshold: i32) -> Vec<(usize, u64)> {
+        let mut words = Vec::new();
+        if k == 0 || self.len() < k {
+            return words;
+        }
+        // Per position: residue codes (as `encode_kmer` numbers them) and their scores
+        let by_code: Vec<[i32; 20]> = (0..self.len())
+            .map(|p| INT_TO_AA.map(|aa| self.score(p, aa)))
+            .collect();
+        let best: Vec<i32> = by_code.iter().map(|row| *row.iter().max().unwrap()).collect();
+        for (q_pos, window) in self.query.windows(k).enumerate() {
+            let Some(own) = encode_kmer(window) else { continue };
+            let start = words.len();
+            // Best score still reachable from each depth on
+            let mut reachable = vec![0; k + 1];
+            for d in (0..k).rev() {
+                reachable[d] = reachable[d + 1] + best[q_pos + d];
+            }
+            if reachable[0] >= threshold {
+                self.extend_word(&by_code[q_pos..q_pos + k], &reachable, threshold, 0, 0, 0, &mut |w| words.push((q_pos, w)));
+            }
+            if !words[start..].iter().any(|&(_, w)| w == own) {
+                words.push((q_pos, own));
+            }
+        }
+        words
+    }
+
+    // Depth-first over residues, pruning prefixes that can no longer reach `threshold`
+    #[allow(clippy::too_many_arguments)]
+    fn extend_word(
+        &self,
+        rows: &[[i32; 20]],
+        reachable: &[i32],
+        threshold: i32,
+        depth: usize,
+        score: i32,
+        word: u64,
+        emit: &mut impl FnMut(u64),
+    ) {
+        if depth == rows.len() {
+            emit(word);
+            return;
+        }
+        for (code, &s) in rows[depth].iter().enumerate() {
+            if score + s + reachable[depth + 1] >= threshold {
+                self.extend_word(rows, reachable, threshold, depth + 1, score + s, (word << 5) | code as u64, emit);
+            }
+        }
+    }
+
+    /// Best-scoring ungapped segment of the profile against `target` along `diagonal`
+    /// (target position - query position).
+    pub fn best_segment(&self, target: &[u8], diagonal: i32) -> Option<ExtensionResult> {
+        let (q0, t0) = if diagonal >= 0 { (0, diagonal as usize) } else { ((-diagonal) as usize, 0) };
+        if q0 >= self.len() || t0 >= target.len() {
+            return None;
+        }
+        let len = (self.len() - q0).min(target.len() - t0);
+        let (mut best, mut best_start, mut best_end) = (0, 0, 0);
+        let (mut run, mut run_start) = (0, 0);
+        for i in 0..len {
+            if run <= 0 {
+                run = 0;
+                run_start = i;
+            }
+            run += self.score(q0 + i, target[t0 + i]);
+            if run > best {
+                (best, best_start, best_end) = (run, run_start, i + 1);
+            }
+        }
+        Some(ExtensionResult {
+            score: best,
+            q_start: q0 + best_start,
+            q_end: q0 + best_end,
+            t_start: t0 + best_start,
+            t_end: t0 + best_end,
+        })
+    }
+
+    /// Writes the profile like PSI-BLAST's `-out_ascii_pssm`: per position the scores,
+    /// the weighted observed percentages, the information content and the relative
+    /// weight of observed residues to pseudocounts.
+    pub fn write_ascii<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out)?;
+        writeln!(out, "Last position-specific scoring matrix computed, weighted observed percentages rounded down, information per position, and relative weight of gapless real matches to pseudocounts")?;
+        write!(out, "         ")?;
+        for &aa in RESIDUES {
+            write!(out, "  {}", aa as char)?;
+        }
+        for &aa in RESIDUES {
+            write!(out, "   {}", aa as char)?;
+        }
+        writeln!(out)?;
+        for pos in 0..self.len() {
+            write!(out, "{:>5} {} ", pos + 1, self.query[pos].to_ascii_uppercase() as char)?;
+            for &s in &self.scores[pos][..STANDARD_RESIDUES] {
+                write!(out, "{:>3}", s)?;
+            }
+            write!(out, " ")?;
+            for &f in &self.frequencies[pos] {
+                write!(out, "{:>4}", (f * 100.0).floor() as i32)?;
+            }
+            writeln!(out, "  {:.2} {:.2}", self.information[pos], self.relative_weight[pos])?;
+        }
+        writeln!(out)?;
+        writeln!(out, "                      K         Lambda")?;
+        writeln!(out, "PSI Gapped         {:.4}     {:.4}", GAPPED_K, GAPPED_LAMBDA)?;
+        Ok(())
+    }
+}
+
+/// A local alignment of a target to a profile; coordinates are 0-based, half-open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileAlignment {
+    pub score: i32,
+    pub q_start: usize,
+    pub q_end: usize,
+    pub t_start: usize,
+    pub t_end: usize,
+    /// Target residue or `GAP` for each query position in q_start..q_end; target
+    /// residues inserted between query positions are left out
+    pub columns: Vec<u8>,
+}
+
+impl ProfileAlignment {
+    /// The hit as a row of the query-anchored alignment `Pssm::build` takes.
+    pub fn row(&self, query_len: usize) -> Vec<Option<u8>> {
+        let mut row = vec![None; query_len];
+        for (i, &b) in self.columns.iter().enumerate() {
+            row[self.q_start + i] = Some(b);
+        }
+        row
+    }
+}
+
+const NEG: i32 = i32::MIN / 4;
+// Traceback byte: bits 0-1 the H source, bit 2 E extended, bit 3 F extended
+const FROM_ZERO: u8 = 0;
+const FROM_DIAGONAL: u8 = 1;
+const FROM_E: u8 = 2;
+const FROM_F: u8 = 3;
+const E_EXTEND: u8 = 4;
+const F_EXTEND: u8 = 8;
+
+/// Smith-Waterman of `target` against the whole profile with affine gaps, in the
+/// `Scoring` convention (a gap of length L costs gap_open + L * gap_extend).
+pub fn align_profile(pssm: &Pssm, target: &[u8], gap_open: i32, gap_extend: i32) -> ProfileAlignment {
+    let (rows, cols) = (pssm.len() + 1, target.len() + 1);
+    // E: gap in the query (target insertion), F: gap in the target
+    let mut h = vec![0i32; cols];
+    let mut f = vec![NEG; cols];
+    let mut trace = vec![0u8; rows * cols];
+    let (mut best, mut best_u, mut best_v) = (0, 0, 0);
+    for u in 1..rows {
+        let mut diagonal = 0; // h[u - 1][v - 1]
+        let mut e = NEG;
+        let mut left = 0; // h[u][v - 1]
+        for v in 1..cols {
+            let mut tb = 0u8;
+            let e_open = left + gap_open + gap_extend;
+            let e_ext = e + gap_extend;
+            e = if e_ext > e_open { tb |= E_EXTEND; e_ext } else { e_open };
+            let f_open = h[v] + gap_open + gap_extend;
+            let f_ext = f[v] + gap_extend;
+            f[v] = if f_ext > f_open { tb |= F_EXTEND; f_ext } else { f_open };
+
+            let mut score = 0;
+            let mut source = FROM_ZERO;
+            for (from, value) in [(FROM_DIAGONAL, diagonal + pssm.score(u - 1, target[v - 1])), (FROM_E, e), (FROM_F, f[v])] {
+                if value > score {
+                    score = value;
+                    source = from;
+                }
+            }
+            diagonal = h[v];
+            h[v] = score;
+            left = score;
+            trace[u * cols + v] = tb | source;
+            if score > best {
+                (best, best_u, best_v) = (score, u, v);
+            }
+        }
+    }
+
+    let (mut u, mut v) = (best_u, best_v);
+    let mut columns = Vec::new();
+    let mut state = FROM_DIAGONAL;
+    loop {
+        let tb = trace[u * cols + v];
+        match state {
+            FROM_E => {
+                state = if tb & E_EXTEND != 0 { FROM_E } else { FROM_DIAGONAL };
+                v -= 1;
+                continue;
+            }
+            FROM_F => {
+                state = if tb & F_EXTEND != 0 { FROM_F } else { FROM_DIAGONAL };
+                columns.push(GAP);
+                u -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        match tb & 3 {
+            FROM_ZERO => break,
+            FROM_DIAGONAL => {
+                columns.push(target[v - 1]);
+                u -= 1;
+                v -= 1;
+            }
+            source => state = source,
+        }
+    }
+    columns.reverse();
+    ProfileAlignment { score: best, q_start: u, q_end: best_u, t_start: v, t_end: best_v, columns }
+}
@@ -3,6 +3,13 @@ use std::arch::x86_64::*;
 
 use crate::align::ungapped::Scoring;
 
+/// Residue identity ignoring case: lowercase only marks soft-masked residues.
+/// Only letters fold, so e.g. '@' and '`' stay distinct.
+#[inline(always)]
+pub fn same_residue(a: u8, b: u8) -> bool {
+    a == b || (a ^ b == 0x20 && a.is_ascii_alphabetic())
+}
+
 /// Optimized right-extension using AVX2.
 /// Falls back to scalar if AVX2 is not available or for left-extension.
 pub fn extend_direction_simd(
@@ -14,8 +21,8 @@ pub fn extend_direction_simd(
     step: isize,
     x_drop: i32,
 ) -> (i32, usize, usize) {
-    // Only optimize Right Extension (+1) with AVX2
-    if step == 1 && is_x86_feature_detected!("avx2") {
+    // Only optimize Right Extension (+1) with AVX2; matrix scores need per-pair lookups
+    if step == 1 && scoring.matrix.is_none() && is_x86_feature_detected!("avx2") {
         unsafe { extend_right_avx2(query, target, scoring, q_start, t_start, x_drop) }
     } else {
         // Fallback for Left Extension (-1) or non-AVX CPUs
@@ -50,11 +57,7 @@ fn extend_direction_scalar(
         let q_char = query[q_idx as usize];
         let t_char = target[t_idx as usize];
 
-        if q_char == t_char {
-            current_score += scoring.match_score;
-        } else {
-            current_score += scoring.mismatch_score;
-        }
+        current_score += scoring.score(q_char, t_char);
 
         if current_score > best_score {
             best_score = current_score;
@@ -71,6 +74,17 @@ fn extend_direction_scalar(
     (best_score, best_q_idx, best_t_idx)
 }
 
+/// Lowercases the uppercase ASCII letters among 32 bytes; other bytes are unchanged.
+#[target_feature(enable = "avx2")]
+#[inline]
+fn lowercase_letters(v: __m256i) -> __m256i {
+    let upper = _mm256_and_si256(
+        _mm256_cmpgt_epi8(v, _mm256_set1_epi8(b'A' as i8 - 1)),
+        _mm256_cmpgt_epi8(_mm256_set1_epi8(b'Z' as i8 + 1), v),
+    );
+    _mm256_or_si256(v, _mm256_and_si256(upper, _mm256_set1_epi8(0x20)))
+}
+
 /// AVX2 Optimized Implementation for Step = 1
 #[target_feature(enable = "avx2")]
 unsafe fn extend_right_avx2(
@@ -99,8 +113,9 @@ unsafe fn extend_right_avx2(
             let v_q = _mm256_loadu_si256(query.as_ptr().add(q_curr) as *const __m256i);
             let v_t = _mm256_loadu_si256(target.as_ptr().add(t_curr) as *const __m256i);
 
-            // Compare: result is 0xFF for equal, 0x00 for not equal
-            let v_cmp = _mm256_cmpeq_epi8(v_q, v_t);
+            // Compare case-insensitively (lowercase the uppercase letters on both sides,
+            // leaving other bytes alone): result is 0xFF for equal, 0x00 for not equal
+            let v_cmp = _mm256_cmpeq_epi8(lowercase_letters(v_q), lowercase_letters(v_t));
 
             // Create Mask: Collapses 32 bytes into a 32-bit integer
             // Bit i is 1 if match, 0 if mismatch
@@ -146,7 +161,7 @@ unsafe fn extend_right_avx2(
             let val_q = unsafe { *query.get_unchecked(q_i) };
             let val_t = unsafe { *target.get_unchecked(t_i) };
 
-            if val_q == val_t {
+            if same_residue(val_q, val_t) {
                 current_score += scoring.match_score;
             } else {
                 current_score += scoring.mismatch_score;
@@ -1,5 +1,7 @@
 use bio::alignment::pairwise::{self, Scoring};
-use bio::alignment::Alignment;
+use bio::alignment::{Alignment, AlignmentOperation};
+use crate::align::simd::same_residue;
+use crate::align::ungapped;
 
 pub fn align_sw(
     query: &[u8], 
@@ -9,16 +11,47 @@ pub fn align_sw(
     match_score: i32,
     mismatch_score: i32
 ) -> Alignment {
-    let scoring = Scoring::from_scores(
-        gap_open, gap_extend, 
-        match_score, mismatch_score
-    );
+    // Case-insensitive, so soft-masked (lowercase) residues still align
+    let scoring = Scoring::new(gap_open, gap_extend, move |a: u8, b: u8| {
+        if same_residue(a, b) { match_score } else { mismatch_score }
+    });
 
     let mut aligner = pairwise::Aligner::with_scoring(scoring);
 
     aligner.local(query, target)
 }
 
+/// Local alignment with a full scoring scheme (matrix or identity, plus its gap costs).
+pub fn align_scored(query: &[u8], target: &[u8], scoring: &ungapped::Scoring) -> Alignment {
+    let Some(matrix) = scoring.matrix.as_deref() else {
+        return align_sw(query, target, scoring.gap_open, scoring.gap_extend,
+                        scoring.match_score, scoring.mismatch_score);
+    };
+    let scores = Scoring::new(scoring.gap_open, scoring.gap_extend, |a: u8, b: u8| matrix.score(a, b));
+    pairwise::Aligner::with_scoring(scores).local(query, target)
+}
+
+/// Identical residue pairs (case-insensitive) in a local alignment of `query` to
+/// `target`, and its length in columns, gaps included.
+pub fn identities(alignment: &Alignment, query: &[u8], target: &[u8]) -> (usize, usize) {
+    let (mut x, mut y) = (alignment.xstart, alignment.ystart);
+    let (mut identities, mut length) = (0, 0);
+    for op in &alignment.operations {
+        match op {
+            AlignmentOperation::Match | AlignmentOperation::Subst => {
+                identities += usize::from(same_residue(query[x], target[y]));
+                x += 1;
+                y += 1;
+            }
+            AlignmentOperation::Ins => x += 1,
+            AlignmentOperation::Del => y += 1,
+            AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_) => continue,
+        }
+        length += 1;
+    }
+    (identities, length)
+}
+
 /// Extract a window around a center position, returning a slice reference and offset.
 /// This avoids unnecessary allocations by returning a slice instead of a Vec.
 pub fn extract_window(
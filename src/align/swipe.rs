@@ -0,0 +1,357 @@
+// Inter-sequence Smith-Waterman scores, after Rognes' SWIPE: one query against many
+// targets at a time, one per lane of an AVX2 vector. The DP runs down the query for one
+// target column at a time; a lane whose target ends is refilled with the next target
+// before the next column, and its row of H and E is cleared as it is read.
+//
+// Targets are first scored 32 at a time in unsigned 8-bit lanes, with substitution
+// scores biased to be non-negative. Lanes that reach the byte limit are re-scored 16 at a
+// time in 16-bit lanes, and those that reach i16::MAX with the scalar kernel in i32.
+//
+// The query profile holds the scheme's score of every query residue class against every
+// target residue class, so each column only gathers 24 score vectors (one per class)
+// from it. Residues are classed by `matrix_index` (case-insensitive; unknown bytes are
+// X), which is exact for BLOSUM62 and for identity scoring of the matrix alphabet.
+// Scores only: no coordinates or traceback.
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::align::matrix::MATRIX_SIZE;
+use crate::align::ungapped::Scoring;
+use crate::core::alphabet::matrix_index;
+
+/// Targets per vector in the 8-bit and 16-bit kernels
+pub const BYTE_LANES: usize = 32;
+pub const WORD_LANES: usize = 16;
+
+/// Target class of an empty lane
+const PAD: usize = MATRIX_SIZE;
+const CLASSES: usize = MATRIX_SIZE + 1;
+/// A residue of each class, in `matrix_index` order
+const CLASS_RESIDUES: &[u8; MATRIX_SIZE] = b"ARNDCQEGHILKMFPSTWYVBZX*";
+
+/// A query prepared for scoring against many targets.
+#[derive(Debug, Clone)]
+pub struct QueryProfile {
+    /// `matrix_index` class of each query residue
+    classes: Vec<u8>,
+    /// Score of query class `a` against target class `c` at `a * CLASSES + c`
+    table: Vec<i16>,
+    /// Cost of a gap's first residue (open + extend) and of each further one, negative
+    open: i16,
+    extend: i16,
+    /// None when the scores do not fit the 8-bit kernel
+    bytes: Option<ByteProfile>,
+}
+
+/// The profile for unsigned 8-bit lanes: scores plus `bias`, gap costs positive.
+#[derive(Debug, Clone)]
+struct ByteProfile {
+    table: Vec<u8>,
+    bias: u8,
+    open: u8,
+    extend: u8,
+}
+
+impl ByteProfile {
+    fn new(table: &[i16], open: i16, extend: i16) -> Option<Self> {
+        let scores = table.iter().filter(|&&s| s != i16::MIN);
+        let bias = -scores.clone().copied().min().unwrap_or(0).min(0);
+        let max = scores.copied().max().unwrap_or(0);
+        // Half the range at least has to be left for alignment scores
+        if bias > 127 || max + bias > 255 || open > 0 || extend > 0 {
+            return None;
+        }
+        Some(Self {
+            // Empty lanes score -bias against everything and stay at 0
+            table: table.iter().map(|&s| if s == i16::MIN { 0 } else { (s + bias) as u8 }).collect(),
+            bias: bias as u8,
+            open: open.unsigned_abs().min(255) as u8,
+            extend: extend.unsigned_abs().min(255) as u8,
+        })
+    }
+
+    /// Scores at or above this may have been cut off
+    fn limit(&self) -> i32 {
+        255 - self.bias as i32
+    }
+}
+
+fn to_i16(score: i32) -> i16 {
+    score.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+impl QueryProfile {
+    pub fn new(query: &[u8], scoring: &Scoring) -> Self {
+        let mut table = vec![i16::MIN; MATRIX_SIZE * CLASSES];
+        for (a, &qa) in CLASS_RESIDUES.iter().enumerate() {
+            for (c, &tc) in CLASS_RESIDUES.iter().enumerate() {
+                table[a * CLASSES + c] = to_i16(scoring.score(qa, tc));
+            }
+        }
+        let open = to_i16(scoring.gap_open + scoring.gap_extend);
+        let extend = to_i16(scoring.gap_extend);
+        Self {
+            classes: query.iter().map(|&b| matrix_index(b) as u8).collect(),
+            bytes: ByteProfile::new(&table, open, extend),
+            table,
+            open,
+            extend,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    #[inline(always)]
+    fn score(&self, a: u8, c: usize) -> i32 {
+        self.table[a as usize * CLASSES + c] as i32
+    }
+}
+
+/// Local alignment score of the query against each target, in target order.
+pub fn align_scores(profile: &QueryProfile, targets: &[&[u8]]) -> Vec<i32> {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        let words = |targets: &[&[u8]]| unsafe { align_scores_avx2_i16(profile, targets) };
+        let mut scores = match &profile.bytes {
+            Some(bytes) => {
+                let mut scores = unsafe { align_scores_avx2_u8(profile, bytes, targets) };
+                rescore(&mut scores, targets, bytes.limit(), words);
+                scores
+            }
+            None => words(targets),
+        };
+        rescore(&mut scores, targets, i16::MAX as i32, |targets| align_scores_scalar(profile, targets));
+        return scores;
+    }
+    align_scores_scalar(profile, targets)
+}
+
+/// Replaces the scores at or above `limit` with those of a wider kernel.
+#[cfg(target_arch = "x86_64")]
+fn rescore(scores: &mut [i32], targets: &[&[u8]], limit: i32, wider: impl FnOnce(&[&[u8]]) -> Vec<i32>) {
+    let overflowed: Vec<usize> = (0..scores.len()).filter(|&i| scores[i] >= limit).collect();
+    if overflowed.is_empty() {
+        return;
+    }
+    let subset: Vec<&[u8]> = overflowed.iter().map(|&i| targets[i]).collect();
+    for (i, score) in overflowed.into_iter().zip(wider(&subset)) {
+        scores[i] = score;
+    }
+}
+
+/// `align_scores` without SIMD.
+pub fn align_scores_scalar(profile: &QueryProfile, targets: &[&[u8]]) -> Vec<i32> {
+    targets.iter().map(|t| align_score_scalar(profile, t)).collect()
+}
+
+/// Gotoh local alignment score in i32, column by column like the vector kernel.
+fn align_score_scalar(profile: &QueryProfile, target: &[u8]) -> i32 {
+    let (open, extend) = (profile.open as i32, profile.extend as i32);
+    let m = profile.len();
+    let mut h = vec![0i32; m];
+    let mut e = vec![i32::MIN / 2; m];
+    let mut best = 0;
+    for &t in target {
+        let c = matrix_index(t);
+        let (mut diagonal, mut f, mut up) = (0, i32::MIN / 2, 0);
+        for i in 0..m {
+            let left = h[i];
+            e[i] = (e[i] + extend).max(left + open);
+            f = (f + extend).max(up + open);
+            let cell = (diagonal + profile.score(profile.classes[i], c)).max(0).max(e[i]).max(f);
+            best = best.max(cell);
+            diagonal = left;
+            h[i] = cell;
+            up = cell;
+        }
+    }
+    best
+}
+
+/// The target and next position of each lane, refilled in target order.
+#[cfg(target_arch = "x86_64")]
+struct Lanes<'t, const N: usize> {
+    targets: &'t [&'t [u8]],
+    slots: [Option<(usize, usize)>; N],
+    next: usize,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl<'t, const N: usize> Lanes<'t, N> {
+    fn new(targets: &'t [&'t [u8]]) -> Self {
+        Self { targets, slots: [None; N], next: 0 }
+    }
+
+    /// Puts the next targets into free lanes and returns which lanes got a new one,
+    /// or None once every target is done. Empty targets score 0 and never take a lane.
+    fn refill(&mut self) -> Option<[bool; N]> {
+        let mut reset = [false; N];
+        for (lane, slot) in self.slots.iter_mut().enumerate() {
+            while slot.is_none() && self.next < self.targets.len() {
+                if !self.targets[self.next].is_empty() {
+                    *slot = Some((self.next, 0));
+                    reset[lane] = true;
+                }
+                self.next += 1;
+            }
+        }
+        self.slots.iter().any(Option::is_some).then_some(reset)
+    }
+
+    /// `matrix_index` class of each lane's current residue, PAD for empty lanes.
+    fn classes(&self) -> [usize; N] {
+        std::array::from_fn(|lane| match self.slots[lane] {
+            Some((t, pos)) => matrix_index(self.targets[t][pos]),
+            None => PAD,
+        })
+    }
+
+    /// Moves every lane one residue on, calling `done(lane, target)` for the lanes
+    /// whose target ended.
+    fn advance(&mut self, mut done: impl FnMut(usize, usize)) {
+        for (lane, slot) in self.slots.iter_mut().enumerate() {
+            let Some((t, pos)) = slot else { continue };
+            *pos += 1;
+            if *pos == self.targets[*t].len() {
+                done(lane, *t);
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Unsigned saturating arithmetic: H, E and F are kept at or above 0, which leaves
+/// every cell unchanged since a local alignment never goes below 0.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn align_scores_avx2_u8(profile: &QueryProfile, bytes: &ByteProfile, targets: &[&[u8]]) -> Vec<i32> {
+    let m = profile.len();
+    let mut scores = vec![0; targets.len()];
+    if m == 0 {
+        return scores;
+    }
+    let zero = _mm256_setzero_si256();
+    let bias = _mm256_set1_epi8(bytes.bias as i8);
+    let open = _mm256_set1_epi8(bytes.open as i8);
+    let extend = _mm256_set1_epi8(bytes.extend as i8);
+    let mut h = vec![zero; m];
+    let mut e = vec![zero; m];
+    let mut class_scores = [zero; MATRIX_SIZE];
+    let mut best = zero;
+    let mut lanes = Lanes::<BYTE_LANES>::new(targets);
+
+    while let Some(reset) = lanes.refill() {
+        let classes = lanes.classes();
+        for (a, v) in class_scores.iter_mut().enumerate() {
+            let row = &bytes.table[a * CLASSES..(a + 1) * CLASSES];
+            let column: [u8; BYTE_LANES] = std::array::from_fn(|lane| row[classes[lane]]);
+            *v = unsafe { _mm256_loadu_si256(column.as_ptr() as *const __m256i) };
+        }
+        let any_reset = reset.contains(&true);
+        let reset: [u8; BYTE_LANES] = std::array::from_fn(|lane| if reset[lane] { u8::MAX } else { 0 });
+        let reset = unsafe { _mm256_loadu_si256(reset.as_ptr() as *const __m256i) };
+        if any_reset {
+            best = _mm256_andnot_si256(reset, best);
+        }
+
+        let (mut diagonal, mut f, mut up) = (zero, zero, zero);
+        for i in 0..m {
+            let (mut left, mut e_i) = (h[i], e[i]);
+            if any_reset {
+                left = _mm256_andnot_si256(reset, left);
+                e_i = _mm256_andnot_si256(reset, e_i);
+            }
+            e_i = _mm256_max_epu8(_mm256_subs_epu8(e_i, extend), _mm256_subs_epu8(left, open));
+            f = _mm256_max_epu8(_mm256_subs_epu8(f, extend), _mm256_subs_epu8(up, open));
+            let s = class_scores[profile.classes[i] as usize];
+            let matched = _mm256_subs_epu8(_mm256_adds_epu8(diagonal, s), bias);
+            let cell = _mm256_max_epu8(matched, _mm256_max_epu8(e_i, f));
+            best = _mm256_max_epu8(best, cell);
+            diagonal = left;
+            h[i] = cell;
+            e[i] = e_i;
+            up = cell;
+        }
+
+        let mut lane_best: Option<[u8; BYTE_LANES]> = None;
+        lanes.advance(|lane, t| {
+            let values = lane_best.get_or_insert_with(|| {
+                let mut values = [0u8; BYTE_LANES];
+                unsafe { _mm256_storeu_si256(values.as_mut_ptr() as *mut __m256i, best) };
+                values
+            });
+            scores[t] = values[lane] as i32;
+        });
+    }
+    scores
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn align_scores_avx2_i16(profile: &QueryProfile, targets: &[&[u8]]) -> Vec<i32> {
+    let m = profile.len();
+    let mut scores = vec![0; targets.len()];
+    if m == 0 {
+        return scores;
+    }
+    let zero = _mm256_setzero_si256();
+    let min = _mm256_set1_epi16(i16::MIN);
+    let open = _mm256_set1_epi16(profile.open);
+    let extend = _mm256_set1_epi16(profile.extend);
+    let mut h = vec![zero; m];
+    let mut e = vec![min; m];
+    let mut class_scores = [zero; MATRIX_SIZE];
+    let mut best = zero;
+    let mut lanes = Lanes::<WORD_LANES>::new(targets);
+
+    while let Some(reset) = lanes.refill() {
+        let classes = lanes.classes();
+        for (a, v) in class_scores.iter_mut().enumerate() {
+            let row = &profile.table[a * CLASSES..(a + 1) * CLASSES];
+            let column: [i16; WORD_LANES] = std::array::from_fn(|lane| row[classes[lane]]);
+            *v = unsafe { _mm256_loadu_si256(column.as_ptr() as *const __m256i) };
+        }
+        let any_reset = reset.contains(&true);
+        let reset: [i16; WORD_LANES] = std::array::from_fn(|lane| if reset[lane] { -1 } else { 0 });
+        let reset = unsafe { _mm256_loadu_si256(reset.as_ptr() as *const __m256i) };
+        if any_reset {
+            best = _mm256_andnot_si256(reset, best);
+        }
+
+        let (mut diagonal, mut f, mut up) = (zero, min, zero);
+        for i in 0..m {
+            let (mut left, mut e_i) = (h[i], e[i]);
+            if any_reset {
+                left = _mm256_andnot_si256(reset, left);
+                e_i = _mm256_blendv_epi8(e_i, min, reset);
+            }
+            e_i = _mm256_max_epi16(_mm256_adds_epi16(e_i, extend), _mm256_adds_epi16(left, open));
+            f = _mm256_max_epi16(_mm256_adds_epi16(f, extend), _mm256_adds_epi16(up, open));
+            let s = class_scores[profile.classes[i] as usize];
+            let matched = _mm256_max_epi16(_mm256_adds_epi16(diagonal, s), zero);
+            let cell = _mm256_max_epi16(matched, _mm256_max_epi16(e_i, f));
+            best = _mm256_max_epi16(best, cell);
+            diagonal = left;
+            h[i] = cell;
+            e[i] = e_i;
+            up = cell;
+        }
+
+        let mut lane_best: Option<[i16; WORD_LANES]> = None;
+        lanes.advance(|lane, t| {
+            let values = lane_best.get_or_insert_with(|| {
+                let mut values = [0i16; WORD_LANES];
+                unsafe { _mm256_storeu_si256(values.as_mut_ptr() as *mut __m256i, best) };
+                values
+            });
+            scores[t] = values[lane] as i32;
+        });
+    }
+    scores
+}
@@ -1,7 +1,10 @@
 use crate::core::database::Database;
 use crate::index::kmer::ProteinId;
 use crate::filter::seed::Candidate;
-use crate::align::simd;
+use crate::align::simd::{self, same_residue};
+use crate::align::matrix::ScoreMatrix;
+use crate::align::composition::PairScoring;
+use std::borrow::Cow;
 
 #[derive(Debug, Clone)]
 pub struct ExtensionResult {
@@ -12,15 +15,55 @@ pub struct ExtensionResult {
     pub t_end: usize,
 }
 
+/// Scoring scheme shared by ungapped extension and Smith-Waterman.
+/// Identity scoring (match / mismatch) unless a substitution matrix is set.
+#[derive(Debug, Clone)]
 pub struct Scoring {
     pub match_score: i32,
     pub mismatch_score: i32,
+    pub matrix: Option<Box<ScoreMatrix>>,
+    pub gap_open: i32,
+    pub gap_extend: i32,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self { match_score: 1, mismatch_score: -1, matrix: None, gap_open: -10, gap_extend: -1 }
+    }
+}
+
+/// Substitution scores selectable per search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ScoringScheme {
+    /// +1 / -1 identity scoring, gaps 10/1
+    #[default]
+    Identity,
+    /// BLOSUM62, gaps 11/1
+    Blosum62,
+}
+
+impl ScoringScheme {
+    pub fn scoring(self) -> Scoring {
+        match self {
+            ScoringScheme::Identity => Scoring::default(),
+            ScoringScheme::Blosum62 => Scoring::blosum62(),
+        }
+    }
 }
 
-// Use simple score here, maybe BLOSUM62 in the future
 impl Scoring {
-    pub fn default() -> Self {
-        Self{match_score: 1, mismatch_score: -1 }
+    /// BLOSUM62 with BLAST's default 11/1 gap costs.
+    pub fn blosum62() -> Self {
+        Self { matrix: Some(Box::new(ScoreMatrix::blosum62())), gap_open: -11, gap_extend: -1, ..Self::default() }
+    }
+
+    #[inline(always)]
+    pub fn score(&self, a: u8, b: u8) -> i32 {
+        match &self.matrix {
+            Some(matrix) => matrix.score(a, b),
+            None if same_residue(a, b) => self.match_score,
+            None => self.mismatch_score,
+        }
     }
 }
 
@@ -52,6 +95,34 @@ pub fn extend_ungapped(
         t_end:right_t_end }
 }
 
+/// Extends along `diag` (target pos - query pos) from where the diagonal enters the matrix.
+pub fn extend_diagonal(
+    query: &[u8],
+    target_seq: &[u8],
+    diag: i32,
+    scoring: &Scoring,
+    x_drop: i32,
+) -> Option<ExtensionResult> {
+    // If diag > 0 (T > Q): Q=0, T=diag
+    // If diag < 0 (Q > T): Q=-diag, T=0
+    let (q_start, t_start) = if diag >= 0 {
+        (0, diag as usize)
+    } else {
+        ((-diag) as usize, 0)
+    };
+
+    // Safety check: prevent out of bounds
+    if t_start >= target_seq.len() || q_start >= query.len() {
+        return None;
+    }
+
+    // Extend
+    Some(extend_ungapped(
+        query, target_seq, scoring, 
+        q_start, t_start, x_drop
+    ))
+}
+
 // Wrap ungapped function
 pub fn refine_ungapped(
     query: &[u8],
@@ -61,8 +132,34 @@ pub fn refine_ungapped(
     x_drop: i32,
     top_n: usize,
 ) -> Vec<(ProteinId, ExtensionResult)> {
-    
-    let mut hits = Vec::with_capacity(candidates.len());
+    refine_with(query, candidates, db, |_| Cow::Borrowed(scoring), x_drop, top_n)
+        .into_iter()
+        .map(|(id, ext, _)| (id, ext))
+        .collect()
+}
+
+/// `refine_ungapped` with each target scored by its composition-adjusted matrix.
+/// Each hit keeps its scoring, so Smith-Waterman does not build the matrix again.
+pub fn refine_ungapped_adjusted<'p>(
+    query: &[u8],
+    candidates: &[Candidate],
+    db: &Database,
+    pair: &'p PairScoring,
+    x_drop: i32,
+    top_n: usize,
+) -> Vec<(ProteinId, ExtensionResult, Cow<'p, Scoring>)> {
+    refine_with(query, candidates, db, |target| pair.for_target(target), x_drop, top_n)
+}
+
+fn refine_with<'s, F: Fn(&[u8]) -> Cow<'s, Scoring>>(
+    query: &[u8],
+    candidates: &[Candidate],
+    db: &Database,
+    scoring_for: F,
+    x_drop: i32,
+    top_n: usize,
+) -> Vec<(ProteinId, ExtensionResult, Cow<'s, Scoring>)> {
+    let mut hits = Vec::with_capacity(candidates.len().min(2 * top_n.max(1)));
 
     for cand in candidates {
         // Get Target sequence
@@ -70,38 +167,21 @@ pub fn refine_ungapped(
             Some(entry) => entry,
             None => continue,
         };
-        let diag = cand.best_diagonal;
-        
-        // If diag > 0 (T > Q): Q=0, T=diag
-        // If diag < 0 (Q > T): Q=-diag, T=0
-        let (q_start, t_start) = if diag >= 0 {
-            (0, diag as usize)
-        } else {
-            ((-diag) as usize, 0)
-        };
-
-        // Safety check: prevent out of bounds
-        if t_start >= target_seq.len() || q_start >= query.len() {
-            continue;
+        let scoring = scoring_for(target_seq);
+        if let Some(result) = extend_diagonal(query, target_seq, cand.best_diagonal, &scoring, x_drop) {
+            hits.push((cand.id, result, scoring));
+            // Hits may hold their own matrix, so only the best are kept as we go
+            if hits.len() >= 2 * top_n.max(1) {
+                keep_best(&mut hits, top_n);
+            }
         }
-
-        // Extend
-        let result = extend_ungapped(
-            query, target_seq, scoring, 
-            q_start, t_start, x_drop
-        );
-
-        hits.push((cand.id, result));
-    }
-
-    // Sort by Score Descending
-    // Use unstable sort for faster
-    hits.sort_unstable_by(|a, b| b.1.score.cmp(&a.1.score));
-
-    // Truncate (Top N)
-    if hits.len() > top_n {
-        hits.truncate(top_n);
     }
-
+    keep_best(&mut hits, top_n);
     hits
-}
\ No newline at end of file
+}
+
+/// Sorts by score descending (ties by ID) and keeps the first `top_n`.
+fn keep_best<S>(hits: &mut Vec<(ProteinId, ExtensionResult, S)>, top_n: usize) {
+    hits.sort_unstable_by_key(|h| (std::cmp::Reverse(h.1.score), h.0));
+    hits.truncate(top_n);
+}
@@ -1,8 +1,10 @@
 use std::time::Instant;
 use crate::core::database::Database;
+use crate::core::fasta::{open_input, FastaReader};
 use crate::index::kmer::{KmerIndex, ProteinId};
+use crate::index::weighting::Bm25;
 use crate::align::ungapped::{Scoring, refine_ungapped};
-use crate::bench::query_gen::{self, QueryConfig};
+use crate::bench::query_gen::{self, GroundTruth, QueryConfig};
 use crate::bench::helper::{print_comparison, run_gapped_wrapper,create_csv_writer,write_metric_csv};
 use crate::bench::metric::calculate_metrics;
 use crate::filter::seed; // Step 2
@@ -34,10 +36,11 @@ impl ExpResult {
 }
 
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_k_tradeoff(
     db: &Database,top_n: usize, mutate: bool, 
     length: usize, sub_rate: f64, 
-    indel_rate: f64,sample_num: usize,
+    indel_rate: f64,sample_num: usize, truth: GroundTruth,
     csv_path: Option<&Path>
 ) {
     println!("\n=== Task 1: K-mer Trade-off Analysis ===");
@@ -46,12 +49,12 @@ pub fn run_k_tradeoff(
         writeln!(w, "{}", ExpResult::csv_header()).unwrap();
     }
     let config = if mutate {
-        QueryConfig { length, sub_rate, indel_rate }
+        QueryConfig { length, sub_rate, indel_rate, truth }
     } else {
-        QueryConfig { length, sub_rate: 0.0, indel_rate: 0.0 }
+        QueryConfig { length, sub_rate: 0.0, indel_rate: 0.0, truth }
     };
     let queries = query_gen::sample_queries(db, sample_num, &config);
-    let truths: Vec<ProteinId> = queries.iter().map(|q| q.original_pid).collect();
+    let truths: Vec<ProteinId> = queries.iter().map(|q| q.truth).collect();
 
     for k in 3..=7 {
         let start = Instant::now();
@@ -86,24 +89,25 @@ pub fn run_k_tradeoff(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_filter_comparison(
     db: &Database,top_n: usize, k: usize, mutate: bool, 
     length: usize, sub_rate: f64, 
-    indel_rate: f64,sample_num: usize,min_diagonal: usize,
+    indel_rate: f64,sample_num: usize,min_diagonal: usize, truth: GroundTruth,
     csv_path: Option<&Path>
 ) {
-    println!("\n=== Task 2: Diagonal Filtering vs Voting (k={}) ===", k);
+    println!("\n=== Task 2: Diagonal Filtering vs Voting vs IDF (k={}) ===", k);
     let mut csv_writer = create_csv_writer(csv_path);
     if let Some(w) = &mut csv_writer {
         writeln!(w, "{}", ExpResult::csv_header()).unwrap();
     }
     let config = if mutate {
-        QueryConfig { length, sub_rate, indel_rate }
+        QueryConfig { length, sub_rate, indel_rate, truth }
     } else {
-        QueryConfig { length, sub_rate: 0.0, indel_rate: 0.0 }
+        QueryConfig { length, sub_rate: 0.0, indel_rate: 0.0, truth }
     };
     let queries = query_gen::sample_queries(db, sample_num, &config);
-    let truths: Vec<ProteinId> = queries.iter().map(|q| q.original_pid).collect();
+    let truths: Vec<ProteinId> = queries.iter().map(|q| q.truth).collect();
 
     let index = KmerIndex::build(db, k);
 
@@ -145,13 +149,52 @@ pub fn run_filter_comparison(
     if let Some(w) = &mut csv_writer {
         write!(w, "{}", result_b.to_csv_line()).unwrap();
     }
+
+    let start_c = Instant::now();
+    let res_c: Vec<Vec<(ProteinId, f32)>> = queries.iter()
+        .map(|q| index.search_idf(&q.sequence, top_n, None))
+        .collect();
+    let metrics_c = calculate_metrics(&res_c, &truths, start_c.elapsed().as_millis() as f64);
+
+    let result_c = ExpResult {
+        name: "Method C (IDF)".to_string(),
+        recall_1: metrics_c.recall_at_1,
+        recall_10: metrics_c.recall_at_10,
+        mrr: metrics_c.mrr,
+        avg_time: metrics_c.avg_time_ms,
+        candidates: metrics_c.avg_candidates
+    };
+    result_c.print();
+    if let Some(w) = &mut csv_writer {
+        write!(w, "{}", result_c.to_csv_line()).unwrap();
+    }
+
+    let start_d = Instant::now();
+    let res_d: Vec<Vec<(ProteinId, f32)>> = queries.iter()
+        .map(|q| index.search_idf(&q.sequence, top_n, Some(Bm25::default())))
+        .collect();
+    let metrics_d = calculate_metrics(&res_d, &truths, start_d.elapsed().as_millis() as f64);
+
+    let result_d = ExpResult {
+        name: "Method D (BM25)".to_string(),
+        recall_1: metrics_d.recall_at_1,
+        recall_10: metrics_d.recall_at_10,
+        mrr: metrics_d.mrr,
+        avg_time: metrics_d.avg_time_ms,
+        candidates: metrics_d.avg_candidates
+    };
+    result_d.print();
+    if let Some(w) = &mut csv_writer {
+        write!(w, "{}", result_d.to_csv_line()).unwrap();
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_ungapped_test(
     db: &Database, sample_num: usize, 
     top_n: usize, k: usize, 
     length: usize, sub_rate: f64, 
-    indel_rate: f64,x_drop: usize,
+    indel_rate: f64,x_drop: usize, truth: GroundTruth,
     csv_path: Option<&Path>
 ) {
 
@@ -160,8 +203,8 @@ pub fn run_ungapped_test(
     if let Some(w) = &mut csv_writer {
         writeln!(w, "Scenario,Recall_1,Recall_10,MRR,Avg_Time_ms,Avg_Candidates").unwrap();
     }
-    let config_sub  = QueryConfig{length, sub_rate, indel_rate:0.0};
-    let config_indel = QueryConfig{length, sub_rate:0.0, indel_rate};
+    let config_sub  = QueryConfig{length, sub_rate, indel_rate:0.0, truth};
+    let config_indel = QueryConfig{length, sub_rate:0.0, indel_rate, truth};
     
     let index = KmerIndex::build(db, k);
     let scoring = Scoring::default();
@@ -178,7 +221,7 @@ pub fn run_ungapped_test(
 }
 
 
-pub fn run_stress_all(db: &Database, sample_num: usize, top_n: usize, csv_path: Option<&Path>) {
+pub fn run_stress_all(db: &Database, sample_num: usize, top_n: usize, truth: GroundTruth, csv_path: Option<&Path>) {
     println!("\n===============================================================");
     println!("   STRESS TEST: K-mer Tradeoffs & Mutation Robustness");
     println!("===============================================================");
@@ -198,10 +241,11 @@ pub fn run_stress_all(db: &Database, sample_num: usize, top_n: usize, csv_path:
         let config = QueryConfig { 
             length: 60, 
             sub_rate, 
-            indel_rate: 0.0 // Focus on Substitution
+            indel_rate: 0.0, // Focus on Substitution
+            truth,
         };
         let queries = query_gen::sample_queries(db, sample_num, &config);
-        let truths: Vec<_> = queries.iter().map(|q| q.original_pid).collect();
+        let truths: Vec<_> = queries.iter().map(|q| q.truth).collect();
 
         for &k in &k_values {
             let index = KmerIndex::build(db, k);
@@ -224,7 +268,7 @@ pub fn run_stress_all(db: &Database, sample_num: usize, top_n: usize, csv_path:
             println!("{:<4} | {:<8.1} | {:<8.4} | {:<8.4} | {:<8.4} | {:<8.1} | {:<10.2} | {:<10.2}", 
                     k, sub_rate, m.recall_at_1, m.recall_at_10, m.mrr, m.avg_time_ms, m.avg_candidates, mem_mb);
             if let Some(w) = &mut csv_writer {
-                write!(w, "{},{:.6},{:.6},{:.6},{:.6},{:.2},{:.2},{:.2}\n", 
+                writeln!(w, "{},{:.6},{:.6},{:.6},{:.6},{:.2},{:.2},{:.2}", 
                     k, sub_rate, m.recall_at_1, m.recall_at_10, m.mrr, m.avg_time_ms, m.avg_candidates, mem_mb).unwrap();
             }
     }
@@ -234,11 +278,12 @@ pub fn run_stress_all(db: &Database, sample_num: usize, top_n: usize, csv_path:
 
 
 /// Task 5 : Indel Robustness & SW Refinement
+#[allow(clippy::too_many_arguments)]
 pub fn run_indel_test(
     db: &Database, sample_num: usize, 
     top_n: usize, k: usize, 
     length: usize, sub_rate: f64, 
-    indel_rate: f64, x_drop: usize,
+    indel_rate: f64, x_drop: usize, truth: GroundTruth,
     csv_path: Option<&Path>
 ) {
     println!("\n=== Task 5: Indel Robustness & SW Refinement ===");
@@ -246,9 +291,9 @@ pub fn run_indel_test(
     if let Some(w) = &mut csv_writer {
         writeln!(w, "Scenario,Recall_1,Recall_10,MRR,Avg_Time_ms,Avg_Candidates").unwrap();
     }
-    let config = QueryConfig { length, sub_rate, indel_rate };
+    let config = QueryConfig { length, sub_rate, indel_rate, truth };
     let queries = query_gen::sample_queries(db, sample_num, &config);
-    let truths: Vec<ProteinId> = queries.iter().map(|q| q.original_pid).collect();
+    let truths: Vec<ProteinId> = queries.iter().map(|q| q.truth).collect();
 
     let index = KmerIndex::build(db, k);
     let scoring = Scoring::default();
@@ -282,12 +327,12 @@ pub fn run_indel_test(
 
             // 2. Smith-Waterman
             // Gap Open = -10, Gap Extend = -1
-            let align = smith_waterman::align_sw(&q_sub, &t_sub, -10, -1, 1, -1);
+            let align = smith_waterman::align_sw(q_sub, t_sub, -10, -1, 1, -1);
             
             sw_hits.push((*id, align.score));
         }
 
-        sw_hits.sort_by(|a, b| b.1.cmp(&a.1));
+        sw_hits.sort_by_key(|h| std::cmp::Reverse(h.1));
         res_sw.push(sw_hits.into_iter().map(|(id, s)| (id, s as u32)).collect());
     }
 
@@ -305,10 +350,11 @@ pub fn run_indel_test(
 }
 
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_spaced_seed_test(
     db: &Database, top_n: usize,
     k: usize,pattern: &str,sample_num: usize, 
-    sub_rate: f64, indel_rate: f64,length: usize,
+    sub_rate: f64, indel_rate: f64,length: usize, truth: GroundTruth,
     csv_path: Option<&Path>
 ) {
     println!("\n=== Task 6: Spaced Seeds vs Contiguous (High Mutation) ===");
@@ -317,9 +363,9 @@ pub fn run_spaced_seed_test(
         writeln!(w, "{}", ExpResult::csv_header()).unwrap();
     }
     // 30% mutation rate
-    let config = QueryConfig { length, sub_rate, indel_rate };
+    let config = QueryConfig { length, sub_rate, indel_rate, truth };
     let queries = query_gen::sample_queries(db, sample_num, &config);
-    let truths: Vec<ProteinId> = queries.iter().map(|q| q.original_pid).collect();
+    let truths: Vec<ProteinId> = queries.iter().map(|q| q.truth).collect();
 
     // 1. Contiguous k=5 (Weight=5, Span=5)
     let index_k5 = KmerIndex::build(db, k);
@@ -372,3 +418,48 @@ pub fn run_spaced_seed_test(
         write!(w, "{}", result_2.to_csv_line()).unwrap();
     }
 }
+
+/// Task 7 : FASTA parsing throughput
+/// Best of `repeats` runs of decompression alone, `FastaReader` alone and a full
+/// `Database` load, in MB/s of decompressed input.
+pub fn run_parse_throughput(path: &Path, repeats: usize) {
+    println!("\n===============================================================");
+    println!("   PARSE TEST: FASTA throughput on {:?}", path);
+    println!("===============================================================");
+    let best = |f: &mut dyn FnMut()| {
+        (0..repeats.max(1))
+            .map(|_| {
+                let start = Instant::now();
+                f();
+                start.elapsed().as_secs_f64()
+            })
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    let mut bytes = 0;
+    let read = best(&mut || {
+        let mut input = open_input(path).expect("Failed to open input");
+        bytes = std::io::copy(&mut input, &mut std::io::sink()).expect("Failed to read input");
+    });
+    let mut records = 0;
+    let parse = best(&mut || {
+        let mut reader = FastaReader::open(path).expect("Failed to open input");
+        let (mut data, mut header) = (Vec::new(), String::new());
+        records = 0;
+        while reader.read_record_into(&mut data, &mut header).expect("Failed to parse input") {
+            records += 1;
+            data.clear();
+        }
+    });
+    let load = best(&mut || {
+        Database::load_from_fasta(path).expect("Failed to load database");
+    });
+
+    let mb = bytes as f64 / 1e6;
+    println!("{:.1} MB, {} records", mb, records);
+    println!("{:<20} | {:>10} | {:>10}", "Stage", "Time(ms)", "MB/s");
+    println!("---------------------------------------------------------------");
+    for (name, secs) in [("Read only", read), ("FastaReader", parse), ("Database load", load)] {
+        println!("{:<20} | {:>10.1} | {:>10.0}", name, secs * 1e3, mb / secs);
+    }
+}
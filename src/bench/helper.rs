@@ -45,7 +45,7 @@ pub fn run_gapped_wrapper(
     sample_num: usize, config: &QueryConfig, 
     scoring: &Scoring, x_drop: i32) -> BenchmarkResult {
     let queries = sample_queries(db, sample_num, config);
-    let truths: Vec<ProteinId> = queries.iter().map(|q| q.original_pid).collect();
+    let truths: Vec<ProteinId> = queries.iter().map(|q| q.truth).collect();
 
     let mut results = Vec::with_capacity(queries.len());
     let start_time = Instant::now();
@@ -8,8 +8,8 @@ pub struct BenchmarkResult {
     pub avg_time_ms: f64,
 }
 
-pub fn calculate_metrics(
-    candidates_list: &[Vec<(ProteinId, u32)>], 
+pub fn calculate_metrics<S>(
+    candidates_list: &[Vec<(ProteinId, S)>], 
     truths: &[ProteinId],
     total_time_ms: f64
 ) -> BenchmarkResult {
@@ -1,12 +1,26 @@
 use rand::Rng;
 use rand::seq::IndexedRandom;
+use crate::align::swipe::{align_scores, QueryProfile};
+use crate::align::ungapped::Scoring;
 use crate::core::database::Database;
 use crate::index::kmer::ProteinId;
 
 
+/// What a sampled query should find first.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum GroundTruth {
+    /// The sequence the query was cut from
+    #[default]
+    Origin,
+    /// The best exhaustive Smith-Waterman hit (BLOSUM62), ties to the origin
+    Exhaustive,
+}
+
 pub struct GroundTruthQuery {
     pub sequence: Vec<u8>,
     pub original_pid: ProteinId,
+    /// Expected top hit under `QueryConfig::truth`
+    pub truth: ProteinId,
     pub original_pos: usize,
     pub mutation_info: String,
 }
@@ -15,6 +29,7 @@ pub struct QueryConfig {
     pub length: usize,
     pub sub_rate: f64,
     pub indel_rate: f64,
+    pub truth: GroundTruth,
 }
 
 pub fn sample_queries(db: &Database, num_queries: usize, config: &QueryConfig) -> Vec<GroundTruthQuery> {
@@ -36,10 +51,22 @@ pub fn sample_queries(db: &Database, num_queries: usize, config: &QueryConfig) -
         queries.push(GroundTruthQuery {
             sequence: mutated_seq,
             original_pid: pid as ProteinId,
+            truth: pid as ProteinId,
             original_pos: start,
             mutation_info: format!("sub:{}, indel:{}", config.sub_rate, config.indel_rate),
         });
     }
+    if config.truth == GroundTruth::Exhaustive {
+        let scoring = Scoring::blosum62();
+        let targets: Vec<&[u8]> = (0..db.len()).map(|i| db.get(i).unwrap().1).collect();
+        for q in queries.iter_mut() {
+            let scores = align_scores(&QueryProfile::new(&q.sequence, &scoring), &targets);
+            let best = scores.iter().copied().max().unwrap_or(0);
+            if scores[q.original_pid as usize] < best {
+                q.truth = scores.iter().position(|&s| s == best).unwrap() as ProteinId;
+            }
+        }
+    }
     queries
 }
 
@@ -39,33 +39,9 @@ pub const AA_TO_INT: [u8; 256] = [
     // 91-96 (Symbols [ \ ] ^ _ `) -> All Invalid
     255, 255, 255, 255, 255, 255, 
 
-    // Lowercase Amino Acids (97-122) - Just in case
-    0,   // 97: a
-    255, // 98: b
-    1,   // 99: c
-    2,   // 100: d
-    3,   // 101: e
-    4,   // 102: f
-    5,   // 103: g
-    6,   // 104: h
-    7,   // 105: i
-    255, // 106: j
-    8,   // 107: k
-    9,   // 108: l
-    10,  // 109: m
-    11,  // 110: n
-    255, // 111: o
-    12,  // 112: p
-    13,  // 113: q
-    14,  // 114: r
-    15,  // 115: s
-    16,  // 116: t
-    255, // 117: u
-    17,  // 118: v
-    18,  // 119: w
-    255, // 120: x
-    19,  // 121: y
-    255, // 122: z
+    // 97-122 Lowercase -> Invalid: lowercase marks soft-masked residues, which never seed
+    255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, // 97-112
+    255, 255, 255, 255, 255, 255, 255, 255, 255, 255,                               // 113-122
 
     // 123-255 (Symbols, Extended ASCII) -> All Invalid
     255, 255, 255, 255, 255, // 123-127
@@ -100,6 +76,36 @@ pub fn encode_kmer(seq: &[u8]) -> Option<u64> {
     Some(encoded)
 }
 
+/// Like `encode_kmer`, but a single X is expanded into all 20 residues (X as wildcard).
+/// K-mers with more than one X, or any other invalid residue, yield nothing.
+pub fn encode_kmer_wildcard<F: FnMut(u64)>(seq: &[u8], mut f: F) {
+    if seq.len() > 12 {
+        return;
+    }
+    let mut encoded: u64 = 0;
+    let mut wildcard = None;
+    for (i, &byte) in seq.iter().enumerate() {
+        let val = AA_TO_INT[byte as usize];
+        if val == INVALID_AA {
+            if byte != b'X' || wildcard.is_some() {
+                return;
+            }
+            wildcard = Some(seq.len() - 1 - i);
+            encoded <<= 5;
+            continue;
+        }
+        encoded = (encoded << 5) | (val as u64);
+    }
+    match wildcard {
+        None => f(encoded),
+        Some(shift) => {
+            for val in 0..20u64 {
+                f(encoded | (val << (5 * shift)));
+            }
+        }
+    }
+}
+
 pub fn encode_spaced(seq: &[u8], mask: &[bool]) -> Option<u64> {
     let mut encoded: u64 = 0;
     for (i, &mask_bit) in mask.iter().enumerate() {
@@ -149,15 +155,18 @@ static AA_TO_INDEX: [u8; 256] = {
     table[b'Y' as usize] = 18; table[b'V' as usize] = 19;
     table[b'B' as usize] = 20; table[b'Z' as usize] = 21;
     table[b'X' as usize] = 22; table[b'*' as usize] = 23;
-    // Lowercase support (optional)
-    table[b'a' as usize] = 0;  table[b'r' as usize] = 1; 
-    // ... other lowercase ...
+    // Lowercase scores like uppercase
+    let mut c = b'a';
+    while c <= b'z' {
+        table[c as usize] = table[(c - 32) as usize];
+        c += 1;
+    }
     table
 };
 
-/// Flattened BLOSUM62 matrix (24x24 = 576 elements)
+/// Flattened BLOSUM62 matrix (24x24 = 576 elements), rows/columns in `AA_TO_INDEX` order
 /// Data source: NCBI standard
-static BLOSUM62: [i8; 576] = [
+pub static BLOSUM62: [i8; 576] = [
     // A, R, N, D, C, Q, E, G, H, I, L, K, M, F, P, S, T, W, Y, V, B, Z, X, *
     4, -1, -2, -2,  0, -1, -1,  0, -2, -1, -1, -1, -1, -2, -1,  1,  0, -3, -2,  0, -2, -1,  0, -4, // A
    -1,  5,  0, -2, -3,  1,  0, -2,  0, -3, -2,  2, -1, -3, -2, -1, -1, -3, -2, -3, -1,  0, -1, -4, // R
@@ -185,6 +194,12 @@ static BLOSUM62: [i8; 576] = [
    -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4,  1, // *
 ];
 
+/// Row/column of `a` in `BLOSUM62` (ARNDCQEGHILKMFPSTWYV, then B, Z, X, *); case-insensitive.
+#[inline(always)]
+pub fn matrix_index(a: u8) -> usize {
+    AA_TO_INDEX[a as usize] as usize
+}
+
 #[inline(always)]
 pub fn get_score(a: u8, b: u8) -> i32 {
     // Two array lookups (very fast, even vectorizable)
@@ -1,88 +1,215 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader,Read};
 use std::path::Path;
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
+use rustc_hash::FxHashMap;
 
-use flate2::read::GzDecoder; // gzip decoder
-use zstd::stream::read::Decoder as ZstdDecoder; // zstd decoder
+use crate::core::fasta::FastaReader;
+use crate::core::residues::{ResidueMapper, ResiduePolicy};
 
 pub struct Database {
-    pub ids: Vec<String>,
     pub data: Vec<u8>,        
     pub offsets: Vec<usize>,  // Flattened offsets for quick access
+    pub headers: String,      // Full header lines (without '>'), concatenated
+    pub header_offsets: Vec<usize>,
+    pub id_spans: Vec<(u32, u32)>, // ID (first header word) as start within its header and length
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Database {
+    pub fn new() -> Self {
+        Database {
+            data: Vec::new(),
+            offsets: vec![0],
+            headers: String::new(),
+            header_offsets: vec![0],
+            id_spans: Vec::new(),
+        }
+    }
+
+    /// Appends one record. `header` is the full FASTA header without the leading '>'.
+    pub fn push(&mut self, header: &str, seq: &[u8]) {
+        self.data.extend_from_slice(seq);
+        self.finish_record(header);
+    }
+
+
+    /// Appends a record whose residues were already written to `data`.
+    pub(crate) fn finish_record(&mut self, header: &str) {
+        let rest = header.trim_start();
+        let start = header.len() - rest.len();
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.id_spans.push((start as u32, len as u32));
+        self.headers.push_str(header);
+        self.header_offsets.push(self.headers.len());
+        self.offsets.push(self.data.len());
+    }
+
     pub fn load_from_fasta<P: AsRef<Path>>(path:P) -> Result<Self> {
+        Self::load_from_fasta_with(path, None)
+    }
+
+    /// Loads a FASTA file, normalizing residues with `residues` if given (raw bytes otherwise).
+    pub fn load_from_fasta_with<P: AsRef<Path>>(path: P, residues: Option<&ResidueMapper>) -> Result<Self> {
         let path = path.as_ref();
-        let file = File::open(path)
-            .with_context(|| format!("Failed to open file: {:?}", path))?;
-        let reader :Box<dyn Read> = match path.extension().and_then(|s| s.to_str()){
-            Some("gz") => {
-                println!("Detected gzip file");
-                Box::new(GzDecoder::new(file))
+        let mut reader = FastaReader::open(path)?;
+        let mut db = Database::new();
+        db.data.reserve(100 * 1024 * 1024);
+        let mut start = 0;
+        let mut header = String::new();
+        while reader.read_record_into(&mut db.data, &mut header)? {
+            match residues {
+                Some(mapper) => mapper.finish_record(&mut db, start, &header),
+                None => db.finish_record(&header),
             }
-            Some("zst") => {
-                println!("Detected zstd file");
-                Box::new(ZstdDecoder::new(file)?)
-            }
-            _ => {
-                Box::new(BufReader::new(file))
-            }
-        };
-        let reader = BufReader::new(reader);
-        let mut ids = Vec::new();
-        let mut data = Vec::with_capacity(100 * 1024 * 1024); 
-        let mut offsets = vec![0];
-
-        let mut current_id = String::new();
-        let mut in_record = false;
-        for line_res in reader.lines() {
-            let line = line_res.context("Fail to read line")?;
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-            if line.starts_with(">") {
-                if in_record {
-                    ids.push(current_id);
-                    offsets.push(data.len());
-                }
-            
-                current_id = line[1..].split_whitespace().next().unwrap_or("unknown").to_string();
-                
-                in_record = true;
-        } else{
-            data.extend_from_slice(line.as_bytes());
+            start = db.data.len();
         }
+        log::info!("Loaded {} proteins from {:?}", db.len(), path);
+        Ok(db)
     }
-    if in_record {
-        ids.push(current_id);
-        offsets.push(data.len());
+
+    /// Opens `path` for chunked reading, at most `max_residues` residues per chunk.
+    pub fn chunks<P: AsRef<Path>>(path: P, max_residues: usize) -> Result<DatabaseChunks> {
+        Self::chunks_within(path, move |residues, _, _| residues <= max_residues)
     }
-    println!("Loaded {} proteins from {:?}", ids.len(), path);
-        
-        Ok(Database {
-            ids,
-            data,
-            offsets,
+
+    /// Opens `path` for chunked reading. A chunk grows while `fits(residues, proteins,
+    /// header_bytes)` holds for it with the next record added; a record that does not
+    /// fit on its own is an error.
+    pub fn chunks_within<P, F>(path: P, fits: F) -> Result<DatabaseChunks>
+    where
+        P: AsRef<Path>,
+        F: Fn(usize, usize, usize) -> bool + 'static,
+    {
+        Ok(DatabaseChunks {
+            reader: FastaReader::open(path)?,
+            fits: Box::new(fits),
+            next_base: 0,
+            pending: None,
+            residues: None,
         })
     }
     pub fn len(&self) -> usize {
-        self.ids.len()
+        self.id_spans.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.ids.is_empty()
+        self.id_spans.is_empty()
+    }
+
+    /// Accession: the first word of the header, "unknown" for a blank one.
+    /// Panics if `index` is out of range.
+    pub fn id(&self, index: usize) -> &str {
+        let (start, len) = self.id_spans[index];
+        if len == 0 {
+            return "unknown";
+        }
+        let start = self.header_offsets[index] + start as usize;
+        &self.headers[start..start + len as usize]
+    }
+
+    /// Full header line (BLAST `stitle`): ID plus description.
+    pub fn title(&self, index: usize) -> Option<&str> {
+        if index < self.len() {
+            Some(&self.headers[self.header_offsets[index]..self.header_offsets[index + 1]])
+        } else {
+            None
+        }
+    }
+
+    /// Header text after the ID, e.g. "Titin OS=Homo sapiens OX=9606 GN=TTN".
+    pub fn description(&self, index: usize) -> Option<&str> {
+        let title = self.title(index)?;
+        let (start, len) = self.id_spans[index];
+        Some(title[(start + len) as usize..].trim_start())
+    }
+
+    /// Accession -> index map, borrowing the IDs. The first record wins on duplicates.
+    pub fn accession_index(&self) -> FxHashMap<&str, usize> {
+        let mut map = FxHashMap::with_capacity_and_hasher(self.len(), Default::default());
+        for i in 0..self.len() {
+            map.entry(self.id(i)).or_insert(i);
+        }
+        map
     }
+
     pub fn get(&self, index: usize) -> Option<(&str, &[u8])> {
-        if index < self.ids.len() {
+        if index < self.len() {
             let start = self.offsets[index];
             let end = self.offsets[index + 1];
-            Some((&self.ids[index], &self.data[start..end]))
+            Some((self.id(index), &self.data[start..end]))
         } else {
             None
         }
     }
 }
 
+/// A slice of the database file, indexed independently.
+/// Global protein ID = `base` + local index.
+pub struct DatabaseChunk {
+    pub base: usize,
+    pub db: Database,
+}
+
+/// Reads a FASTA file as a sequence of bounded `Database` chunks.
+pub struct DatabaseChunks {
+    reader: FastaReader,
+    fits: Box<dyn Fn(usize, usize, usize) -> bool>,
+    next_base: usize,
+    pending: Option<(String, Vec<u8>)>,
+    residues: Option<ResidueMapper>,
+}
+
+impl DatabaseChunks {
+    /// Normalizes every record with `policy`; global IDs then count split fragments.
+    pub fn with_residue_policy(mut self, policy: &ResiduePolicy) -> Self {
+        self.residues = Some(policy.mapper());
+        self
+    }
+
+    fn push(&self, db: &mut Database, header: &str, seq: &[u8]) {
+        match &self.residues {
+            Some(mapper) => mapper.push(db, header, seq),
+            None => db.push(header, seq),
+        }
+    }
+
+    pub fn next_chunk(&mut self) -> Result<Option<DatabaseChunk>> {
+        let mut db = Database::new();
+        let mut seq = Vec::new();
+        loop {
+            // The record that did not fit into the previous chunk starts this one
+            let header = match self.pending.take() {
+                Some((header, pending)) => {
+                    seq = pending;
+                    header
+                }
+                None => {
+                    seq.clear();
+                    let Some(header) = self.reader.read_record(&mut seq)? else {
+                        break;
+                    };
+                    header
+                }
+            };
+            if !(self.fits)(db.data.len() + seq.len(), db.len() + 1, db.headers.len() + header.len()) {
+                if db.is_empty() {
+                    bail!("Record {} ({}, {} residues) does not fit in a chunk on its own",
+                        self.next_base + 1, header.split_whitespace().next().unwrap_or(""), seq.len());
+                }
+                self.pending = Some((header, std::mem::take(&mut seq)));
+                break;
+            }
+            self.push(&mut db, &header, &seq);
+        }
+        if db.is_empty() {
+            return Ok(None);
+        }
+        let base = self.next_base;
+        self.next_base += db.len();
+        Ok(Some(DatabaseChunk { base, db }))
+    }
+}
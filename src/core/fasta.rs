@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+
+use bzip2::read::MultiBzDecoder; // bzip2 decoder
+use flate2::read::MultiGzDecoder; // gzip decoder (bgzip / concatenated members too)
+use xz2::read::XzDecoder; // xz decoder
+use zstd::stream::read::Decoder as ZstdDecoder; // zstd decoder
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// Identifies the format from the first bytes of the stream, ignoring the file name.
+    pub fn sniff(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Opens `path` ("-" = stdin) and transparently decompresses it based on its magic bytes.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    let raw: Box<dyn Read> = if path == Path::new("-") {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(File::open(path).with_context(|| format!("Failed to open file: {:?}", path))?)
+    };
+    let mut raw = BufReader::new(raw);
+    let magic = raw.fill_buf().with_context(|| format!("Failed to read {:?}", path))?;
+    let compression = Compression::sniff(magic);
+    if compression != Compression::None {
+        log::info!("Detected {:?} compression in {:?}", compression, path);
+    }
+    Ok(match compression {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(raw)),
+        Compression::Zstd => Box::new(ZstdDecoder::new(raw)?),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(raw)),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(raw)),
+        Compression::None => Box::new(raw),
+    })
+}
+
+/// Bytes of the read buffer each `FastaReader` holds.
+pub const READ_BUFFER_BYTES: usize = 1 << 20;
+
+/// Record-at-a-time FASTA reader, so callers can stream files larger than RAM.
+/// Lines are parsed in place in the read buffer: no per-line String or copy, and no
+/// UTF-8 validation of sequence lines.
+///
+/// Accepted input: LF or CRLF line endings, blank lines, `;` comment lines,
+/// headers with empty sequences, and whitespace or digits inside sequence lines
+/// (both are dropped, as in GenBank-style numbered sequences). Residues before the
+/// first header are kept as a prefix of the first record.
+pub struct FastaReader {
+    reader: BufReader<Box<dyn Read>>,
+    source: String,
+    // A line split across two buffer fills
+    partial: Vec<u8>,
+    line_no: usize,
+    // Header of the next record, read while finishing the current one
+    next_header: String,
+    has_next: bool,
+    // First line of residues seen before any header
+    headerless_line: Option<usize>,
+}
+
+impl FastaReader {
+    /// Opens a plain or compressed FASTA file, or stdin for "-".
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = open_input(path)?;
+        Ok(Self::from_reader(reader, &path.display().to_string()))
+    }
+
+    /// Reads already opened (and decompressed) input; `source` names it in errors.
+    pub fn from_reader(reader: Box<dyn Read>, source: &str) -> Self {
+        Self {
+            reader: BufReader::with_capacity(READ_BUFFER_BYTES, reader),
+            source: source.to_string(),
+            partial: Vec::new(),
+            line_no: 0,
+            next_header: String::new(),
+            has_next: false,
+            headerless_line: None,
+        }
+    }
+
+    /// Reads the next record, appending its residues to `data`.
+    /// Returns the full header (without '>'), or None at end of input.
+    /// Errors carry a `file:line:column` location.
+    pub fn read_record(&mut self, data: &mut Vec<u8>) -> Result<Option<String>> {
+        let mut header = String::new();
+        Ok(self.read_record_into(data, &mut header)?.then_some(header))
+    }
+
+    /// `read_record` into a reused `header` buffer, so a whole-file load does not
+    /// allocate per record. Returns false at end of input.
+    pub fn read_record_into(&mut self, data: &mut Vec<u8>, header: &mut String) -> Result<bool> {
+        let mut current = std::mem::take(&mut self.has_next);
+        if current {
+            std::mem::swap(header, &mut self.next_header);
+        }
+        let start = data.len();
+        loop {
+            let buf = self.reader.fill_buf()
+                .with_context(|| format!("{}:{}: failed to read", self.source, self.line_no + 1))?;
+            let (line, used) = match memchr::memchr(b'\n', buf) {
+                Some(i) if self.partial.is_empty() => (&buf[..i], i + 1),
+                Some(i) => {
+                    self.partial.extend_from_slice(&buf[..i]);
+                    (&self.partial[..], i + 1)
+                }
+                // Last line without a newline
+                None if buf.is_empty() && !self.partial.is_empty() => (&self.partial[..], 0),
+                None if buf.is_empty() => break,
+                None => {
+                    let n = buf.len();
+                    self.partial.extend_from_slice(buf);
+                    self.reader.consume(n);
+                    continue;
+                }
+            };
+            self.line_no += 1;
+            let found = parse_line(line, data).map_err(|col| anyhow!("{}:{}:{}: unexpected byte 0x{:02x} in sequence",
+                    self.source, self.line_no, col + 1, line[col]))?;
+            let done = match found {
+                Some(text) if current => {
+                    set_header(&mut self.next_header, text);
+                    self.has_next = true;
+                    true
+                }
+                Some(text) => {
+                    set_header(header, text);
+                    current = true;
+                    // Any residues before it belong to this record
+                    self.headerless_line = None;
+                    false
+                }
+                None => {
+                    if !current && self.headerless_line.is_none() && data.len() > start {
+                        self.headerless_line = Some(self.line_no);
+                    }
+                    false
+                }
+            };
+            self.reader.consume(used);
+            self.partial.clear();
+            if done {
+                return Ok(true);
+            }
+        }
+        if !current
+            && let Some(line_no) = self.headerless_line.take() {
+            bail!("{}:{}:1: sequence data without a '>' header", self.source, line_no);
+        }
+        Ok(current)
+    }
+}
+
+// Header bytes are taken as UTF-8, with invalid sequences replaced
+fn set_header(header: &mut String, text: &[u8]) {
+    header.clear();
+    match std::str::from_utf8(text) {
+        Ok(text) => header.push_str(text),
+        Err(_) => header.push_str(&String::from_utf8_lossy(text)),
+    }
+}
+
+/// One line without its '\n': returns the header text of a '>' line, appends the
+/// residues of a sequence line to `data`, and skips blank and `;` lines.
+/// Errors give the column.
+#[inline]
+fn parse_line<'a>(line: &'a [u8], data: &mut Vec<u8>) -> std::result::Result<Option<&'a [u8]>, usize> {
+    let trimmed = line.trim_ascii();
+    match trimmed.first() {
+        None | Some(b';') => Ok(None),
+        Some(b'>') => Ok(Some(&trimmed[1..])),
+        Some(_) => {
+            let indent = line.len() - line.trim_ascii_start().len();
+            append_residues(trimmed, data).map(|_| None).map_err(|col| indent + col)
+        }
+    }
+}
+
+#[inline]
+fn is_residue(b: u8) -> bool {
+    b.is_ascii_graphic() && !b.is_ascii_digit()
+}
+
+/// Appends residues of one trimmed sequence line. Whitespace and digits are skipped;
+/// any other non-printable or non-ASCII byte is an error at the returned column.
+#[inline]
+fn append_residues(line: &[u8], data: &mut Vec<u8>) -> std::result::Result<(), usize> {
+    // Fast path: a clean line is copied in one go. No early exit, so the check vectorizes.
+    if line.iter().fold(true, |clean, &b| clean & is_residue(b)) {
+        data.extend_from_slice(line);
+        return Ok(());
+    }
+    for (col, &b) in line.iter().enumerate() {
+        if is_residue(b) {
+            data.push(b);
+        } else if !(b.is_ascii_whitespace() || b.is_ascii_digit()) {
+            return Err(col);
+        }
+    }
+    Ok(())
+}
@@ -1,2 +1,6 @@
 pub mod alphabet;
-pub mod database;
\ No newline at end of file
+pub mod database;
+pub mod fasta;
+pub mod residues;
+pub mod translate;
+pub mod validate;
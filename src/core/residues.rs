@@ -0,0 +1,146 @@
+// How non-standard residues are treated, applied once when sequences are loaded so that
+// seeding (`AA_TO_INT`), scoring (`get_score`) and the extension kernels all see the same
+// letters. Case is kept: lowercase is a soft mask (no seeding, scored like uppercase).
+// Everything else is opt-in.
+use crate::core::database::Database;
+
+/// What X (unknown residue) does to a seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum UnknownResidue {
+    /// A k-mer containing X is not indexed
+    #[default]
+    Break,
+    /// A single X in a database k-mer matches any residue (contiguous seeds only)
+    Wildcard,
+}
+
+/// What '*' (stop / terminator) does to a sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum StopResidue {
+    /// Keep '*' as a residue; it breaks seeds like any invalid letter
+    #[default]
+    Keep,
+    /// Split the record at '*' into fragments ID_1, ID_2, ...
+    Split,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::Args)]
+pub struct ResiduePolicy {
+    /// Upper-case the input instead of treating lowercase residues as soft-masked
+    #[arg(long, global = true)]
+    pub ignore_lowercase: bool,
+    /// Map selenocysteine U to C and pyrrolysine O to K
+    #[arg(long, global = true)]
+    pub map_rare: bool,
+    /// Map ambiguity codes B, Z, J to D, E, L
+    #[arg(long, global = true)]
+    pub map_ambiguous: bool,
+    /// X inside seeds
+    #[arg(long = "x-policy", value_enum, global = true, default_value_t)]
+    pub unknown: UnknownResidue,
+    /// '*' inside sequences
+    #[arg(long = "stop-policy", value_enum, global = true, default_value_t)]
+    pub stop: StopResidue,
+}
+
+impl ResiduePolicy {
+    pub fn mapper(&self) -> ResidueMapper {
+        let mut table = [0u8; 256];
+        for (b, slot) in table.iter_mut().enumerate() {
+            *slot = b as u8;
+        }
+        let mut map = |from: u8, to: u8| {
+            table[from as usize] = to;
+            table[from.to_ascii_lowercase() as usize] = to.to_ascii_lowercase();
+        };
+        if self.map_rare {
+            map(b'U', b'C');
+            map(b'O', b'K');
+        }
+        if self.map_ambiguous {
+            map(b'B', b'D');
+            map(b'Z', b'E');
+            map(b'J', b'L');
+        }
+        if self.ignore_lowercase {
+            for slot in table.iter_mut() {
+                slot.make_ascii_uppercase();
+            }
+        }
+        ResidueMapper { table, split_stops: self.stop == StopResidue::Split }
+    }
+}
+
+/// Shown when input is mostly lowercase, which otherwise silently finds nothing.
+pub const LOWERCASE_WARNING: &str =
+    "lowercase residues are soft-masked and never seed; use --ignore-lowercase if the input is lowercase by convention";
+
+/// True when more than half of the letters in `seq` are lowercase.
+pub fn is_mostly_lowercase(seq: &[u8]) -> bool {
+    let (lower, letters) = seq.iter().fold((0, 0), |(lower, letters), &b| {
+        (lower + usize::from(b.is_ascii_lowercase()), letters + usize::from(b.is_ascii_alphabetic()))
+    });
+    2 * lower > letters
+}
+
+/// Records of `db` that are mostly lowercase.
+pub fn count_mostly_lowercase(db: &Database) -> usize {
+    (0..db.len()).filter(|&i| is_mostly_lowercase(db.get(i).unwrap().1)).count()
+}
+
+/// Byte translation table plus splitting rule, built from a `ResiduePolicy`.
+pub struct ResidueMapper {
+    table: [u8; 256],
+    split_stops: bool,
+}
+
+impl ResidueMapper {
+    pub fn normalize(&self, seq: &mut [u8]) {
+        for b in seq.iter_mut() {
+            *b = self.table[*b as usize];
+        }
+    }
+
+    /// Appends one record to `db`, normalized and split at '*' if configured.
+    pub fn push(&self, db: &mut Database, header: &str, seq: &[u8]) {
+        let start = db.data.len();
+        db.data.extend_from_slice(seq);
+        self.finish_record(db, start, header);
+    }
+
+    /// Closes a record whose raw residues were appended to `db.data` from `start`.
+    /// A record with a single non-empty fragment (e.g. a trailing '*') keeps its header.
+    pub(crate) fn finish_record(&self, db: &mut Database, start: usize, header: &str) {
+        self.normalize(&mut db.data[start..]);
+        if !self.split_stops || !db.data[start..].contains(&b'*') {
+            db.finish_record(header);
+            return;
+        }
+        let residues = db.data.split_off(start);
+        let fragments: Vec<&[u8]> = residues.split(|&b| b == b'*').filter(|f| !f.is_empty()).collect();
+        if fragments.len() <= 1 {
+            db.data.extend_from_slice(fragments.first().copied().unwrap_or_default());
+            db.finish_record(header);
+            return;
+        }
+        let header = header.trim_start();
+        let (id, description) = header.split_once(char::is_whitespace).unwrap_or((header, ""));
+        for (n, fragment) in fragments.iter().enumerate() {
+            db.data.extend_from_slice(fragment);
+            let title = format!("{}_{} {}", id, n + 1, description.trim_start());
+            db.finish_record(title.trim_end());
+        }
+    }
+
+    /// Normalized (and possibly split) copies of one record, as (ID, residues) pairs.
+    pub fn fragments(&self, header: &str, seq: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut db = Database::new();
+        self.push(&mut db, header, seq);
+        (0..db.len())
+            .map(|i| {
+                let (id, seq) = db.get(i).unwrap();
+                (id.to_string(), seq.to_vec())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,296 @@
+// Nucleotide to protein translation with the NCBI genetic codes, for translated searches.
+//
+// Codons are indexed in NCBI order (first base T, C, A, G; then the second; then the third),
+// so each code is the 64-letter `AAs` string from NCBI's gc.prt. Codons with an ambiguous
+// base (N, R, Y, ...) translate to X. Stops are '*', which breaks seeds like any invalid
+// residue. A codon with a lowercase base translates to a lowercase residue, so soft-masked
+// DNA stays soft-masked after translation.
+use std::path::Path;
+use anyhow::{bail, Result};
+use crate::core::database::Database;
+use crate::core::fasta::FastaReader;
+use crate::core::residues::ResidueMapper;
+
+/// Molecule type of a sequence set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SequenceType {
+    #[default]
+    Protein,
+    /// DNA or RNA, translated in six frames
+    #[value(alias = "dna")]
+    Nucleotide,
+}
+
+/// NCBI translation tables: (id, name, amino acids in TCAG codon order).
+const TABLES: &[(u8, &str, &[u8; 64])] = &[
+    (1, "Standard", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (2, "Vertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"),
+    (3, "Yeast Mitochondrial", b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (4, "Mold, Protozoan and Coelenterate Mitochondrial; Mycoplasma/Spiroplasma",
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (5, "Invertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG"),
+    (6, "Ciliate, Dasycladacean and Hexamita Nuclear",
+        b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (9, "Echinoderm and Flatworm Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (10, "Euplotid Nuclear", b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (11, "Bacterial, Archaeal and Plant Plastid", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (12, "Alternative Yeast Nuclear", b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (13, "Ascidian Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG"),
+    (14, "Alternative Flatworm Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (16, "Chlorophycean Mitochondrial", b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (21, "Trematode Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (22, "Scenedesmus obliquus Mitochondrial", b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (23, "Thraustochytrium Mitochondrial", b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (24, "Rhabdopleuridae Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+    (25, "Candidate Division SR1 and Gracilibacteria", b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (26, "Pachysolen tannophilus Nuclear", b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (27, "Karyorelict Nuclear", b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (28, "Condylostoma Nuclear", b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (29, "Mesodinium Nuclear", b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (30, "Peritrich Nuclear", b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (31, "Blastocrithidia Nuclear", b"FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (33, "Cephalodiscidae Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct GeneticCode {
+    pub id: u8,
+    pub name: &'static str,
+    amino_acids: &'static [u8; 64],
+}
+
+impl Default for GeneticCode {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl GeneticCode {
+    pub fn standard() -> Self {
+        let (id, name, amino_acids) = TABLES[0];
+        Self { id, name, amino_acids }
+    }
+
+    /// Looks up an NCBI translation table by number (1 = standard, 11 = bacterial, ...).
+    pub fn ncbi(id: u8) -> Result<Self> {
+        match TABLES.iter().find(|t| t.0 == id) {
+            Some(&(id, name, amino_acids)) => Ok(Self { id, name, amino_acids }),
+            None => {
+                let known: Vec<String> = TABLES.iter().map(|t| t.0.to_string()).collect();
+                bail!("Unknown genetic code {}; NCBI tables are {}", id, known.join(", "))
+            }
+        }
+    }
+
+    /// Residue for one codon; X if any base is ambiguous.
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        let lowercase = codon[..3].iter().any(u8::is_ascii_lowercase);
+        let mut index = 0;
+        for &b in &codon[..3] {
+            let base = match b.to_ascii_uppercase() {
+                b'T' | b'U' => 0,
+                b'C' => 1,
+                b'A' => 2,
+                b'G' => 3,
+                _ => return if lowercase { b'x' } else { b'X' },
+            };
+            index = index * 4 + base;
+        }
+        let aa = self.amino_acids[index];
+        if lowercase { aa.to_ascii_lowercase() } else { aa }
+    }
+}
+
+/// Reading frame: +1, +2, +3 on the given strand, -1, -2, -3 on its reverse complement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Frame(i8);
+
+impl Frame {
+    pub const ALL: [Frame; 6] = [Frame(1), Frame(2), Frame(3), Frame(-1), Frame(-2), Frame(-3)];
+
+    pub fn new(frame: i8) -> Option<Self> {
+        matches!(frame.abs(), 1..=3).then_some(Self(frame))
+    }
+
+    pub fn get(self) -> i8 {
+        self.0
+    }
+
+    pub fn is_reverse(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Frame whose codons start at `offset` on the forward (or reverse) strand.
+    pub fn at_offset(offset: usize, reverse: bool) -> Self {
+        let frame = (offset % 3) as i8 + 1;
+        Self(if reverse { -frame } else { frame })
+    }
+
+    /// Offset of the first codon on the frame's strand.
+    pub fn offset(self) -> usize {
+        (self.0.unsigned_abs() - 1) as usize
+    }
+
+    /// Strand offsets (0-based, half-open) of residues `aa_start..aa_end` of this frame.
+    pub fn strand_range(self, aa_start: usize, aa_end: usize) -> (usize, usize) {
+        (self.offset() + 3 * aa_start, self.offset() + 3 * aa_end)
+    }
+
+    /// 1-based, inclusive nucleotide coordinates on the forward strand of residues
+    /// `aa_start..aa_end` (0-based, half-open) of this frame's translation of a sequence
+    /// `dna_len` long. Reverse frames give start > end, as BLAST reports them.
+    pub fn nucleotide_range(self, aa_start: usize, aa_end: usize, dna_len: usize) -> (usize, usize) {
+        let (from, to) = self.strand_range(aa_start, aa_end);
+        forward_range(from, to, dna_len, self.is_reverse())
+    }
+}
+
+/// 1-based, inclusive forward-strand coordinates of strand offsets `from..to` (0-based,
+/// half-open) of a sequence `dna_len` long; start > end on the reverse strand.
+pub fn forward_range(from: usize, to: usize, dna_len: usize, reverse: bool) -> (usize, usize) {
+    if reverse {
+        (dna_len - from, dna_len - to + 1)
+    } else {
+        (from + 1, to)
+    }
+}
+
+/// 1-based forward-strand coordinate of strand offset `offset`.
+pub fn forward_position(offset: usize, dna_len: usize, reverse: bool) -> usize {
+    if reverse { dna_len - offset } else { offset + 1 }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:+}", self.0)
+    }
+}
+
+/// Reverse complement, keeping case; IUPAC ambiguity codes are complemented too.
+pub fn reverse_complement(dna: &[u8]) -> Vec<u8> {
+    dna.iter()
+        .rev()
+        .map(|&b| {
+            let c = match b.to_ascii_uppercase() {
+                b'A' => b'T',
+                b'T' | b'U' => b'A',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'R' => b'Y',
+                b'Y' => b'R',
+                b'K' => b'M',
+                b'M' => b'K',
+                b'B' => b'V',
+                b'V' => b'B',
+                b'D' => b'H',
+                b'H' => b'D',
+                other => other,
+            };
+            if b.is_ascii_lowercase() { c.to_ascii_lowercase() } else { c }
+        })
+        .collect()
+}
+
+/// Translation of one frame; a trailing partial codon is dropped.
+pub fn translate(dna: &[u8], frame: Frame, code: &GeneticCode) -> Vec<u8> {
+    if frame.is_reverse() {
+        translate_strand(&reverse_complement(dna), frame, code)
+    } else {
+        translate_strand(dna, frame, code)
+    }
+}
+
+/// Translation of `frame` from the strand it reads (the reverse complement for - frames).
+fn translate_strand(strand: &[u8], frame: Frame, code: &GeneticCode) -> Vec<u8> {
+    strand.get(frame.offset()..)
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .map(|codon| code.translate_codon(codon))
+        .collect()
+}
+
+/// All six frames, in `Frame::ALL` order.
+pub fn six_frames(dna: &[u8], code: &GeneticCode) -> Vec<(Frame, Vec<u8>)> {
+    let reverse = reverse_complement(dna);
+    Frame::ALL.iter()
+        .map(|&frame| {
+            let strand = if frame.is_reverse() { &reverse } else { dna };
+            (frame, translate_strand(strand, frame, code))
+        })
+        .collect()
+}
+
+/// Source of one record of a `TranslatedDatabase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameOrigin {
+    /// Index of the nucleotide record
+    pub record: usize,
+    pub frame: Frame,
+}
+
+/// A nucleotide database searched as proteins (tblastn): every record is stored as its
+/// six translations (records 6r..6r+6 in `Frame::ALL` order), each a record of `proteins`
+/// with the source header, so the k-mer
+/// and spaced indexes and the whole pipeline run on it unchanged. The nucleotides
+/// themselves are not kept.
+pub struct TranslatedDatabase {
+    pub proteins: Database,
+    /// Per record of `proteins`
+    pub origins: Vec<FrameOrigin>,
+    /// Length in nucleotides of each source record
+    pub lengths: Vec<usize>,
+    pub code: GeneticCode,
+}
+
+impl TranslatedDatabase {
+    pub fn new(code: GeneticCode) -> Self {
+        Self { proteins: Database::new(), origins: Vec::new(), lengths: Vec::new(), code }
+    }
+
+    /// Appends the six frames of one nucleotide record, normalized with `residues` if given.
+    pub fn push(&mut self, header: &str, dna: &[u8], residues: Option<&ResidueMapper>) {
+        let record = self.lengths.len();
+        self.lengths.push(dna.len());
+        for (frame, mut protein) in six_frames(dna, &self.code) {
+            if let Some(mapper) = residues {
+                mapper.normalize(&mut protein);
+            }
+            self.proteins.push(header, &protein);
+            self.origins.push(FrameOrigin { record, frame });
+        }
+    }
+
+    /// Translates a nucleotide FASTA record by record.
+    pub fn load_from_fasta<P: AsRef<Path>>(path: P, code: GeneticCode, residues: Option<&ResidueMapper>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = FastaReader::open(path)?;
+        let mut db = Self::new(code);
+        let mut dna = Vec::new();
+        while let Some(header) = reader.read_record(&mut dna)? {
+            db.push(&header, &dna, residues);
+            dna.clear();
+        }
+        log::info!("Translated {} nucleotide records ({} nt) from {:?} with genetic code {}",
+                db.records(), db.lengths.iter().sum::<usize>(), path, code.id);
+        Ok(db)
+    }
+
+    /// Number of nucleotide records.
+    pub fn records(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// The three translations of one strand of nucleotide record `record`, indexed by
+    /// codon offset (`Frame::offset`), as `align_frameshift` takes them.
+    pub fn strand_frames(&self, record: usize, reverse: bool) -> [&[u8]; 3] {
+        let first = 6 * record + if reverse { 3 } else { 0 };
+        [0, 1, 2].map(|i| self.proteins.get(first + i).unwrap().1)
+    }
+
+    /// Nucleotide coordinates of residues `aa_start..aa_end` of translated record `index`,
+    /// as `Frame::nucleotide_range` gives them.
+    pub fn nucleotide_range(&self, index: usize, aa_start: usize, aa_end: usize) -> (usize, usize) {
+        let origin = self.origins[index];
+        origin.frame.nucleotide_range(aa_start, aa_end, self.lengths[origin.record])
+    }
+}
@@ -0,0 +1,225 @@
+// Database sanity checks: what the loader accepted, and what the index will silently skip.
+// Standard residues are the 20 letters `AA_TO_INT` encodes, in either case (lowercase is
+// only a soft mask). Other letters (B, J, O, U, X, Z) are counted as ambiguous; anything
+// else is invalid.
+use anyhow::{anyhow, bail, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+use crate::core::alphabet::{AA_TO_INT, INVALID_AA};
+use crate::core::database::Database;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResidueClass {
+    Standard,
+    Ambiguous,
+    Invalid,
+}
+
+pub fn classify(b: u8) -> ResidueClass {
+    if AA_TO_INT[b.to_ascii_uppercase() as usize] != INVALID_AA {
+        ResidueClass::Standard
+    } else if b.is_ascii_alphabetic() {
+        ResidueClass::Ambiguous
+    } else {
+        ResidueClass::Invalid
+    }
+}
+
+/// A sequence with at least one non-standard residue.
+#[derive(Debug, Clone)]
+pub struct SequenceIssue {
+    pub index: usize,
+    pub ambiguous: usize,
+    pub invalid: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseReport {
+    pub sequences: usize,
+    pub residues: usize,
+    /// Residue counts by byte value, as stored (case preserved)
+    pub composition: Vec<u64>,
+    /// Bucket i counts sequences with length in [2^i, 2^(i+1)); empty records are not counted
+    pub length_histogram: Vec<usize>,
+    pub min_len: usize,
+    pub max_len: usize,
+    pub issues: Vec<SequenceIssue>,
+    /// Accessions seen more than once, with every index they occur at
+    pub duplicates: Vec<(String, Vec<usize>)>,
+    pub empty: Vec<usize>,
+}
+
+impl DatabaseReport {
+    pub fn build(db: &Database) -> Self {
+        let mut composition = vec![0u64; 256];
+        let mut length_histogram = Vec::new();
+        let mut issues = Vec::new();
+        let mut empty = Vec::new();
+        let (mut min_len, mut max_len) = (usize::MAX, 0);
+
+        for i in 0..db.len() {
+            let (_, seq) = db.get(i).unwrap();
+            min_len = min_len.min(seq.len());
+            max_len = max_len.max(seq.len());
+            if seq.is_empty() {
+                empty.push(i);
+                continue;
+            }
+            let bucket = seq.len().ilog2() as usize;
+            if length_histogram.len() <= bucket {
+                length_histogram.resize(bucket + 1, 0);
+            }
+            length_histogram[bucket] += 1;
+
+            let mut issue = SequenceIssue { index: i, ambiguous: 0, invalid: 0 };
+            for &b in seq {
+                composition[b as usize] += 1;
+                match classify(b) {
+                    ResidueClass::Standard => {}
+                    ResidueClass::Ambiguous => issue.ambiguous += 1,
+                    ResidueClass::Invalid => issue.invalid += 1,
+                }
+            }
+            if issue.ambiguous + issue.invalid > 0 {
+                issues.push(issue);
+            }
+        }
+
+        let mut seen: FxHashMap<&str, Vec<usize>> = FxHashMap::default();
+        for i in 0..db.len() {
+            seen.entry(db.id(i)).or_default().push(i);
+        }
+        let mut duplicates: Vec<(String, Vec<usize>)> = seen.into_iter()
+            .filter(|(_, at)| at.len() > 1)
+            .map(|(id, at)| (id.to_string(), at))
+            .collect();
+        duplicates.sort_unstable_by_key(|d| d.1[0]);
+
+        Self {
+            sequences: db.len(),
+            residues: db.data.len(),
+            composition,
+            length_histogram,
+            min_len: if db.is_empty() { 0 } else { min_len },
+            max_len,
+            issues,
+            duplicates,
+            empty,
+        }
+    }
+
+    pub fn ambiguous_residues(&self) -> usize {
+        self.issues.iter().map(|s| s.ambiguous).sum()
+    }
+
+    pub fn invalid_residues(&self) -> usize {
+        self.issues.iter().map(|s| s.invalid).sum()
+    }
+
+    /// Fails if the database has any invalid residue, duplicate accession or empty record.
+    /// Ambiguous residues are legitimate and do not fail the check.
+    pub fn check(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let invalid = self.invalid_residues();
+        if invalid > 0 {
+            let seqs = self.issues.iter().filter(|s| s.invalid > 0).count();
+            problems.push(format!("{} invalid residues in {} sequences", invalid, seqs));
+        }
+        if !self.duplicates.is_empty() {
+            problems.push(format!("{} duplicate accessions", self.duplicates.len()));
+        }
+        if !self.empty.is_empty() {
+            problems.push(format!("{} empty records", self.empty.len()));
+        }
+        if !problems.is_empty() {
+            bail!("Database failed validation: {}", problems.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Prints the report; per-record listings stop after `max_listed` entries.
+    pub fn print(&self, db: &Database, max_listed: usize) {
+        println!("   Sequences: {} ({} residues)", self.sequences, self.residues);
+        if self.sequences > 0 {
+            println!("   Length:    min {}, max {}, mean {:.1}",
+                    self.min_len, self.max_len, self.residues as f64 / self.sequences as f64);
+        }
+
+        println!("   Residue composition:");
+        let mut residues: Vec<(u8, u64)> = self.composition.iter().enumerate()
+            .filter(|&(_, &c)| c > 0)
+            .map(|(b, &c)| (b as u8, c))
+            .collect();
+        residues.sort_unstable_by_key(|r| (std::cmp::Reverse(r.1), r.0));
+        for (b, count) in residues {
+            let label = match classify(b) {
+                ResidueClass::Standard => "",
+                ResidueClass::Ambiguous => " (ambiguous)",
+                ResidueClass::Invalid => " (invalid)",
+            };
+            println!("   {:>4} | {:>12} {:>6.2}%{}", show_byte(b), count,
+                    100.0 * count as f64 / self.residues.max(1) as f64, label);
+        }
+
+        println!("   Length histogram:");
+        for (i, &count) in self.length_histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let lo = 1usize << i;
+            let hi = (1usize << (i + 1)) - 1;
+            println!("   {:>8}-{:<8} | {}", lo, hi, count);
+        }
+
+        println!("   Ambiguous residues: {} | Invalid residues: {} ({} sequences affected)",
+                self.ambiguous_residues(), self.invalid_residues(), self.issues.len());
+        for issue in self.issues.iter().take(max_listed) {
+            println!("     #{} {}: {} ambiguous, {} invalid",
+                    issue.index, db.id(issue.index), issue.ambiguous, issue.invalid);
+        }
+        more(self.issues.len(), max_listed);
+
+        println!("   Duplicate accessions: {}", self.duplicates.len());
+        for (id, at) in self.duplicates.iter().take(max_listed) {
+            println!("     {} at {:?}", id, at);
+        }
+        more(self.duplicates.len(), max_listed);
+
+        println!("   Empty records: {}", self.empty.len());
+        for &i in self.empty.iter().take(max_listed) {
+            println!("     #{} {}", i, db.id(i));
+        }
+        more(self.empty.len(), max_listed);
+    }
+}
+
+/// `DatabaseReport::check` for a database read in pieces (chunks or single records).
+/// Each piece is checked as it arrives. Accessions are kept across pieces to catch
+/// duplicates, so memory grows with their total length.
+#[derive(Debug, Default)]
+pub struct StreamCheck {
+    seen: FxHashSet<String>,
+}
+
+impl StreamCheck {
+    /// Checks `piece`, whose first record is record `base` of the whole database.
+    pub fn check(&mut self, piece: &Database, base: usize) -> Result<()> {
+        DatabaseReport::build(piece).check()
+            .map_err(|e| anyhow!("{} (records {}-{})", e, base, base + piece.len().saturating_sub(1)))?;
+        for i in 0..piece.len() {
+            if !self.seen.insert(piece.id(i).to_string()) {
+                bail!("Database failed validation: duplicate accession {} at record {}", piece.id(i), base + i);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn show_byte(b: u8) -> String {
+    if b.is_ascii_graphic() { (b as char).to_string() } else { format!("0x{:02x}", b) }
+}
+
+fn more(total: usize, shown: usize) {
+    if total > shown {
+        println!("     ... and {} more", total - shown);
+    }
+}
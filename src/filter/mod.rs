@@ -1 +1,3 @@
-pub mod seed;
\ No newline at end of file
+pub mod seed;
+pub mod seg;
+pub mod significance;
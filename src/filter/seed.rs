@@ -1,70 +1,158 @@
 use rustc_hash::FxHashMap;
-use crate::index::kmer::{ProteinId,KmerIndex};
+use crate::index::kmer::{grown_table_bytes, ProteinId, SeedIndex};
 use crate::core::alphabet::encode_kmer;
+use crate::filter::significance::diagonal_pvalue;
 use smallvec::SmallVec;
+use std::mem;
 
 #[derive(Debug, Clone)]
 pub struct Candidate {
     pub id: ProteinId,
     pub score: usize,
     pub best_diagonal: i32,
+    pub pvalue: Option<f64>, // Chance of `score` hits on one diagonal of a random target, with `max_pvalue` only
 }
 
-type HitList = SmallVec<[i32; 8]>;
+pub type HitList = SmallVec<[i32; 8]>;
 
-pub fn find_candidate(
-    kmer_index: &KmerIndex, 
+/// Most populated diagonal and its hit count; the smallest diagonal wins ties.
+/// `diagonals` must be non-empty and is sorted in place.
+pub fn best_diagonal(diagonals: &mut [i32]) -> (i32, usize) {
+    diagonals.sort_unstable();
+    let mut max_diagonal = 0;
+    let mut max_hit = 0;
+    let mut diagonal_count = 0;
+    let mut current_diagonal = diagonals[0];
+    for &diagonal in diagonals.iter() {
+        if diagonal == current_diagonal {
+            diagonal_count += 1;
+        } else{
+            if diagonal_count > max_hit {
+                max_hit = diagonal_count;
+                max_diagonal = current_diagonal;
+            }
+            current_diagonal = diagonal;
+            diagonal_count = 1;
+        }
+    }
+    if diagonal_count > max_hit {
+        max_hit = diagonal_count;
+        max_diagonal = current_diagonal;
+    }
+    (max_diagonal, max_hit)
+}
+
+/// Sliding windows of the query itself, as (query position, encoded k-mer) seeds.
+fn query_words(query_seq: &[u8], k: usize) -> impl Iterator<Item = (usize, u64)> + '_ {
+    let windows: &[u8] = if query_seq.len() > k { query_seq } else { &[] };
+    windows.windows(k)
+        .enumerate()
+        .filter_map(|(q_pos, window)| encode_kmer(window).map(|encoded| (q_pos, encoded)))
+}
+
+pub fn find_candidate<I: SeedIndex>(
+    kmer_index: &I, 
     query_seq: &[u8],
     min_diagonal: usize
 ) -> Vec<Candidate>{
+    let words = query_words(query_seq, kmer_index.k());
+    find_candidate_words(kmer_index, query_seq.len(), words, min_diagonal)
+}
+
+/// `find_candidate` for any (query position, encoded k-mer) seeds, e.g. the
+/// neighborhood words of a profile.
+pub fn find_candidate_words<I: SeedIndex, W: IntoIterator<Item = (usize, u64)>>(
+    kmer_index: &I,
+    query_len: usize,
+    words: W,
+    min_diagonal: usize
+) -> Vec<Candidate> {
+    let mut candidate = collect_candidates(kmer_index, query_len, words, min_diagonal, None);
+    candidate.sort_unstable_by_key(|c| (std::cmp::Reverse(c.score), c.id));
+    candidate
+}
+
+/// Best diagonal of every target with at least `min_diagonal` hits on it. P-values are
+/// computed only under `max_pvalue`, which then also filters the candidates.
+fn collect_candidates<I: SeedIndex, W: IntoIterator<Item = (usize, u64)>>(
+    kmer_index: &I,
+    query_len: usize,
+    words: W,
+    min_diagonal: usize,
+    max_pvalue: Option<f64>
+) -> Vec<Candidate> {
     let mut protein_hit : FxHashMap<ProteinId,HitList> = FxHashMap::default();
+    let mut query_postings = 0;
 
-    // 1. Sliding windows and query
-    if query_seq.len() > kmer_index.k {
-        for (q_pos, window) in query_seq.windows(kmer_index.k).enumerate() {
-            if let Some(encoded) = encode_kmer(window){
-                if let Some(hits) = kmer_index.query(encoded){
-                    for &(pid, t_pos) in hits{
-                        let diagonal = t_pos as i32 - q_pos as i32;
-                        protein_hit.entry(pid).or_default().push(diagonal);
-                    }
-                }
-            }
-        }
+    // 1. Look up every word
+    let k = kmer_index.k();
+    for (q_pos, encoded) in words {
+        query_postings += kmer_index.for_each_posting(encoded, |pid, t_pos| {
+            let diagonal = t_pos as i32 - q_pos as i32;
+            protein_hit.entry(pid).or_default().push(diagonal);
+        });
     }
     // 2. Calculate scores and best diagonals
+    let query_windows = (query_len + 1).saturating_sub(k);
     let mut candidate = Vec::new();
     for (pid, mut diagonals) in protein_hit {
         if diagonals.len() < min_diagonal {
             continue;
         }
-        diagonals.sort_unstable();
-        let mut max_diagonal = 0;
-        let mut max_hit = 0;
-        let mut diagonal_count = 0;
-        let mut current_diagonal = diagonals[0];
-        for &diagonal in diagonals.iter() {
-            if diagonal == current_diagonal {
-                diagonal_count += 1;
-            } else{
-                if diagonal_count > max_hit {
-                    max_hit = diagonal_count;
-                    max_diagonal = current_diagonal;
+        let (max_diagonal, max_hit) = best_diagonal(&mut diagonals);
+        if max_hit < min_diagonal {
+            continue;
+        }
+        let pvalue = match max_pvalue {
+            None => None,
+            Some(max_pvalue) => {
+                let target_len = kmer_index.seq_lens().get(pid as usize).copied().unwrap_or(0) as usize;
+                let target_windows = (target_len + 1).saturating_sub(k);
+                let pvalue = diagonal_pvalue(
+                    max_hit, query_windows, target_windows,
+                    query_postings, kmer_index.total_postings()
+                );
+                if pvalue > max_pvalue {
+                    continue;
                 }
-                current_diagonal = diagonal;
-                diagonal_count = 1;
+                Some(pvalue)
             }
-        }
-        if diagonal_count > max_hit {
-            max_hit = diagonal_count;
-            max_diagonal = current_diagonal;
-        }
-        if max_hit >= min_diagonal {
-            candidate.push(Candidate { id: pid, score: max_hit, best_diagonal: max_diagonal });
-        }
+        };
+        candidate.push(Candidate { id: pid, score: max_hit, best_diagonal: max_diagonal, pvalue });
     }
-    candidate.sort_unstable_by(|a, b| b.score.cmp(&a.score));
-
     candidate
+}
+
+/// Upper bound on the buffers `find_candidate_words` holds for a query touching
+/// `postings` postings in at most `proteins` targets.
+pub fn seed_buffer_bytes(proteins: usize, postings: usize) -> usize {
+    grown_table_bytes(0, proteins, mem::size_of::<ProteinId>() + mem::size_of::<HitList>())
+        + 2 * postings * mem::size_of::<i32>()
+        + proteins * mem::size_of::<Candidate>()
+}
 
+/// Like `find_candidate`, but keeps diagonals that are unlikely to be random
+/// (p-value <= `max_pvalue`) and ranks them by p-value instead of raw hit count.
+/// Hits from common k-mers and long targets are discounted automatically.
+pub fn find_significant_candidate<I: SeedIndex>(
+    kmer_index: &I,
+    query_seq: &[u8],
+    max_pvalue: f64
+) -> Vec<Candidate> {
+    let words = query_words(query_seq, kmer_index.k());
+    find_significant_candidate_words(kmer_index, query_seq.len(), words, max_pvalue)
+}
+
+/// `find_significant_candidate` for precomputed seeds, most significant first.
+pub fn find_significant_candidate_words<I: SeedIndex, W: IntoIterator<Item = (usize, u64)>>(
+    kmer_index: &I,
+    query_len: usize,
+    words: W,
+    max_pvalue: f64
+) -> Vec<Candidate> {
+    let mut candidate = collect_candidates(kmer_index, query_len, words, 1, Some(max_pvalue));
+    candidate.sort_unstable_by(|a, b| {
+        a.pvalue.unwrap().total_cmp(&b.pvalue.unwrap()).then(b.score.cmp(&a.score)).then(a.id.cmp(&b.id))
+    });
+    candidate
 }
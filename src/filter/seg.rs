@@ -0,0 +1,283 @@
+// SEG low-complexity masking (Wootton & Federhen, 1993).
+//
+// Each window of `window` residues gets its Shannon entropy (bits). A window at or
+// below `locut` triggers a segment, which is extended left and right over adjacent
+// windows at or below `hicut`. The segment is then trimmed to its least probable
+// subsequence (dropping at most `MAX_TRIM` residues), and a trigger window trimmed
+// off the left end is searched again, as in the NCBI `seg` program.
+//
+// Soft masking lowercases the segment: `AA_TO_INT` rejects lowercase, so masked
+// residues never seed, while extension and SW compare case-insensitively.
+// Hard masking replaces the segment with X.
+use std::borrow::Cow;
+use crate::core::alphabet::{AA_TO_INT, INVALID_AA};
+use crate::core::database::Database;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum MaskMode {
+    /// Lowercase: excluded from seeding, kept for extension and SW
+    #[default]
+    Soft,
+    /// Replace with X
+    Hard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SegTarget {
+    None,
+    #[default]
+    Query,
+    Database,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Seg {
+    pub window: usize,
+    pub locut: f64,
+    pub hicut: f64,
+}
+
+// BLASTP defaults
+impl Default for Seg {
+    fn default() -> Self {
+        Self { window: 12, locut: 2.2, hicut: 2.5 }
+    }
+}
+
+/// Most residues `trim` drops from a segment (the `seg` default).
+const MAX_TRIM: usize = 100;
+
+impl Seg {
+    /// Low-complexity segments as half-open residue ranges, in order, non-overlapping.
+    pub fn segments(&self, seq: &[u8]) -> Vec<(usize, usize)> {
+        let mut segments = Vec::new();
+        if self.window == 0 || seq.len() < self.window {
+            return segments;
+        }
+        let entropy = window_entropies(seq, self.window);
+        let ln_fact = ln_factorials(seq.len().max(ALPHABET_SIZE));
+        self.find_segments(seq, &entropy, &ln_fact, 0, seq.len(), &mut segments);
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(segments.len());
+        for (start, end) in segments {
+            match merged.last_mut() {
+                Some((_, last_end)) if start < *last_end => *last_end = end.max(*last_end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    /// Appends the segments of `seq[lo..hi]`, using only windows inside that range.
+    fn find_segments(&self, seq: &[u8], entropy: &[f64], ln_fact: &[f64], lo: usize, hi: usize, segments: &mut Vec<(usize, usize)>) {
+        let w = self.window;
+        let mut floor = lo; // first window not yet claimed by a segment
+        let mut i = lo;
+        while i + w <= hi {
+            if entropy[i] > self.locut {
+                i += 1;
+                continue;
+            }
+            let mut left = i;
+            while left > floor && entropy[left - 1] <= self.hicut {
+                left -= 1;
+            }
+            let mut right = i;
+            while right + 1 + w <= hi && entropy[right + 1] <= self.hicut {
+                right += 1;
+            }
+            let (trim_start, trim_end) = trim(&seq[left..right + w], ln_fact);
+            let (start, end) = (left + trim_start, left + trim_end);
+            // The trigger window itself was trimmed away: look for segments left of the kept part
+            if i + w <= start {
+                self.find_segments(seq, entropy, ln_fact, left, start, segments);
+            }
+            segments.push((start, end));
+            i = right.min(end - 1) + 1;
+            floor = i;
+        }
+    }
+
+    /// Masks low-complexity segments in place.
+    pub fn mask(&self, seq: &mut [u8], mode: MaskMode) {
+        for (start, end) in self.segments(seq) {
+            for b in &mut seq[start..end] {
+                *b = match mode {
+                    MaskMode::Soft => b.to_ascii_lowercase(),
+                    MaskMode::Hard => b'X',
+                };
+            }
+        }
+    }
+}
+
+/// Shannon entropy (bits) of every `w`-residue window, computed with a sliding count.
+/// Residues outside the 20 standard ones (either case) share one extra bin.
+fn window_entropies(seq: &[u8], w: usize) -> Vec<f64> {
+    let bin = |b: u8| {
+        let v = AA_TO_INT[b.to_ascii_uppercase() as usize];
+        if v == INVALID_AA { 20 } else { v as usize }
+    };
+    // n * log2(n) for every count a window can hold
+    let nlogn: Vec<f64> = (0..=w).map(|n| if n == 0 { 0.0 } else { n as f64 * (n as f64).log2() }).collect();
+    let mut counts = [0usize; 21];
+    let mut sum_nlogn = 0.0;
+    let add = |counts: &mut [usize; 21], sum: &mut f64, b: u8, delta: isize| {
+        let c = &mut counts[bin(b)];
+        *sum -= nlogn[*c];
+        *c = (*c as isize + delta) as usize;
+        *sum += nlogn[*c];
+    };
+    for &b in &seq[..w] {
+        add(&mut counts, &mut sum_nlogn, b, 1);
+    }
+    let total = (w as f64).log2();
+    let mut entropy = Vec::with_capacity(seq.len() - w + 1);
+    entropy.push(total - sum_nlogn / w as f64);
+    for i in w..seq.len() {
+        add(&mut counts, &mut sum_nlogn, seq[i - w], -1);
+        add(&mut counts, &mut sum_nlogn, seq[i], 1);
+        entropy.push(total - sum_nlogn / w as f64);
+    }
+    entropy
+}
+
+const ALPHABET_SIZE: usize = 20;
+
+/// ln(n!) for n up to `max`.
+fn ln_factorials(max: usize) -> Vec<f64> {
+    let mut table = Vec::with_capacity(max + 1);
+    let mut sum = 0.0;
+    table.push(0.0);
+    for n in 1..=max {
+        sum += (n as f64).ln();
+        table.push(sum);
+    }
+    table
+}
+
+/// Natural log of the probability that a random sequence of `len` residues has the
+/// same compositional complexity as `counts`: (ways to assign the counts to residue
+/// types) * (orderings of the residues) / 20^len.
+fn ln_probability(counts: &[usize; ALPHABET_SIZE], len: usize, ln_fact: &[f64]) -> f64 {
+    let mut sorted = *counts;
+    sorted.sort_unstable();
+    let assignments = ln_fact[ALPHABET_SIZE]
+        - sorted.chunk_by(|a, b| a == b).map(|class| ln_fact[class.len()]).sum::<f64>();
+    let orderings = ln_fact[len] - counts.iter().map(|&c| ln_fact[c]).sum::<f64>();
+    assignments + orderings - len as f64 * (ALPHABET_SIZE as f64).ln()
+}
+
+/// The least probable subsequence of `seq` at least `seq.len() - MAX_TRIM` long, as a
+/// half-open range. Longer and then leftmost subsequences win ties. Residues outside the
+/// 20 standard ones add length but no composition.
+fn trim(seq: &[u8], ln_fact: &[f64]) -> (usize, usize) {
+    let bin = |b: u8| {
+        let v = AA_TO_INT[b.to_ascii_uppercase() as usize];
+        (v != INVALID_AA).then_some(v as usize)
+    };
+    let min_len = seq.len().saturating_sub(MAX_TRIM).max(1);
+    let mut best = (0, seq.len());
+    let mut min_prob = f64::INFINITY;
+    for len in (min_len + 1..=seq.len()).rev() {
+        let mut counts = [0usize; ALPHABET_SIZE];
+        for v in seq[..len].iter().filter_map(|&b| bin(b)) {
+            counts[v] += 1;
+        }
+        for start in 0..=seq.len() - len {
+            if start > 0 {
+                if let Some(v) = bin(seq[start - 1]) {
+                    counts[v] -= 1;
+                }
+                if let Some(v) = bin(seq[start + len - 1]) {
+                    counts[v] += 1;
+                }
+            }
+            let prob = ln_probability(&counts, len, ln_fact);
+            if prob < min_prob {
+                min_prob = prob;
+                best = (start, start + len);
+            }
+        }
+    }
+    best
+}
+
+/// Where and how SEG is applied in a search.
+#[derive(Debug, Clone, Copy, clap::Args)]
+#[command(about = None, long_about = None)]
+pub struct SegOptions {
+    /// Apply SEG low-complexity masking to
+    #[arg(long = "seg", value_enum, default_value_t)]
+    pub target: SegTarget,
+    /// SEG masking style
+    #[arg(id = "seg_mask", long = "seg-mask", value_enum, default_value_t)]
+    pub mode: MaskMode,
+    /// SEG window length
+    #[arg(long = "seg-window", default_value_t = 12)]
+    pub window: usize,
+    /// SEG trigger entropy (bits)
+    #[arg(long = "seg-locut", default_value_t = 2.2)]
+    pub locut: f64,
+    /// SEG extension entropy (bits)
+    #[arg(long = "seg-hicut", default_value_t = 2.5)]
+    pub hicut: f64,
+}
+
+impl Default for SegOptions {
+    fn default() -> Self {
+        let seg = Seg::default();
+        Self {
+            target: SegTarget::default(),
+            mode: MaskMode::default(),
+            window: seg.window,
+            locut: seg.locut,
+            hicut: seg.hicut,
+        }
+    }
+}
+
+impl SegOptions {
+    fn seg(&self) -> Seg {
+        Seg { window: self.window, locut: self.locut, hicut: self.hicut }
+    }
+
+    pub fn masks_queries(&self) -> bool {
+        matches!(self.target, SegTarget::Query | SegTarget::Both)
+    }
+
+    pub fn masks_database(&self) -> bool {
+        matches!(self.target, SegTarget::Database | SegTarget::Both)
+    }
+
+    /// The query as it should be searched: masked copy, or borrowed unchanged.
+    pub fn mask_query<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        self.mask_if(self.masks_queries(), seq)
+    }
+
+    /// A streamed database sequence as it would be indexed.
+    pub fn mask_target<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        self.mask_if(self.masks_database(), seq)
+    }
+
+    /// Masks every record in place, before the database is indexed.
+    pub fn mask_database(&self, db: &mut Database) {
+        if !self.masks_database() {
+            return;
+        }
+        let seg = self.seg();
+        for i in 0..db.len() {
+            let (start, end) = (db.offsets[i], db.offsets[i + 1]);
+            seg.mask(&mut db.data[start..end], self.mode);
+        }
+    }
+
+    fn mask_if<'a>(&self, enabled: bool, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        if !enabled {
+            return Cow::Borrowed(seq);
+        }
+        let mut masked = seq.to_vec();
+        self.seg().mask(&mut masked, self.mode);
+        Cow::Owned(masked)
+    }
+}
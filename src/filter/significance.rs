@@ -0,0 +1,54 @@
+// Seed statistics: how likely is a diagonal with `c` hits against a random target?
+//
+// Model: every query k-mer w hits a random database position with probability
+// f_w / N (f_w = postings length, N = total indexed k-mers). A target with n_t
+// windows therefore collects lambda = n_t * sum(f_w) / N random hits, spread over
+// the n_q + n_t - 1 diagonals of the query/target dot plot. The hit count of one
+// diagonal is Poisson(lambda / D), and the best of D diagonals is Bonferroni-corrected.
+
+/// Expected number of random k-mer hits between the query and one target.
+pub fn expected_hits(query_postings: usize, total_postings: usize, target_windows: usize) -> f64 {
+    if total_postings == 0 {
+        return 0.0;
+    }
+    target_windows as f64 * query_postings as f64 / total_postings as f64
+}
+
+/// P-value of observing `count` hits on the best of all diagonals.
+pub fn diagonal_pvalue(
+    count: usize,
+    query_windows: usize,
+    target_windows: usize,
+    query_postings: usize,
+    total_postings: usize,
+) -> f64 {
+    let lambda = expected_hits(query_postings, total_postings, target_windows);
+    let num_diagonals = (query_windows + target_windows).saturating_sub(1).max(1) as f64;
+    let per_diagonal = lambda / num_diagonals;
+    (num_diagonals * poisson_upper_tail(count, per_diagonal)).min(1.0)
+}
+
+/// P(X >= c) for X ~ Poisson(mu), summed directly to avoid 1 - CDF cancellation.
+pub fn poisson_upper_tail(c: usize, mu: f64) -> f64 {
+    if c == 0 {
+        return 1.0;
+    }
+    if mu <= 0.0 {
+        return 0.0;
+    }
+    // ln(e^-mu * mu^c / c!)
+    let ln_first = -mu + c as f64 * mu.ln() - ln_factorial(c);
+    let mut term = ln_first.exp();
+    let mut total = term;
+    let mut i = c;
+    while term > total * 1e-12 && i < c + 10_000 {
+        i += 1;
+        term *= mu / i as f64;
+        total += term;
+    }
+    total.min(1.0)
+}
+
+fn ln_factorial(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).ln()).sum()
+}
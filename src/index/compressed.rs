@@ -0,0 +1,314 @@
+use rustc_hash::FxHashMap;
+use crate::core::database::Database;
+use crate::core::alphabet::encode_kmer;
+use crate::index::kmer::{
+    count_kmers, for_each_kmer, grown_table_bytes, max_distinct_words, seq_lens, BuildOptions, IndexStats, KmerIndex, Position, ProteinId, SeedIndex,
+};
+use crate::index::weighting::{Bm25, WeightedScorer};
+use std::mem;
+
+// Postings blob layout, per k-mer:
+//   varint count
+//   count x (varint pid_delta, varint pos)
+// ProteinIds are delta-encoded against the previous posting. When the delta is 0
+// (same protein), the position is delta-encoded against the previous position too.
+
+#[inline]
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+#[inline]
+pub fn read_varint(bytes: &[u8], cursor: &mut usize) -> u64 {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*cursor];
+        *cursor += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[inline]
+fn varint_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+// `write_varint` into preallocated room
+#[inline]
+fn put_varint(out: &mut [u8], cursor: &mut usize, mut value: u64) {
+    while value >= 0x80 {
+        out[*cursor] = (value as u8) | 0x80;
+        *cursor += 1;
+        value >>= 7;
+    }
+    out[*cursor] = value as u8;
+    *cursor += 1;
+}
+
+// Per k-mer delta state while encoding
+#[derive(Default, Clone, Copy)]
+struct DeltaEncoder {
+    seen: bool,
+    last_pid: ProteinId,
+    last_pos: Position,
+}
+
+impl DeltaEncoder {
+    /// The two values stored for the next posting.
+    #[inline]
+    fn next(&mut self, pid: ProteinId, pos: Position) -> (u64, u64) {
+        let pid_delta = pid - self.last_pid;
+        let pos_value = if self.seen && pid_delta == 0 { pos - self.last_pos } else { pos };
+        *self = Self { seen: true, last_pid: pid, last_pos: pos };
+        (pid_delta as u64, pos_value as u64)
+    }
+}
+
+/// K-mer index with delta/varint compressed postings in one flat blob.
+/// Keys are sorted for binary-search lookup, so no hash table is kept at query time.
+pub struct CompressedKmerIndex {
+    pub k: usize,
+    pub keys: Vec<u64>,
+    pub offsets: Vec<u64>, // keys.len() + 1 entries into `blob`
+    pub blob: Vec<u8>,
+    pub seq_lens: Vec<u32>,
+    pub total_postings: usize,
+    pub stop_list: FxHashMap<u64, u32>,
+    pub dropped_postings: usize,
+}
+
+impl CompressedKmerIndex {
+    pub fn build(db: &Database, k: usize) -> Self {
+        Self::build_with(db, k, &BuildOptions::default())
+    }
+
+    /// Three passes over `db`: count the k-mers (which gives the stop-list), size each
+    /// postings list, then encode straight into the blob. Besides the finished index,
+    /// the build holds a hash slot and a few words per distinct k-mer, never raw postings.
+    pub fn build_with(db: &Database, k: usize, options: &BuildOptions) -> Self {
+        log::info!("Building compressed index with k={} for {} proteins...", k, db.len());
+        let (mut slots, stop_list) = options.split_counts(count_kmers(db, k, options.wildcard_x));
+        let dropped_postings = stop_list.values().map(|&c| c as usize).sum();
+        if !stop_list.is_empty() {
+            log::info!("Stop-listed {} k-mers ({} postings)", stop_list.len(), dropped_postings);
+        }
+
+        // Sorted keys; from here on `slots` takes a k-mer to its position in them
+        let mut keys: Vec<u64> = slots.keys().copied().collect();
+        keys.sort_unstable();
+        let mut counts = Vec::with_capacity(keys.len());
+        for (slot, kmer) in keys.iter().enumerate() {
+            counts.push(mem::replace(slots.get_mut(kmer).unwrap(), slot as u32));
+        }
+        let total_postings = counts.iter().map(|&c| c as usize).sum();
+
+        // Byte length of each list: the count header and two varints per posting
+        let mut encoders = vec![DeltaEncoder::default(); keys.len()];
+        let mut offsets: Vec<u64> = counts.iter().map(|&c| varint_len(c as u64) as u64).collect();
+        for_each_kmer(db, k, options.wildcard_x, |pid, pos, encoded| {
+            if let Some(&slot) = slots.get(&encoded) {
+                let (pid_delta, pos_value) = encoders[slot as usize].next(pid, pos);
+                offsets[slot as usize] += (varint_len(pid_delta) + varint_len(pos_value)) as u64;
+            }
+        });
+        let mut end = 0;
+        for offset in offsets.iter_mut() {
+            let len = *offset;
+            *offset = end;
+            end += len;
+        }
+        offsets.push(end);
+
+        let mut blob = vec![0u8; end as usize];
+        let mut cursors: Vec<usize> = Vec::with_capacity(keys.len());
+        for (&start, &count) in offsets.iter().zip(&counts) {
+            let mut cursor = start as usize;
+            put_varint(&mut blob, &mut cursor, count as u64);
+            cursors.push(cursor);
+        }
+        drop(counts);
+        encoders.fill(DeltaEncoder::default());
+        for_each_kmer(db, k, options.wildcard_x, |pid, pos, encoded| {
+            if let Some(&slot) = slots.get(&encoded) {
+                let slot = slot as usize;
+                let (pid_delta, pos_value) = encoders[slot].next(pid, pos);
+                put_varint(&mut blob, &mut cursors[slot], pid_delta);
+                put_varint(&mut blob, &mut cursors[slot], pos_value);
+            }
+        });
+        log::info!("Index built! Total unique k-mers: {}", keys.len());
+
+        Self { k, keys, offsets, blob, seq_lens: seq_lens(db), total_postings, stop_list, dropped_postings }
+    }
+
+    /// Upper bound on the peak bytes of `build_with`: the count table, the per-key arrays
+    /// of the sizing pass and the blob. A posting takes at most a protein-delta varint and
+    /// a position varint, and no position exceeds `residues`.
+    pub fn estimated_bytes(residues: usize, proteins: usize, k: usize) -> usize {
+        let distinct = max_distinct_words(residues, k);
+        let per_key = mem::size_of::<u64>() * 2      // keys, offsets
+            + mem::size_of::<u32>()                  // counts
+            + mem::size_of::<DeltaEncoder>()
+            + mem::size_of::<usize>();               // cursors
+        let position = varint_len(residues as u64);
+        let blob = residues * (varint_len(proteins as u64) + position) + distinct * position;
+        grown_table_bytes(0, distinct, mem::size_of::<(u64, u32)>())
+            + distinct * per_key
+            + blob
+            + proteins * mem::size_of::<u32>()
+    }
+
+    /// Compresses an existing index; useful to compare layouts on the same data.
+    pub fn from_index(index: &KmerIndex) -> Self {
+        let mut keys: Vec<u64> = index.map.keys().copied().collect();
+        keys.sort_unstable();
+
+        let mut offsets = Vec::with_capacity(keys.len() + 1);
+        let mut blob = Vec::new();
+        offsets.push(0);
+        for kmer in &keys {
+            let postings = &index.map[kmer];
+            write_varint(&mut blob, postings.len() as u64);
+            let mut encoder = DeltaEncoder::default();
+            for &(pid, pos) in postings {
+                let (pid_delta, pos_value) = encoder.next(pid, pos);
+                write_varint(&mut blob, pid_delta);
+                write_varint(&mut blob, pos_value);
+            }
+            offsets.push(blob.len() as u64);
+        }
+        Self {
+            k: index.k,
+            keys,
+            offsets,
+            blob,
+            seq_lens: index.seq_lens.clone(),
+            total_postings: index.total_postings,
+            stop_list: index.stop_list.clone(),
+            dropped_postings: index.dropped_postings,
+        }
+    }
+
+    fn postings_bytes(&self, encoded_kmer: u64) -> Option<&[u8]> {
+        let i = self.keys.binary_search(&encoded_kmer).ok()?;
+        Some(&self.blob[self.offsets[i] as usize..self.offsets[i + 1] as usize])
+    }
+
+    /// Decodes a postings list; mostly for IDF scoring and debugging.
+    pub fn query(&self, encoded_kmer: u64) -> Option<Vec<(ProteinId, Position)>> {
+        let mut hits = Vec::new();
+        let n = self.for_each_posting(encoded_kmer, |pid, pos| hits.push((pid, pos)));
+        if n == 0 { None } else { Some(hits) }
+    }
+
+    pub fn search_basic(&self, query_seq: &[u8], top_n: usize) -> Vec<(ProteinId, u32)> {
+        let mut scores: FxHashMap<ProteinId, u32> = FxHashMap::default();
+        if query_seq.len() >= self.k {
+            for window in query_seq.windows(self.k) {
+                if let Some(encoded) = encode_kmer(window) {
+                    self.for_each_posting(encoded, |pid, _pos| {
+                        *scores.entry(pid).or_insert(0) += 1;
+                    });
+                }
+            }
+        }
+        let mut candidates: Vec<(ProteinId, u32)> = scores.into_iter().collect();
+        candidates.sort_unstable_by_key(|c| (std::cmp::Reverse(c.1), c.0));
+        candidates.truncate(top_n);
+        candidates
+    }
+
+    /// IDF / BM25 weighted voting, see `KmerIndex::search_idf`.
+    pub fn search_idf(&self, query_seq: &[u8], top_n: usize, length_norm: Option<Bm25>) -> Vec<(ProteinId, f32)> {
+        let mut scorer = WeightedScorer::new(&self.seq_lens, length_norm);
+        if query_seq.len() >= self.k {
+            for window in query_seq.windows(self.k) {
+                if let Some(encoded) = encode_kmer(window)
+                    && let Some(hits) = self.query(encoded) {
+                    scorer.add_postings(&hits);
+                }
+            }
+        }
+        scorer.finish(top_n)
+    }
+
+    /// Bytes actually held by this index.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.keys.capacity() * mem::size_of::<u64>()
+            + self.offsets.capacity() * mem::size_of::<u64>()
+            + self.blob.capacity()
+            + self.seq_lens.capacity() * mem::size_of::<u32>()
+            + self.stop_list.capacity() * (mem::size_of::<u64>() + mem::size_of::<u32>() + 1)
+    }
+
+    /// Postings length of every kept k-mer, read from the list headers.
+    pub fn postings_lens(&self) -> impl Iterator<Item = usize> + '_ {
+        self.offsets[..self.keys.len()].iter().map(|&start| {
+            let mut cursor = start as usize;
+            read_varint(&self.blob, &mut cursor) as usize
+        })
+    }
+
+    /// Like `KmerIndex::stats`; dropped bytes are estimated at this index's bytes per posting.
+    pub fn stats(&self) -> IndexStats {
+        let per_posting = self.blob.len() as f64 / self.total_postings.max(1) as f64;
+        let per_key = 2 * mem::size_of::<u64>();
+        IndexStats {
+            unique_kmers: self.keys.len(),
+            total_postings: self.total_postings,
+            stopped_kmers: self.stop_list.len(),
+            dropped_postings: self.dropped_postings,
+            memory_bytes: self.memory_usage(),
+            dropped_bytes: self.stop_list.len() * per_key + (self.dropped_postings as f64 * per_posting) as usize,
+            histogram: IndexStats::histogram(self.postings_lens().chain(self.stop_list.values().map(|&c| c as usize))),
+        }
+    }
+
+    /// Bytes the same postings would take in an uncompressed `KmerIndex`, as
+    /// `KmerIndex::predicted_memory_usage` over this index's sequences.
+    pub fn raw_memory_usage(&self) -> usize {
+        let residues = self.seq_lens.iter().map(|&len| len as usize).sum();
+        KmerIndex::predicted_memory_usage(self.postings_lens(), residues, self.seq_lens.len())
+    }
+}
+
+impl SeedIndex for CompressedKmerIndex {
+    fn k(&self) -> usize {
+        self.k
+    }
+    fn seq_lens(&self) -> &[u32] {
+        &self.seq_lens
+    }
+    fn total_postings(&self) -> usize {
+        self.total_postings
+    }
+    /// Decodes straight into the caller's accumulator, without materializing the list.
+    #[inline]
+    fn for_each_posting<F: FnMut(ProteinId, Position)>(&self, encoded_kmer: u64, mut f: F) -> usize {
+        let Some(bytes) = self.postings_bytes(encoded_kmer) else {
+            return 0;
+        };
+        let mut cursor = 0;
+        let count = read_varint(bytes, &mut cursor) as usize;
+        let mut pid: ProteinId = 0;
+        let mut pos: Position = 0;
+        for i in 0..count {
+            let pid_delta = read_varint(bytes, &mut cursor) as ProteinId;
+            let pos_value = read_varint(bytes, &mut cursor) as Position;
+            pid += pid_delta;
+            pos = if i > 0 && pid_delta == 0 { pos + pos_value } else { pos_value };
+            f(pid, pos);
+        }
+        count
+    }
+}
@@ -1,16 +1,190 @@
 use rustc_hash::FxHashMap;
 use crate::core::database::Database;
-use crate::core::alphabet::encode_kmer;
+use crate::core::alphabet::{encode_kmer, encode_kmer_wildcard, INT_TO_AA};
+use crate::index::weighting::{Bm25, WeightedScorer};
 use smallvec::SmallVec;
 use std::mem;
 
 pub type ProteinId = u32;
-pub type Position = u16;
+// u32, not u16: titin-class and assembled polyproteins exceed 65,535 residues.
+// (ProteinId, u32) is 8 bytes, the same as the padded (ProteinId, u16) it replaces.
+pub type Position = u32;
 
 pub type PostingsList = SmallVec<[(ProteinId, Position); 2]>;
+
+/// Controls for dropping over-represented k-mers at build time.
+/// Dropped k-mers go to the stop-list instead of the postings map.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuildOptions {
+    /// Drop k-mers with more postings than this
+    pub max_postings: Option<usize>,
+    /// Drop k-mers whose postings length is above this percentile (0-100)
+    pub max_percentile: Option<f64>,
+    /// Index k-mers with a single X under all 20 residues
+    pub wildcard_x: bool,
+}
+
+impl BuildOptions {
+    /// Largest postings length allowed by these options, given all postings lengths.
+    pub fn postings_limit(&self, lens: impl Iterator<Item = usize>) -> Option<usize> {
+        let by_percentile = self.max_percentile.and_then(|p| {
+            let mut lens: Vec<usize> = lens.collect();
+            if lens.is_empty() {
+                return None;
+            }
+            lens.sort_unstable();
+            let rank = ((p.clamp(0.0, 100.0) / 100.0) * lens.len() as f64).ceil() as usize;
+            Some(lens[rank.saturating_sub(1).min(lens.len() - 1)])
+        });
+        match (self.max_postings, by_percentile) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn has_caps(&self) -> bool {
+        self.max_postings.is_some() || self.max_percentile.is_some()
+    }
+
+    /// Splits k-mer postings counts into the kept ones and the stop-list.
+    pub fn split_counts(&self, mut counts: FxHashMap<u64, u32>) -> (FxHashMap<u64, u32>, FxHashMap<u64, u32>) {
+        let mut stop_list = FxHashMap::default();
+        if let Some(limit) = self.postings_limit(counts.values().map(|&c| c as usize)) {
+            counts.retain(|&kmer, &mut count| {
+                let keep = count as usize <= limit;
+                if !keep {
+                    stop_list.insert(kmer, count);
+                }
+                keep
+            });
+            counts.shrink_to_fit();
+        }
+        (counts, stop_list)
+    }
+}
+
+/// Feeds every indexed k-mer of `db` to `f` as (protein, position, encoded k-mer), in
+/// protein and then position order. With `wildcard_x`, a window with one X yields 20 k-mers.
+pub fn for_each_kmer<F: FnMut(ProteinId, Position, u64)>(db: &Database, k: usize, wildcard_x: bool, mut f: F) {
+    for i in 0..db.len() {
+        let pid = i as ProteinId;
+        let seq = &db.data[db.offsets[i]..db.offsets[i + 1]];
+        if seq.len() < k {
+            continue;
+        }
+        for (pos, window) in seq.windows(k).enumerate() {
+            let mut add = |encoded: u64| f(pid, pos as Position, encoded);
+            if wildcard_x {
+                encode_kmer_wildcard(window, add);
+            } else if let Some(encoded) = encode_kmer(window) {
+                add(encoded);
+            }
+        }
+    }
+}
+
+/// Postings length of every k-mer of `db`, without the postings.
+pub fn count_kmers(db: &Database, k: usize, wildcard_x: bool) -> FxHashMap<u64, u32> {
+    let mut counts: FxHashMap<u64, u32> = FxHashMap::default();
+    for_each_kmer(db, k, wildcard_x, |_, _, encoded| *counts.entry(encoded).or_insert(0) += 1);
+    counts
+}
+
+pub fn seq_lens(db: &Database) -> Vec<u32> {
+    db.offsets.windows(2).map(|w| (w[1] - w[0]) as u32).collect()
+}
+
+/// Map capacity reserved before an uncapped build: about 100 words per protein,
+/// but never more than there are windows.
+pub fn initial_capacity(residues: usize, proteins: usize) -> usize {
+    (proteins * 100).min(residues)
+}
+
+/// Most distinct words of `weight` residues that `windows` windows can hold.
+pub fn max_distinct_words(windows: usize, weight: usize) -> usize {
+    INT_TO_AA.len().checked_pow(weight as u32).map_or(windows, |all| all.min(windows))
+}
+
+/// Buckets of a hash table sized for `entries` entries: a power of two at most 7/8 full.
+pub fn hash_buckets(entries: usize) -> usize {
+    match entries {
+        0..4 => 4,
+        4..8 => 8,
+        _ => (entries * 8 / 7).next_power_of_two(),
+    }
+}
+
+/// Bytes of a hash table sized for `entries` entries of `entry_size` bytes, with one
+/// control byte per bucket.
+pub fn hash_table_bytes(entries: usize, entry_size: usize) -> usize {
+    hash_buckets(entries) * (entry_size + 1)
+}
+
+/// Peak bytes of a table reserved for `initial` entries and filled to `entries`.
+/// Each resize briefly holds the old table next to the new one.
+pub fn grown_table_bytes(initial: usize, entries: usize, entry_size: usize) -> usize {
+    let table = hash_table_bytes(initial.max(entries), entry_size);
+    if entries > initial { table + table / 2 } else { table }
+}
+
+/// What the seeding stage needs from an index, whatever the postings layout.
+pub trait SeedIndex {
+    fn k(&self) -> usize;
+    fn seq_lens(&self) -> &[u32];
+    fn total_postings(&self) -> usize;
+    /// Feeds every posting of `encoded_kmer` to `f` and returns how many there were.
+    fn for_each_posting<F: FnMut(ProteinId, Position)>(&self, encoded_kmer: u64, f: F) -> usize;
+}
+
 pub struct KmerIndex {
     pub map: FxHashMap<u64,PostingsList>, // pub for test purpose
     pub k: usize,
+    pub seq_lens: Vec<u32>,     // Target lengths, used by seed statistics
+    pub total_postings: usize,  // Number of indexed k-mer occurrences
+    pub stop_list: FxHashMap<u64, u32>, // Dropped k-mer -> original postings length
+    pub dropped_postings: usize,
+}
+
+pub struct IndexStats {
+    pub unique_kmers: usize,
+    pub total_postings: usize,
+    pub stopped_kmers: usize,
+    pub dropped_postings: usize,
+    pub memory_bytes: usize,
+    pub dropped_bytes: usize,
+    /// Bucket i counts k-mers with postings length in [2^i, 2^(i+1)), stop-listed ones included
+    pub histogram: Vec<usize>,
+}
+
+impl IndexStats {
+    /// Log2 buckets of postings lengths.
+    pub fn histogram(lens: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        for len in lens {
+            let bucket = len.max(1).ilog2() as usize;
+            if histogram.len() <= bucket {
+                histogram.resize(bucket + 1, 0);
+            }
+            histogram[bucket] += 1;
+        }
+        histogram
+    }
+
+    pub fn print(&self) {
+        println!("   Unique k-mers: {} ({} postings)", self.unique_kmers, self.total_postings);
+        println!("   Stop-listed:   {} k-mers ({} postings, ~{:.2} MB saved)",
+                self.stopped_kmers, self.dropped_postings, self.dropped_bytes as f64 / 1024.0 / 1024.0);
+        println!("   Memory:        {:.2} MB", self.memory_bytes as f64 / 1024.0 / 1024.0);
+        println!("   Postings length histogram:");
+        for (i, &count) in self.histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let lo = 1usize << i;
+            let hi = (1usize << (i + 1)) - 1;
+            println!("   {:>8}-{:<8} | {}", lo, hi, count);
+        }
+    }
 }
 
 impl KmerIndex {
@@ -18,32 +192,53 @@ impl KmerIndex {
         Self {
             map: FxHashMap::default(),
             k,
+            seq_lens: Vec::new(),
+            total_postings: 0,
+            stop_list: FxHashMap::default(),
+            dropped_postings: 0,
         }
     }
     pub fn build(db: &Database, k: usize) -> Self {
-        let estimated_capacity = db.len() * 100;
-        let mut map: FxHashMap<u64, PostingsList> = FxHashMap::with_capacity_and_hasher(estimated_capacity, Default::default());
-        println!("Building index with k={} for {} proteins...", k, db.len());
-        for i in 0..db.len() {
-            let pid = i as ProteinId;
-            let start = db.offsets[i];
-            let end = db.offsets[i+1];
-            let seq = &db.data[start..end];
-            if seq.len() < k {
-                continue;
-            }
-
-            for (pos, window) in seq.windows(k).enumerate() {
-                if let Some(encoded) = encode_kmer(window) {
-                    map.entry(encoded)
-                        .or_default()
-                        .push((pid, pos as Position));
+        Self::build_with(db, k, &BuildOptions::default())
+    }
+    pub fn build_with(db: &Database, k: usize, options: &BuildOptions) -> Self {
+        log::info!("Building index with k={} for {} proteins...", k, db.len());
+        // With caps, a counting pass finds the stop-list first, so capped k-mers never get
+        // postings and every kept list is allocated at its final length.
+        let capped = options.has_caps();
+        let (mut map, stop_list): (FxHashMap<u64, PostingsList>, _) = if capped {
+            let (counts, stop_list) = options.split_counts(count_kmers(db, k, options.wildcard_x));
+            let map = counts.into_iter()
+                .map(|(kmer, count)| (kmer, PostingsList::with_capacity(count as usize)))
+                .collect();
+            (map, stop_list)
+        } else {
+            let estimated_capacity = initial_capacity(db.data.len(), db.len());
+            (FxHashMap::with_capacity_and_hasher(estimated_capacity, Default::default()), FxHashMap::default())
+        };
+        let mut total_postings = 0;
+        for_each_kmer(db, k, options.wildcard_x, |pid, pos, encoded| {
+            let postings = if capped {
+                // Absent means stop-listed
+                match map.get_mut(&encoded) {
+                    Some(postings) => postings,
+                    None => return,
                 }
-            }
+            } else {
+                map.entry(encoded).or_default()
+            };
+            postings.push((pid, pos));
+            total_postings += 1;
+        });
+        let dropped_postings = stop_list.values().map(|&c| c as usize).sum();
+        if !stop_list.is_empty() {
+            log::info!("Stop-listed {} k-mers ({} postings)", stop_list.len(), dropped_postings);
         }
-        println!("Index built! Total unique k-mers: {}", map.len());
-        KmerIndex { map, k }
+        log::info!("Index built! Total unique k-mers: {}", map.len());
+        KmerIndex { map, k, seq_lens: seq_lens(db), total_postings, stop_list, dropped_postings }
     }
+
+    /// Postings of a k-mer. Stop-listed k-mers are absent from the map, so queries skip them.
     pub fn query(&self, encoded_kmer: u64) -> Option<&PostingsList> {
         self.map.get(&encoded_kmer)
     }
@@ -55,11 +250,10 @@ impl KmerIndex {
         
         if query_seq.len() >= self.k {
             for window in query_seq.windows(self.k) {
-                if let Some(encoded) = encode_kmer(window) {
-                    if let Some(hits) = self.map.get(&encoded) {
-                        for &(pid, _pos) in hits {
-                            *scores.entry(pid).or_insert(0) += 1;
-                        }
+                if let Some(encoded) = encode_kmer(window)
+                    && let Some(hits) = self.map.get(&encoded) {
+                    for &(pid, _pos) in hits {
+                        *scores.entry(pid).or_insert(0) += 1;
                     }
                 }
             }
@@ -69,17 +263,69 @@ impl KmerIndex {
         let mut candidates: Vec<(ProteinId, u32)> = scores.into_iter().collect();
         // For small result sets, full sort is fast. For large sets, we could use partial sort
         // but unstable sort is already very efficient for this use case.
-        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        candidates.sort_unstable_by_key(|c| (std::cmp::Reverse(c.1), c.0));
         if candidates.len() > top_n {
             candidates.truncate(top_n);
         }
         candidates
     }
+    /// Weighted voting: each shared k-mer adds its IDF instead of +1.
+    /// With `length_norm`, term frequency saturates and long targets are
+    /// penalized BM25-style.
+    pub fn search_idf(&self, query_seq: &[u8], top_n: usize, length_norm: Option<Bm25>) -> Vec<(ProteinId, f32)> {
+        let mut scorer = WeightedScorer::new(&self.seq_lens, length_norm);
+        if query_seq.len() >= self.k {
+            for window in query_seq.windows(self.k) {
+                if let Some(encoded) = encode_kmer(window)
+                    && let Some(hits) = self.map.get(&encoded) {
+                    scorer.add_postings(hits);
+                }
+            }
+        }
+        scorer.finish(top_n)
+    }
+    /// Upper bound on the peak bytes of `build_with` over `residues` residues in `proteins`
+    /// sequences. Postings lists grow by doubling, so they take at most twice their length.
+    /// With caps, the counting pass's table sits next to the map. Postings added by
+    /// X-wildcard expansion are not counted.
+    pub fn estimated_bytes(residues: usize, proteins: usize, k: usize, options: &BuildOptions) -> usize {
+        let distinct = max_distinct_words(residues, k);
+        let entry = mem::size_of::<u64>() + mem::size_of::<PostingsList>();
+        let map = if options.has_caps() {
+            grown_table_bytes(0, distinct, mem::size_of::<(u64, u32)>()) + hash_table_bytes(distinct, entry)
+        } else {
+            grown_table_bytes(initial_capacity(residues, proteins), distinct, entry)
+        };
+        map + 2 * residues * mem::size_of::<(ProteinId, Position)>() + proteins * mem::size_of::<u32>()
+    }
+
+    /// `memory_usage` of the uncapped index `build` would make over `residues` residues in
+    /// `proteins` sequences, given the postings length of every k-mer (e.g. from a
+    /// compressed index), without building it.
+    pub fn predicted_memory_usage(postings_lens: impl Iterator<Item = usize>, residues: usize, proteins: usize) -> usize {
+        let mut keys = 0;
+        let mut spilled = 0;
+        for len in postings_lens {
+            keys += 1;
+            // Past the two inline entries, SmallVec grows to the next power of two
+            if len > 2 {
+                spilled += len.next_power_of_two();
+            }
+        }
+        let buckets = hash_buckets(initial_capacity(residues, proteins).max(keys));
+        let map_cap = if buckets < 8 { buckets - 1 } else { buckets / 8 * 7 };
+        mem::size_of::<Self>()
+            + proteins * mem::size_of::<u32>()
+            + map_cap * (mem::size_of::<u64>() + mem::size_of::<PostingsList>() + 1)
+            + spilled * mem::size_of::<(ProteinId, Position)>()
+    }
+
     pub fn memory_usage(&self) -> usize {
         let mut total_bytes = 0;
 
         // 1. Stack
         total_bytes += mem::size_of::<Self>();
+        total_bytes += self.seq_lens.capacity() * mem::size_of::<u32>();
         // 2. HashMap skeleton size
         let map_cap = self.map.capacity();
         // Key size
@@ -87,7 +333,7 @@ impl KmerIndex {
         // Value size
         total_bytes += map_cap * mem::size_of::<PostingsList>();
         // Control Bytes
-        total_bytes += map_cap * 1; 
+        total_bytes += map_cap;
         // 3. Payload Deep Size
         let item_size = mem::size_of::<(ProteinId, Position)>();
         
@@ -96,8 +342,55 @@ impl KmerIndex {
                 total_bytes += postings.capacity() * item_size;
             }
         }
+        // 4. Stop-list (key, count, control byte)
+        total_bytes += self.stop_list.capacity() * (mem::size_of::<u64>() + mem::size_of::<u32>() + 1);
 
         total_bytes
     }
+
+    /// Approximate bytes the stop-listed postings would have taken.
+    pub fn dropped_bytes(&self) -> usize {
+        let item_size = mem::size_of::<(ProteinId, Position)>();
+        self.stop_list.len() * (mem::size_of::<u64>() + mem::size_of::<PostingsList>() + 1)
+            + self.dropped_postings * item_size
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        let lens = self.map.values().map(|v| v.len())
+            .chain(self.stop_list.values().map(|&c| c as usize));
+        IndexStats {
+            unique_kmers: self.map.len(),
+            total_postings: self.total_postings,
+            stopped_kmers: self.stop_list.len(),
+            dropped_postings: self.dropped_postings,
+            memory_bytes: self.memory_usage(),
+            dropped_bytes: self.dropped_bytes(),
+            histogram: IndexStats::histogram(lens),
+        }
+    }
     
-}
\ No newline at end of file
+}
+
+impl SeedIndex for KmerIndex {
+    fn k(&self) -> usize {
+        self.k
+    }
+    fn seq_lens(&self) -> &[u32] {
+        &self.seq_lens
+    }
+    fn total_postings(&self) -> usize {
+        self.total_postings
+    }
+    #[inline]
+    fn for_each_posting<F: FnMut(ProteinId, Position)>(&self, encoded_kmer: u64, mut f: F) -> usize {
+        match self.map.get(&encoded_kmer) {
+            Some(hits) => {
+                for &(pid, pos) in hits {
+                    f(pid, pos);
+                }
+                hits.len()
+            }
+            None => 0,
+        }
+    }
+}
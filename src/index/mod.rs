@@ -1,2 +1,4 @@
 pub mod kmer;
-pub mod spaced;
\ No newline at end of file
+pub mod spaced;
+pub mod weighting;
+pub mod compressed;
\ No newline at end of file
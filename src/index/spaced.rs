@@ -1,8 +1,10 @@
 use rustc_hash::FxHashMap;
 use crate::core::database::Database;
 use crate::core::alphabet::{encode_spaced};
-use crate::index::kmer::{ProteinId, Position};
+use crate::index::kmer::{grown_table_bytes, initial_capacity, max_distinct_words, ProteinId, Position};
+use crate::index::weighting::{Bm25, WeightedScorer};
 use smallvec::SmallVec;
+use std::mem;
 
 pub type PostingsList = SmallVec<[(ProteinId, Position); 2]>;
 
@@ -12,6 +14,7 @@ pub struct SpacedIndex {
     pub mask: Vec<bool>,
     pub weight: usize,
     pub num_proteins: usize,
+    pub seq_lens: Vec<u32>,
 }
 
 impl SpacedIndex {
@@ -19,15 +22,17 @@ impl SpacedIndex {
         let mask: Vec<bool> = pattern.chars().map(|c| c == '1').collect();
         let weight = mask.iter().filter(|&&x| x).count();
         let span = mask.len();
-        let estimated_capacity = db.len() * 100;
+        let estimated_capacity = initial_capacity(db.data.len(), db.len());
         let mut map: FxHashMap<u64, PostingsList> = FxHashMap::with_capacity_and_hasher(estimated_capacity, Default::default());
+        let mut seq_lens = Vec::with_capacity(db.len());
 
-        println!("Building Spaced Index (Pattern: {}, Weight: {}, Span: {})...", pattern, weight, span);
+        log::info!("Building Spaced Index (Pattern: {}, Weight: {}, Span: {})...", pattern, weight, span);
         for i in 0..db.len() {
             let pid = i as ProteinId;
             let start = db.offsets[i];
             let end = db.offsets[i+1];
             let seq = &db.data[start..end];
+            seq_lens.push(seq.len() as u32);
             if seq.len() < span {
                 continue;
             }
@@ -46,24 +51,34 @@ impl SpacedIndex {
             mask,
             weight,
             num_proteins: db.len(),
+            seq_lens,
         }
     }
 
+    /// Upper bound on the peak bytes of `build` for a pattern of `weight` ones, see
+    /// `KmerIndex::estimated_bytes`.
+    pub fn estimated_bytes(residues: usize, proteins: usize, weight: usize) -> usize {
+        let entry = mem::size_of::<u64>() + mem::size_of::<PostingsList>();
+        let distinct = max_distinct_words(residues, weight);
+        grown_table_bytes(initial_capacity(residues, proteins), distinct, entry)
+            + 2 * residues * mem::size_of::<(ProteinId, Position)>()
+            + proteins * mem::size_of::<u32>()
+    }
+
     pub fn search_basic(&self, query_seq: &[u8], top_n: usize) -> Vec<(ProteinId, u32)> {
         let span = self.mask.len();
         let mut scores: Vec<u32> = vec![0; self.num_proteins];
         let mut active_pids: Vec<ProteinId> = Vec::new();
         for window in query_seq.windows(span) {
-            if let Some(encoded) = encode_spaced(window, &self.mask) {
-                if let Some(hits) = self.map.get(&encoded) {
-                    // SmallVec 遍历极其高效
-                    for &(pid, _pos) in hits {
-                        let idx = pid as usize;
-                        if scores[idx] == 0 {
-                            active_pids.push(pid);
-                        }
-                        scores[idx] += 1;
+            if let Some(encoded) = encode_spaced(window, &self.mask)
+                && let Some(hits) = self.map.get(&encoded) {
+                // SmallVec 遍历极其高效
+                for &(pid, _pos) in hits {
+                    let idx = pid as usize;
+                    if scores[idx] == 0 {
+                        active_pids.push(pid);
                     }
+                    scores[idx] += 1;
                 }
             }
         }
@@ -71,10 +86,22 @@ impl SpacedIndex {
             .into_iter()
             .map(|pid| (pid, scores[pid as usize]))
             .collect();
-        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        candidates.sort_unstable_by_key(|c| std::cmp::Reverse(c.1));
         if candidates.len() > top_n {
             candidates.truncate(top_n);
         }
         candidates
     }
+
+    /// IDF / BM25 weighted voting, see `KmerIndex::search_idf`.
+    pub fn search_idf(&self, query_seq: &[u8], top_n: usize, length_norm: Option<Bm25>) -> Vec<(ProteinId, f32)> {
+        let mut scorer = WeightedScorer::new(&self.seq_lens, length_norm);
+        for window in query_seq.windows(self.mask.len()) {
+            if let Some(encoded) = encode_spaced(window, &self.mask)
+                && let Some(hits) = self.map.get(&encoded) {
+                scorer.add_postings(hits);
+            }
+        }
+        scorer.finish(top_n)
+    }
 }
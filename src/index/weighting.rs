@@ -0,0 +1,74 @@
+// IDF / BM25 weighted k-mer voting, shared by the contiguous and spaced indexes.
+// A k-mer's "document frequency" is the number of proteins containing it, so common
+// words (poly-Q, poly-A, transmembrane motifs) contribute little to the ranking.
+// Repeats inside one protein raise its term frequency instead.
+use rustc_hash::FxHashMap;
+use crate::index::kmer::{ProteinId, Position};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bm25 {
+    pub k1: f32,
+    pub b: f32,
+}
+
+impl Default for Bm25 {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
+/// Accumulates weighted scores for one query against an index.
+pub struct WeightedScorer<'a> {
+    seq_lens: &'a [u32],
+    avg_len: f32,
+    num_docs: f32,
+    length_norm: Option<Bm25>,
+    scores: FxHashMap<ProteinId, f32>,
+}
+
+impl<'a> WeightedScorer<'a> {
+    pub fn new(seq_lens: &'a [u32], length_norm: Option<Bm25>) -> Self {
+        let total: u64 = seq_lens.iter().map(|&l| l as u64).sum();
+        let avg_len = if seq_lens.is_empty() { 1.0 } else { total as f32 / seq_lens.len() as f32 };
+        Self {
+            seq_lens,
+            avg_len: avg_len.max(1.0),
+            num_docs: seq_lens.len() as f32,
+            length_norm,
+            scores: FxHashMap::default(),
+        }
+    }
+
+    /// BM25-style IDF, always positive even for words hitting most targets.
+    pub fn idf(&self, doc_freq: usize) -> f32 {
+        let df = doc_freq as f32;
+        (1.0 + (self.num_docs - df + 0.5).max(0.0) / (df + 0.5)).ln()
+    }
+
+    /// Adds one query k-mer's postings. Postings are stored in ProteinId order,
+    /// so consecutive runs of the same id give the term frequency and the number
+    /// of runs the document frequency.
+    pub fn add_postings(&mut self, postings: &[(ProteinId, Position)]) {
+        let runs = || postings.chunk_by(|a, b| a.0 == b.0);
+        let idf = self.idf(runs().count());
+        for run in runs() {
+            let (pid, tf) = (run[0].0, run.len());
+            let weight = match self.length_norm {
+                None => idf * tf as f32,
+                Some(Bm25 { k1, b }) => {
+                    let len = self.seq_lens.get(pid as usize).copied().unwrap_or(0) as f32;
+                    let norm = k1 * (1.0 - b + b * len / self.avg_len);
+                    idf * (tf as f32 * (k1 + 1.0)) / (tf as f32 + norm)
+                }
+            };
+            *self.scores.entry(pid).or_insert(0.0) += weight;
+        }
+    }
+
+    pub fn finish(self, top_n: usize) -> Vec<(ProteinId, f32)> {
+        let mut candidates: Vec<(ProteinId, f32)> = self.scores.into_iter().collect();
+        candidates.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        candidates.truncate(top_n);
+        candidates
+    }
+}
@@ -2,4 +2,5 @@ pub mod core;
 pub mod index;
 pub mod filter;
 pub mod align;
-pub mod bench;
\ No newline at end of file
+pub mod bench;
+pub mod search;
\ No newline at end of file
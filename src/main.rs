@@ -1,42 +1,205 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use std::path::PathBuf;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use pro_search::core::database::Database;
-use pro_search::index::kmer::KmerIndex;
-use pro_search::index::spaced;
-use pro_search::filter::{seed};
-use pro_search::align::{ungapped, smith_waterman};
+use pro_search::core::residues::{is_mostly_lowercase, count_mostly_lowercase, ResidueMapper, ResiduePolicy, UnknownResidue, LOWERCASE_WARNING};
+use pro_search::core::translate::{GeneticCode, SequenceType, TranslatedDatabase};
+use pro_search::core::validate::DatabaseReport;
+use pro_search::index::kmer::BuildOptions;
+use pro_search::filter::seg::SegOptions;
+use pro_search::align::composition::CompAdjust;
+use pro_search::align::hmm::Hmm;
+use pro_search::align::msa::Msa;
+use pro_search::align::profile::Pssm;
+use pro_search::align::ungapped::ScoringScheme;
+use pro_search::index::compressed::CompressedKmerIndex;
+use pro_search::search::pipeline::{search_query, ContiguousIndex, Ranking, SearchIndexes, SearchMode, SearchParams};
+use pro_search::search::chunked::{parse_memory, search_chunked, GlobalHit};
+use pro_search::search::query_indexed;
+use pro_search::search::hmmsearch::{search_hmm, HmmSearch, HmmSearchOptions};
+use pro_search::search::iterative::{search_iterative, search_iterative_rows, IterativeSearch, ProfileOptions};
+use pro_search::search::rbh::{coverage, identity, reciprocal_best_hits, RbhOptions};
+use pro_search::search::sensitivity::{search_iterated, IteratedHits, Sensitivity, SensitivityOptions};
+use pro_search::search::translated::{
+    search_translated, search_translated_db, Frameshift, GenomicHit, TranslatedHit, TranslatedQuery,
+};
 use pro_search::bench::experiment;
+use pro_search::bench::query_gen::GroundTruth;
 
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// FASTA database, plain or gzip/zstd/bzip2/xz compressed; "-" reads stdin
     #[arg(short, long, value_name = "DB_FILE")]
     database: PathBuf,
 
+    /// Refuse to load a database with invalid residues, duplicate accessions or empty records
+    #[arg(long, global = true)]
+    strict: bool,
+
+    #[command(flatten)]
+    residues: ResiduePolicy,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once per run, so the size of the search options does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
+    #[command(group(ArgGroup::new("profile_search").args(["iterations", "query_msa"]).multiple(true)))]
     Search {
         #[arg(long)]
         query: Option<String>,
         #[arg(long)]
         query_file: Option<PathBuf>,
+        /// Multiple alignment (aligned FASTA, A3M or Stockholm) searched as a profile
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["query", "query_file", "query_id"])]
+        query_msa: Option<PathBuf>,
+        /// Use database entries as queries (accession, repeatable)
+        #[arg(long)]
+        query_id: Vec<String>,
         #[arg(long, value_enum, default_value_t = SearchMode::Auto)]
         mode: SearchMode,
+        /// K-mer size [default: 5, or set by the preset]
+        #[arg(short, long)]
+        k: Option<usize>,
+        #[arg(short, long, default_value_t = 10)]
+        n: usize,
+        /// X-drop of ungapped extension [default: 10, or set by the preset]
+        #[arg(short, long)]
+        x_drop: Option<usize>,
+        /// K-mer hits a diagonal needs to become a candidate [default: 2, or set by the preset]
+        #[arg(long)]
+        min_diagonal: Option<usize>,
+        /// Spaced seed pattern for spaced mode [default: 11010111, or set by the preset]
+        #[arg(short, long)]
+        pattern: Option<String>,
+        /// Keep only diagonals with seed p-value below this (diagonal/auto modes)
+        #[arg(long)]
+        max_pvalue: Option<f64>,
+        /// K-mer scoring for basic/spaced modes
+        #[arg(long, value_enum, default_value_t = Ranking::Votes)]
+        ranking: Ranking,
+        /// Stop-list k-mers with more postings than this
+        #[arg(long)]
+        max_postings: Option<usize>,
+        /// Stop-list k-mers above this postings-length percentile (0-100)
+        #[arg(long)]
+        mask_percentile: Option<f64>,
+        /// Print index statistics (stop-list, memory, postings histogram)
+        #[arg(long)]
+        index_stats: bool,
+        /// Store postings delta/varint compressed (always with --very-sensitive)
+        #[arg(long)]
+        compressed: bool,
+        /// Print the full subject title (ID and description) for each hit
+        #[arg(long)]
+        stitle: bool,
+        /// Stream the database in chunks to stay under this memory budget (e.g. 4G)
+        #[arg(long)]
+        max_memory: Option<String>,
+        /// Index the queries instead of the database and scan the database once
+        #[arg(long)]
+        query_index: bool,
+        #[command(flatten)]
+        seg: SegOptions,
+        /// Scoring for ungapped extension and Smith-Waterman (auto mode)
+        #[arg(long, value_enum, default_value_t)]
+        scoring: ScoringScheme,
+        /// Composition-based matrix adjustment per query-target pair (needs --scoring blosum62)
+        #[arg(long, value_enum, default_value_t)]
+        comp_based_stats: CompAdjust,
+        /// Query molecule type; nucleotide queries are translated in six frames (blastx)
+        #[arg(long, value_enum, default_value_t)]
+        query_type: SequenceType,
+        /// NCBI genetic code for translating nucleotide queries
+        #[arg(long, default_value_t = 1)]
+        query_gencode: u8,
+        /// Database molecule type; nucleotide databases are indexed as six-frame translations (tblastn)
+        #[arg(long, value_enum, default_value_t)]
+        db_type: SequenceType,
+        /// NCBI genetic code for translating a nucleotide database
+        #[arg(long, default_value_t = 1)]
+        db_gencode: u8,
+        /// Frameshift penalty: stitch per-frame hits of translated searches into one alignment
+        #[arg(long, value_name = "PENALTY")]
+        frameshift: Option<i32>,
+        #[command(flatten)]
+        profile: ProfileOptions,
+        /// Write the last PSSM of an iterative or alignment search in PSI-BLAST ASCII format (single query)
+        #[arg(long, value_name = "FILE", requires = "profile_search")]
+        out_pssm: Option<PathBuf>,
+        #[command(flatten)]
+        sensitivity: SensitivityOptions,
+    },
+    /// Extract sequences from the database, like `blastdbcmd`
+    Fetch {
+        /// Accession(s) to extract, comma separated or repeated
+        #[arg(long, value_delimiter = ',')]
+        id: Vec<String>,
+        /// File with one accession per line
+        #[arg(long)]
+        id_file: Option<PathBuf>,
+        /// Index range START-END (0-based, inclusive)
+        #[arg(long)]
+        index_range: Option<String>,
+        /// Emit only residues FROM-TO (1-based, inclusive)
+        #[arg(long)]
+        residues: Option<String>,
+        /// Output FASTA file (default: stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Report composition, lengths, residue problems and index memory per k
+    #[command(visible_alias = "validate")]
+    Stats {
+        /// Word sizes to estimate index memory for (comma separated)
+        #[arg(long, value_delimiter = ',', default_value = "3,4,5,6")]
+        index_k: Vec<usize>,
+        /// Skip building indexes, only check the sequences
+        #[arg(long)]
+        no_index: bool,
+        /// Records listed per problem category
+        #[arg(long, default_value_t = 10)]
+        max_listed: usize,
+    },
+    /// Reciprocal best hits between the database (A) and a second proteome (B), for orthologs
+    Rbh {
+        /// Second proteome (B), plain or compressed FASTA
+        #[arg(long, value_name = "FASTA")]
+        other: PathBuf,
         #[arg(short, long, default_value_t = 5)]
         k: usize,
+        /// Hits per protein checked against the cutoffs
         #[arg(short, long, default_value_t = 10)]
         n: usize,
         #[arg(short, long, default_value_t = 10)]
-        x_drop:usize,
-        #[arg(short, long,default_value = "11010111")]
-        pattern: String,
+        x_drop: usize,
+        #[command(flatten)]
+        options: RbhOptions,
+        #[command(flatten)]
+        seg: SegOptions,
+        /// Scoring for ungapped extension and Smith-Waterman
+        #[arg(long, value_enum, default_value_t)]
+        scoring: ScoringScheme,
+        /// Pairs table, tab separated (default: stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Scan the database with HMMER3 profile HMMs and report domain hits, like `hmmsearch`
+    Hmmsearch {
+        /// HMMER3 ASCII profile file, plain or compressed; may hold many models (e.g. Pfam-A.hmm)
+        #[arg(long, value_name = "FILE")]
+        hmm: PathBuf,
+        #[command(flatten)]
+        options: HmmSearchOptions,
+        #[arg(long)]
+        stitle: bool,
     },
     Bench {
         #[arg(value_enum)] // Takes the enum as a required positional argument
@@ -63,21 +226,13 @@ enum Commands {
         pattern: String,
         #[arg(short, long)]
         csv_path: Option<PathBuf>,
+        /// Expected top hit of each sampled query: its source sequence, or the best
+        /// exhaustive Smith-Waterman hit
+        #[arg(long, value_enum, default_value_t = GroundTruth::Origin)]
+        truth: GroundTruth,
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
-enum SearchMode {
-    /// K-mer Voting
-    Basic,
-    /// Diagonal Filtering
-    Diagonal,
-    /// Spaced Seeds
-    Spaced,
-    /// Full Pipeline (Index -> Filter -> Ungapped -> Smith-Waterman)
-    Auto, 
-}
-
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum BenchTask {
     /// Task 1: K-mer trade-off
@@ -92,32 +247,399 @@ enum BenchTask {
     Indel,
     /// Task 6: Spaced Seeds
     Spaced,
+    /// Task 7: FASTA parsing throughput
+    Parse,
     /// Run all benchmarks
     All,
 }
-fn main() {
-    let args = Args::parse();
-    if !args.database.exists() {
-        eprintln!("❌ Error: Database file not found: {:?}", args.database);
+/// Parses "A-B" into an inclusive (A, B) pair.
+fn parse_range(s: &str) -> anyhow::Result<(usize, usize)> {
+    let (a, b) = s.split_once('-')
+        .ok_or_else(|| anyhow::anyhow!("Expected a range like 10-20, got {:?}", s))?;
+    let (a, b): (usize, usize) = (a.trim().parse()?, b.trim().parse()?);
+    anyhow::ensure!(a <= b, "Range start {} is after end {}", a, b);
+    Ok((a, b))
+}
+
+fn run_fetch(
+    db: &Database, mut ids: Vec<String>, id_file: Option<PathBuf>,
+    index_range: Option<String>, residues: Option<String>, out: Option<PathBuf>
+) -> anyhow::Result<()> {
+    if let Some(path) = id_file {
+        let text = std::fs::read_to_string(&path)?;
+        ids.extend(text.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from));
+    }
+
+    let mut selected = Vec::new();
+    let accessions = db.accession_index();
+    for id in &ids {
+        match accessions.get(id.as_str()) {
+            Some(&i) => selected.push(i),
+            None => eprintln!("⚠️ Warning: ID not found in database: {}", id),
+        }
+    }
+    if let Some(range) = index_range {
+        let (start, end) = parse_range(&range)?;
+        anyhow::ensure!(end < db.len(), "Index range {} exceeds database size {}", range, db.len());
+        selected.extend(start..=end);
+    }
+    anyhow::ensure!(!selected.is_empty(), "Nothing to fetch. Use --id, --id-file or --index-range.");
+    let residues = residues.as_deref().map(parse_range).transpose()?;
+
+    let mut writer: Box<dyn Write> = match out {
+        Some(path) => Box::new(BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    for i in selected {
+        let (id, seq) = db.get(i).unwrap();
+        let title = db.title(i).unwrap();
+        let seq = match residues {
+            Some((from, to)) => {
+                let from = from.max(1);
+                let to = to.min(seq.len());
+                if from > to {
+                    eprintln!("⚠️ Warning: Residue range is empty for {} (length {})", id, seq.len());
+                    continue;
+                }
+                match db.description(i).unwrap() {
+                    "" => writeln!(writer, ">{}:{}-{}", id, from, to)?,
+                    description => writeln!(writer, ">{}:{}-{} {}", id, from, to, description)?,
+                }
+                &seq[from - 1..to]
+            }
+            None => {
+                writeln!(writer, ">{}", title)?;
+                seq
+            }
+        };
+        for line in seq.chunks(60) {
+            writer.write_all(line)?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn run_rbh(a: &mut Database, b: &mut Database, params: &SearchParams, options: &RbhOptions, out: Option<PathBuf>) -> anyhow::Result<()> {
+    params.seg.mask_database(a);
+    params.seg.mask_database(b);
+    let start = Instant::now();
+    let a_indexes = SearchIndexes::build(a, params);
+    let b_indexes = SearchIndexes::build(b, params);
+    eprintln!("Indexes built in {:.2?}", start.elapsed());
+
+    let start = Instant::now();
+    let pairs = reciprocal_best_hits(a, &a_indexes, b, &b_indexes, params, options);
+    eprintln!("✅ {} reciprocal best hits ({} x {} proteins) in {:.2?}", pairs.len(), a.len(), b.len(), start.elapsed());
+
+    let mut writer: Box<dyn Write> = match out {
+        Some(path) => Box::new(BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    writeln!(writer, "a\tb\tscore_ab\tscore_ba\tidentity\ta_coverage\tb_coverage")?;
+    for pair in &pairs {
+        let (a_cov, b_cov) = coverage(&pair.ab, a.get(pair.a).unwrap().1.len(), b.get(pair.b).unwrap().1.len());
+        writeln!(writer, "{}\t{}\t{}\t{}\t{:.1}\t{:.1}\t{:.1}",
+                a.id(pair.a), b.id(pair.b), pair.ab.score, pair.ba.score, identity(&pair.ab), a_cov, b_cov)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn load_database(path: &Path, strict: bool, residues: Option<&ResidueMapper>) -> Database {
+    eprintln!("Loading database from {:?}...", path);
+    let start_load = Instant::now();
+    let db = match Database::load_from_fasta_with(path, residues) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to load database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("✅ Database loaded in {:.2?} ({} proteins)", start_load.elapsed(), db.len());
+    let lowercase = count_mostly_lowercase(&db);
+    if lowercase > 0 {
+        eprintln!("⚠️ Warning: {} of {} records are mostly lowercase: {}", lowercase, db.len(), LOWERCASE_WARNING);
+    }
+    if strict && let Err(e) = DatabaseReport::build(&db).check() {
+        eprintln!("❌ {} (run `stats` for details)", e);
         std::process::exit(1);
     }
-    println!("Loading database from {:?}...", args.database);
+    db
+}
+
+fn load_translated_database(path: &Path, code: GeneticCode, residues: &ResidueMapper) -> TranslatedDatabase {
+    eprintln!("Loading nucleotide database from {:?} (genetic code {}: {})...", path, code.id, code.name);
     let start_load = Instant::now();
-    let db = match Database::load_from_fasta(&args.database) {
+    let db = match TranslatedDatabase::load_from_fasta(path, code, Some(residues)) {
         Ok(db) => db,
         Err(e) => {
             eprintln!("❌ Failed to load database: {}", e);
             std::process::exit(1);
         }
     };
-    println!("✅ Database loaded in {:.2?} ({} proteins)", start_load.elapsed(), db.len());
+    eprintln!("✅ Database translated in {:.2?} ({} records, {} frames)",
+            start_load.elapsed(), db.records(), db.proteins.len());
+    db
+}
+
+fn run_stats(db: &Database, strict: bool, index_k: &[usize], no_index: bool, max_listed: usize) {
+    println!("--- Database Report ---");
+    let report = DatabaseReport::build(db);
+    report.print(db, max_listed);
+
+    if !no_index {
+        println!("--- Index Memory per k ---");
+        for &k in index_k {
+            if !(1..=12).contains(&k) {
+                eprintln!("⚠️ Warning: Skipping k={}, supported range is 1-12", k);
+                continue;
+            }
+            // The compressed index is far smaller to build; the raw size follows from its postings lengths
+            let compressed = CompressedKmerIndex::build(db, k);
+            let raw = compressed.raw_memory_usage();
+            println!("   k={:<2} | {:>10} k-mers | {:>10.2} MB raw | {:>10.2} MB compressed",
+                    k, compressed.keys.len(),
+                    raw as f64 / 1024.0 / 1024.0,
+                    compressed.memory_usage() as f64 / 1024.0 / 1024.0);
+        }
+    }
 
+    match report.check() {
+        Ok(()) => println!("✅ No invalid residues, duplicate accessions or empty records"),
+        Err(e) if strict => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+        Err(e) => eprintln!("⚠️ Warning: {}", e),
+    }
+}
+
+/// Looks up `--query-id` accessions and appends them as queries.
+fn push_database_queries(db: &Database, query_id: &[String], queries: &mut Vec<(String, Vec<u8>)>) {
+    if query_id.is_empty() {
+        return;
+    }
+    let accessions = db.accession_index();
+    for id in query_id {
+        match accessions.get(id.as_str()) {
+            Some(&i) => {
+                let (id, seq) = db.get(i).unwrap();
+                queries.push((id.to_string(), seq.to_vec()));
+            }
+            None => eprintln!("⚠️ Warning: Query ID not found in database: {}", id),
+        }
+    }
+}
+
+/// IDF/BM25 scores with two decimals, votes and alignment scores as integers.
+fn score_text(score: f32, weighted: bool) -> String {
+    if weighted {
+        format!("{:>7.2}", score)
+    } else {
+        format!("{:>4}", score as i64)
+    }
+}
+
+fn print_hits(hits: &[GlobalHit], stitle: bool, weighted: bool) {
+    println!("   --- Top Hits ---");
+    for (rank, hit) in hits.iter().enumerate() {
+        if stitle {
+            println!("   {}. [Score: {}] {}", rank + 1, score_text(hit.score, weighted), hit.title);
+            continue;
+        }
+        // Truncate Header to avoid screen overflow
+        let short_header: String = hit.accession.chars().take(50).collect();
+        println!("   {}. [Score: {}] {}", rank + 1, score_text(hit.score, weighted), short_header);
+    }
+}
+
+fn print_translated_hits(hits: &[TranslatedHit], stitle: bool, weighted: bool) {
+    println!("   --- Top Hits ---");
+    for (rank, hit) in hits.iter().enumerate() {
+        let name: String = if stitle { hit.title.clone() } else { hit.accession.chars().take(50).collect() };
+        println!("   {}. [Score: {}] [Frame {} | Query {}-{} | Subject {}-{}] {}",
+                rank + 1, score_text(hit.score, weighted), hit.frame, hit.q_start, hit.q_end, hit.t_start, hit.t_end, name);
+        print_frameshifts(&hit.frameshifts);
+    }
+}
+
+fn print_frameshifts(frameshifts: &[Frameshift]) {
+    if frameshifts.is_empty() {
+        return;
+    }
+    let list: Vec<String> = frameshifts.iter().map(|f| format!("{}({:+})", f.position, f.shift)).collect();
+    println!("      Frameshifts: {}", list.join(", "));
+}
+
+fn print_genomic_hits(hits: &[GenomicHit], stitle: bool, weighted: bool) {
+    println!("   --- Top Hits ---");
+    for (rank, hit) in hits.iter().enumerate() {
+        let name: String = if stitle { hit.title.clone() } else { hit.accession.chars().take(50).collect() };
+        let strand = if hit.frame.is_reverse() { "minus" } else { "plus" };
+        match (hit.query_range, hit.subject_range) {
+            (Some((q_start, q_end)), Some((s_start, s_end))) => {
+                println!("   {}. [Score: {}] [Frame {} ({}) | Query {}-{} | Subject {}-{}] {}",
+                        rank + 1, score_text(hit.score, weighted), hit.frame, strand, q_start, q_end, s_start, s_end, name);
+            }
+            _ => println!("   {}. [Score: {}] [Frame {} ({})] {}", rank + 1, score_text(hit.score, weighted), hit.frame, strand, name),
+        }
+        print_frameshifts(&hit.frameshifts);
+    }
+}
+
+fn write_pssm(path: &Path, pssm: &Pssm) {
+    let written = std::fs::File::create(path).and_then(|file| pssm.write_ascii(BufWriter::new(file)));
+    if let Err(e) = written {
+        eprintln!("❌ Failed to write PSSM to {:?}: {}", path, e);
+        std::process::exit(1);
+    }
+    println!("   PSSM written to {:?}", path);
+}
+
+/// Query ranges are profile positions, or MSA columns when `columns` maps them.
+fn print_profile_search(db: &Database, result: &IterativeSearch, columns: Option<&[usize]>, stitle: bool) {
+    for (i, round) in result.rounds.iter().enumerate() {
+        println!("   Round {}: {} hits, {} included ({} new)", i + 1, round.hits, round.included, round.new);
+    }
+    if result.converged {
+        println!("   Converged after {} rounds", result.rounds.len());
+    }
+    println!("   --- Top Hits ---");
+    for (rank, hit) in result.hits.iter().enumerate() {
+        let id = hit.id as usize;
+        let name: String = if stitle { db.title(id).unwrap().to_string() } else { db.id(id).chars().take(50).collect() };
+        let aln = &hit.alignment;
+        let (q_start, q_end) = match columns {
+            Some(columns) => (columns[aln.q_start] + 1, columns[aln.q_end - 1] + 1),
+            None => (aln.q_start + 1, aln.q_end),
+        };
+        let label = if columns.is_some() { "Columns" } else { "Query" };
+        println!("   {}. [Score: {:>7}] [E-value: {:.2e} | {} {}-{} | Subject {}-{}] {}",
+                rank + 1, aln.score, hit.evalue, label, q_start, q_end, aln.t_start + 1, aln.t_end, name);
+    }
+}
+
+fn print_iterated_hits(db: &Database, result: &IteratedHits, rounds: &[(Sensitivity, SearchParams)], stitle: bool) {
+    let (level, params) = &rounds[result.round];
+    if result.significant {
+        println!("   Found in round {} ({}, k={})", result.round + 1, level.name(), params.k);
+    } else {
+        println!("   No significant hit after {} rounds", result.round + 1);
+    }
+    println!("   --- Top Hits ---");
+    for (rank, (hit, evalue)) in result.hits.iter().enumerate() {
+        let id = hit.id as usize;
+        let name: String = if stitle { db.title(id).unwrap().to_string() } else { db.id(id).chars().take(50).collect() };
+        println!("   {}. [Score: {:>7}] [E-value: {:.2e} | Query {}-{} | Subject {}-{}] {}",
+                rank + 1, hit.score, evalue, hit.q_start + 1, hit.q_end, hit.t_start + 1, hit.t_end, name);
+    }
+}
+
+fn print_hmm_hits(db: &Database, result: &HmmSearch, stitle: bool) {
+    println!("   --- Hits ---");
+    for (rank, hit) in result.hits.iter().enumerate() {
+        let name: String = if stitle { db.title(hit.id).unwrap().to_string() } else { db.id(hit.id).chars().take(50).collect() };
+        println!("   {}. [Score: {:>7.1} bits | E-value: {:.2e}] {}", rank + 1, hit.bits, hit.evalue, name);
+        for (i, d) in hit.domains.iter().enumerate() {
+            let domain = &d.domain;
+            println!("      Domain {}: [Score: {:>7.1} bits | i-Evalue: {:.2e} | HMM {}-{} | Subject {}-{}]",
+                    i + 1, domain.bits, d.evalue, domain.hmm_from, domain.hmm_to, domain.seq_from, domain.seq_to);
+        }
+    }
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .init();
+    let args = Args::parse();
+    let from_stdin = args.database == Path::new("-");
+    let residue_map = args.residues.mapper();
+    if !from_stdin && !args.database.exists() {
+        eprintln!("❌ Error: Database file not found: {:?}", args.database);
+        std::process::exit(1);
+    }
 
     match args.command {
         Commands::Search { 
-            query, query_file, 
+            query, query_file, query_id, query_msa,
             mode, k, 
-            n, x_drop, pattern } => {
+            n, x_drop, min_diagonal, pattern, max_pvalue, ranking,
+            max_postings, mask_percentile, index_stats, compressed, stitle,
+            max_memory, query_index, seg, scoring, comp_based_stats,
+            query_type, query_gencode, db_type, db_gencode, frameshift, profile, out_pssm, sensitivity } => {
+            if comp_based_stats != CompAdjust::None && scoring == ScoringScheme::Identity {
+                eprintln!("❌ Error: --comp-based-stats adjusts a substitution matrix; use it with --scoring blosum62.");
+                std::process::exit(1);
+            }
+            if mode == SearchMode::Exhaustive && (comp_based_stats != CompAdjust::None || query_index) {
+                eprintln!("❌ Error: --mode exhaustive scores every target with one query profile; --comp-based-stats and --query-index are not supported.");
+                std::process::exit(1);
+            }
+            let nucleotide = query_type == SequenceType::Nucleotide;
+            let db_nucleotide = db_type == SequenceType::Nucleotide;
+            let (genetic_code, db_genetic_code) = match (GeneticCode::ncbi(query_gencode), GeneticCode::ncbi(db_gencode)) {
+                (Ok(query_code), Ok(db_code)) => (query_code, db_code),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if nucleotide && db_nucleotide {
+                eprintln!("❌ Error: Nucleotide queries against a nucleotide database (tblastx) are not supported.");
+                std::process::exit(1);
+            }
+            if nucleotide && mode != SearchMode::Auto {
+                eprintln!("❌ Error: Nucleotide queries need --mode auto (hits are reported with alignment coordinates).");
+                std::process::exit(1);
+            }
+            if let Some(penalty) = frameshift && (penalty < 0 || !(nucleotide || db_nucleotide) || mode != SearchMode::Auto) {
+                eprintln!("❌ Error: --frameshift {} needs a non-negative penalty, a translated search (--query-type or --db-type nucleotide) and --mode auto.", penalty);
+                std::process::exit(1);
+            }
+            if (nucleotide || db_nucleotide) && (query_index || max_memory.is_some() || !query_id.is_empty()) {
+                eprintln!("❌ Error: Translated searches run in memory; --query-index, --max-memory and --query-id are not supported.");
+                std::process::exit(1);
+            }
+            if sensitivity.iterate {
+                if mode != SearchMode::Auto || scoring != ScoringScheme::Blosum62 {
+                    eprintln!("❌ Error: --iterate needs --mode auto and --scoring blosum62 (significance is a BLOSUM62 E-value).");
+                    std::process::exit(1);
+                }
+                if k.is_some() || x_drop.is_some() || min_diagonal.is_some() {
+                    eprintln!("❌ Error: --iterate takes -k, -x and --min-diagonal from each round's preset; they cannot be given with it.");
+                    std::process::exit(1);
+                }
+                if nucleotide || db_nucleotide || query_index || max_memory.is_some() || profile.iterations.is_some() || query_msa.is_some() {
+                    eprintln!("❌ Error: --iterate searches protein queries against an in-memory database; translated searches, --query-index, --max-memory, --iterations and --query-msa are not supported.");
+                    std::process::exit(1);
+                }
+            }
+            if profile.iterations.is_some() || query_msa.is_some() {
+                if profile.iterations == Some(0) || mode != SearchMode::Auto || scoring != ScoringScheme::Blosum62 || comp_based_stats != CompAdjust::None {
+                    eprintln!("❌ Error: --iterations and --query-msa need at least one round, --mode auto and --scoring blosum62 (without --comp-based-stats).");
+                    std::process::exit(1);
+                }
+                if nucleotide || db_nucleotide || query_index || max_memory.is_some() {
+                    eprintln!("❌ Error: Profile searches need protein sequences and an in-memory database; translated searches, --query-index and --max-memory are not supported.");
+                    std::process::exit(1);
+                }
+            }
+            // Source D: an alignment, searched as one profile query
+            let msa = query_msa.map(|path| match Msa::load(&path) {
+                Ok(msa) => {
+                    let msa_profile = msa.profile();
+                    println!("Loaded {:?} alignment {:?}: {} sequences, {} columns ({} in the profile)",
+                            msa.format, path, msa.rows.len(), msa.columns(), msa_profile.columns.len());
+                    (path, msa_profile)
+                }
+                Err(e) => {
+                    eprintln!("❌ Failed to load query alignment: {}", e);
+                    std::process::exit(1);
+                }
+            });
             // Collect all Queries
             let mut queries = Vec::new();
             
@@ -128,91 +650,345 @@ fn main() {
             
             // Source B: File input
             if let Some(q_path) = query_file {
+                if from_stdin && q_path == Path::new("-") {
+                    eprintln!("❌ Error: The database and --query-file cannot both be read from stdin.");
+                    std::process::exit(1);
+                }
                 match Database::load_from_fasta(&q_path) {
                     Ok(q_db) => {
-                        for (i, id) in q_db.ids.into_iter().enumerate() {
-                            let start = q_db.offsets[i];
-                            let end = q_db.offsets[i+1];
-                            
-                            let seq = q_db.data[start..end].to_vec();
-                            queries.push((id, seq));
+                        for i in 0..q_db.len() {
+                            let (id, seq) = q_db.get(i).unwrap();
+                            queries.push((id.to_string(), seq.to_vec()));
                         }
                     },
                     Err(e) => eprintln!("⚠️ Warning: Failed to load query file: {}", e),
                 }
             }
-            if queries.is_empty() {
-                eprintln!("❌ Error: No query provided. Use --query or --query-file.");
+
+            // Same residue policy as the database (--query-id entries already went through it).
+            // Nucleotide queries get it after translation.
+            let mut queries: Vec<(String, Vec<u8>)> = if nucleotide {
+                queries
+            } else {
+                queries.into_iter()
+                    .flat_map(|(q_id, q_seq)| residue_map.fragments(&q_id, &q_seq))
+                    .collect()
+            };
+            for (q_id, q_seq) in &queries {
+                if is_mostly_lowercase(q_seq) {
+                    eprintln!("⚠️ Warning: Query {} is mostly lowercase: {}", q_id, LOWERCASE_WARNING);
+                }
+            }
+
+            // k, pattern, x_drop, min_diagonal and sw_candidates are set from the preset below
+            let mut params = SearchParams {
+                mode, k: 0, top_n: n, x_drop: 0, pattern: String::new(), max_pvalue, ranking,
+                build: BuildOptions {
+                    max_postings,
+                    max_percentile: mask_percentile,
+                    wildcard_x: args.residues.unknown == UnknownResidue::Wildcard,
+                },
+                compressed,
+                residues: args.residues,
+                strict: args.strict,
+                seg,
+                scoring,
+                comp_adjust: comp_based_stats,
+                frameshift,
+                min_diagonal: 0,
+                sw_candidates: 0,
+            };
+            // A preset fills in what was not given on the command line
+            let tuned = |level: Sensitivity| {
+                let mut params = params.clone();
+                level.apply(&mut params);
+                params.k = k.unwrap_or(params.k);
+                params.pattern = pattern.clone().unwrap_or(params.pattern);
+                params.compressed |= compressed;
+                params.x_drop = x_drop.map_or(params.x_drop, |x| x as i32);
+                params.min_diagonal = min_diagonal.unwrap_or(params.min_diagonal);
+                params
+            };
+            let rounds: Vec<(Sensitivity, SearchParams)> = sensitivity.levels().into_iter().map(|l| (l, tuned(l))).collect();
+            params = tuned(sensitivity.level());
+            let k = params.k;
+            let weighted = ranking != Ranking::Votes && matches!(mode, SearchMode::Basic | SearchMode::Spaced);
+
+            // Query-side index: stream targets past an index of the query batch
+            if query_index {
+                if params.compressed || max_memory.is_some() {
+                    eprintln!("❌ Error: --query-index builds no database index; --compressed (also set by --very-sensitive) and --max-memory do not apply.");
+                    std::process::exit(1);
+                }
+                let db = if query_id.is_empty() { None } else { Some(load_database(&args.database, args.strict, Some(&residue_map))) };
+                if let Some(db) = &db {
+                    push_database_queries(db, &query_id, &mut queries);
+                }
+                if queries.is_empty() {
+                    eprintln!("❌ Error: No query provided. Use --query, --query-file or --query-id.");
+                    return;
+                }
+                let mut q_db = Database::new();
+                for (q_id, q_seq) in &queries {
+                    q_db.push(q_id, q_seq);
+                }
+                println!("Running query-indexed search for {} queries (Mode: {:?}, k={})...", queries.len(), mode, k);
+                let start_search = Instant::now();
+                let results = match &db {
+                    Some(db) => query_indexed::search_database(&q_db, db, &params),
+                    None => query_indexed::search_fasta(&q_db, &args.database, &params),
+                };
+                match results {
+                    Ok(results) => {
+                        for ((q_id, q_seq), hits) in queries.iter().zip(results) {
+                            println!("\n🔍 Query: {} (Length: {})", q_id, q_seq.len());
+                            print_hits(&hits, stitle, weighted);
+                        }
+                        println!("\nTotal search time: {:.2?}", start_search.elapsed());
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Query-indexed search failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+
+            // Streaming: the database never has to fit in memory at once
+            if let Some(max_memory) = max_memory {
+                if !query_id.is_empty() {
+                    eprintln!("❌ Error: --query-id needs the whole database loaded; it cannot be combined with --max-memory.");
+                    std::process::exit(1);
+                }
+                if queries.is_empty() {
+                    eprintln!("❌ Error: No query provided. Use --query or --query-file.");
+                    return;
+                }
+                let budget = match parse_memory(&max_memory) {
+                    Ok(b) => b,
+                    Err(e) => {
+                        eprintln!("❌ Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                println!("Running chunked search for {} queries (Mode: {:?}, k={}, at most {} MB per chunk)...",
+                        queries.len(), mode, k, budget >> 20);
+                let start_search = Instant::now();
+                match search_chunked(&args.database, &queries, &params, budget) {
+                    Ok(results) => {
+                        for ((q_id, q_seq), hits) in queries.iter().zip(results) {
+                            println!("\n🔍 Query: {} (Length: {})", q_id, q_seq.len());
+                            print_hits(&hits, stitle, weighted);
+                        }
+                        println!("\nTotal search time: {:.2?}", start_search.elapsed());
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Chunked search failed: {:#}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+
+            // Protein queries against six-frame translations of a nucleotide database
+            if db_nucleotide {
+                let mut db = load_translated_database(&args.database, db_genetic_code, &residue_map);
+                params.seg.mask_database(&mut db.proteins);
+                if queries.is_empty() {
+                    eprintln!("❌ Error: No query provided. Use --query or --query-file.");
+                    return;
+                }
+                println!("Running translated-database search for {} queries (Mode: {:?}, k={})...", queries.len(), mode, k);
+                let start_idx = Instant::now();
+                let indexes = SearchIndexes::build(&db.proteins, &params);
+                println!("Index built in {:.2?}", start_idx.elapsed());
+                for (q_id, q_seq) in queries {
+                    println!("\n🔍 Query: {} (Length: {})", q_id, q_seq.len());
+                    let start_search = Instant::now();
+                    let hits = search_translated_db(&db, &indexes, &q_seq, &params);
+                    println!("   Search time: {:.2?}", start_search.elapsed());
+                    print_genomic_hits(&hits, stitle, weighted);
+                }
+                return;
+            }
+
+            let mut db = load_database(&args.database, args.strict, Some(&residue_map));
+
+            // Source C: Database entries (taken before the database is masked)
+            push_database_queries(&db, &query_id, &mut queries);
+            params.seg.mask_database(&mut db);
+            let query_count = queries.len() + usize::from(msa.is_some());
+            if query_count == 0 {
+                eprintln!("❌ Error: No query provided. Use --query, --query-file, --query-id or --query-msa.");
                 return;
             }
 
-            println!("Running search for {} queries (Mode: {:?}, k={})...", queries.len(), mode, k);
+            if out_pssm.is_some() && query_count != 1 {
+                eprintln!("❌ Error: --out-pssm writes the profile of a single query, got {} queries.", query_count);
+                std::process::exit(1);
+            }
+
+            if sensitivity.iterate {
+                let levels: Vec<&str> = rounds.iter().map(|(level, _)| level.name()).collect();
+                println!("Running iterated search for {} queries (rounds: {})...", queries.len(), levels.join(", "));
+                let start_search = Instant::now();
+                let q_seqs: Vec<&[u8]> = queries.iter().map(|(_, q_seq)| q_seq.as_slice()).collect();
+                let results = search_iterated(&db, &q_seqs, &rounds, sensitivity.iterate_evalue);
+                for ((q_id, q_seq), result) in queries.iter().zip(&results) {
+                    println!("\n🔍 Query: {} (Length: {})", q_id, q_seq.len());
+                    print_iterated_hits(&db, result, &rounds, stitle);
+                }
+                println!("\nTotal search time: {:.2?}", start_search.elapsed());
+                return;
+            }
+
+            println!("Running search for {} queries (Mode: {:?}, k={})...", query_count, mode, k);
             
             let start_idx = Instant::now();
-            let index = KmerIndex::build(&db, k);
-            //  SpacedIndex
-            let spaced_index = if mode == SearchMode::Spaced {
-                Some(spaced::SpacedIndex::build(&db, &pattern))
-            } else {
-                None
-            };
+            let indexes = SearchIndexes::build(&db, &params);
+            match &indexes.contiguous {
+                ContiguousIndex::Compressed(index) => {
+                    println!("Compressed postings: {:.2} MB (uncompressed: {:.2} MB)",
+                            index.memory_usage() as f64 / 1024.0 / 1024.0,
+                            index.raw_memory_usage() as f64 / 1024.0 / 1024.0);
+                    if index_stats {
+                        index.stats().print();
+                    }
+                }
+                ContiguousIndex::Raw(index) => {
+                    if index_stats {
+                        index.stats().print();
+                    }
+                }
+            }
             println!("Index built in {:.2?}", start_idx.elapsed());
 
 
+            if let Some((path, msa_profile)) = msa {
+                println!("\n🔍 Query: {:?} (Profile columns: {})", path, msa_profile.columns.len());
+                let start_search = Instant::now();
+                let result = search_iterative_rows(&db, &indexes, &msa_profile.consensus, &msa_profile.rows, &params, &profile);
+                println!("   Search time: {:.2?}", start_search.elapsed());
+                print_profile_search(&db, &result, Some(&msa_profile.columns), stitle);
+                if let Some(path) = &out_pssm {
+                    write_pssm(path, &result.pssm);
+                }
+                return;
+            }
+            if nucleotide {
+                println!("Translating queries with genetic code {} ({})", genetic_code.id, genetic_code.name);
+            }
             for (q_id, q_seq) in queries {
+                if nucleotide {
+                    println!("\n🔍 Query: {} (Length: {} nt)", q_id, q_seq.len());
+                    let start_search = Instant::now();
+                    let translated = TranslatedQuery::new(&q_seq, &genetic_code, &residue_map);
+                    let hits = search_translated(&db, &indexes, &translated, &params);
+                    println!("   Search time: {:.2?}", start_search.elapsed());
+                    print_translated_hits(&hits, stitle, weighted);
+                    continue;
+                }
                 println!("\n🔍 Query: {} (Length: {})", q_id, q_seq.len());
                 let start_search = Instant::now();
-
-                let results: Vec<(u32, u32)> = match mode {
-                    SearchMode::Basic => {
-                        index.search_basic(&q_seq, n)
-                    },
-                    SearchMode::Diagonal => {
-                        let cands = seed::find_candidate(&index, &q_seq, 2);
-                        cands.into_iter().take(n).map(|c| (c.id, c.score as u32)).collect()
-                    },
-                    SearchMode::Spaced => {
-                        let idx = spaced_index.as_ref().unwrap();
-                        let mut hits = idx.search_basic(&q_seq, n);
-                        if hits.len() > n { hits.truncate(n); }
-                        hits
-                    },
-                    SearchMode::Auto => {
-                        let candidates = seed::find_candidate(&index, &q_seq, 2);
-                        let scoring = ungapped::Scoring::default();
-                        let ungapped_hits = ungapped::refine_ungapped(
-                            &q_seq, &candidates, &db,
-                            &scoring, x_drop as i32, n
-                        );
-                        
-                        // Smith-Waterman Refinement
-                        let mut final_hits = Vec::new();
-                        for (id, ext) in ungapped_hits.into_iter().take(20) { // Top 20
-                            let (_, t_full) = db.get(id as usize).unwrap();
-                            let q_center = (ext.q_start + ext.q_end) / 2;
-                            let t_center = (ext.t_start + ext.t_end) / 2;
-                            
-                            let (q_sub, _) = smith_waterman::extract_window(&q_seq, q_center, 60);
-                            let (t_sub, _) = smith_waterman::extract_window(t_full, t_center, 60);
-                            
-                            let align = smith_waterman::align_sw(&q_sub, &t_sub, -10, -1, 1, -1);
-                            final_hits.push((id, align.score as u32));
-                        }
-                        
-                        final_hits.sort_by(|a, b| b.1.cmp(&a.1));
-                        final_hits
+                if profile.iterations.is_some() {
+                    let result = search_iterative(&db, &indexes, &q_seq, &params, &profile);
+                    println!("   Search time: {:.2?}", start_search.elapsed());
+                    print_profile_search(&db, &result, None, stitle);
+                    if let Some(path) = &out_pssm {
+                        write_pssm(path, &result.pssm);
                     }
-                };
+                    continue;
+                }
+
+                let hits: Vec<GlobalHit> = search_query(&db, &indexes, &q_seq, &params)
+                    .into_iter()
+                    .map(|(pid, score)| GlobalHit {
+                        id: pid as usize,
+                        score,
+                        accession: db.id(pid as usize).to_string(),
+                        title: db.title(pid as usize).unwrap().to_string(),
+                    })
+                    .collect();
 
                 // Output results
                 println!("   Search time: {:.2?}", start_search.elapsed());
-                println!("   --- Top Hits ---");
-                for (rank, (pid, score)) in results.iter().take(n).enumerate() {
-                    let (header, _) = db.get(*pid as usize).unwrap();
-                    // Truncate Header to avoid screen overflow
-                    let short_header: String = header.chars().take(50).collect();
-                    println!("   {}. [Score: {:>4}] {}", rank + 1, score, short_header);
+                print_hits(&hits, stitle, weighted);
+            }
+        }
+
+        Commands::Fetch { id, id_file, index_range, residues, out } => {
+            let db = load_database(&args.database, args.strict, Some(&residue_map));
+            if let Err(e) = run_fetch(&db, id, id_file, index_range, residues, out) {
+                eprintln!("❌ Fetch failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Stats { index_k, no_index, max_listed } => {
+            // Load raw and without the strict check, so the report shows the input as-is
+            let db = load_database(&args.database, false, None);
+            run_stats(&db, args.strict, &index_k, no_index, max_listed);
+        }
+
+        Commands::Rbh { other, k, n, x_drop, options, seg, scoring, out } => {
+            let params = SearchParams {
+                k, top_n: n, x_drop: x_drop as i32,
+                build: BuildOptions {
+                    wildcard_x: args.residues.unknown == UnknownResidue::Wildcard,
+                    ..Default::default()
+                },
+                residues: args.residues,
+                seg,
+                scoring,
+                ..Default::default()
+            };
+            let mut a = load_database(&args.database, args.strict, Some(&residue_map));
+            let mut b = load_database(&other, args.strict, Some(&residue_map));
+            if let Err(e) = run_rbh(&mut a, &mut b, &params, &options, out) {
+                eprintln!("❌ RBH failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Hmmsearch { hmm, options, stitle } => {
+            let models = match Hmm::open(&hmm) {
+                Ok(models) => models,
+                Err(e) => {
+                    eprintln!("❌ Failed to open profile HMMs: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let db = load_database(&args.database, args.strict, Some(&residue_map));
+            // Models are read one at a time, so all of Pfam never sits in memory
+            let mut searched = 0;
+            for model in models {
+                let model = match model {
+                    Ok(model) => model,
+                    Err(e) => {
+                        eprintln!("❌ Failed to read profile HMM: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                searched += 1;
+                let accession = model.accession.as_deref().map(|a| format!("{}, ", a)).unwrap_or_default();
+                println!("\n🔍 Model: {} ({}{} nodes) {}", model.name, accession, model.len(), model.description.as_deref().unwrap_or(""));
+                let start_search = Instant::now();
+                let result = match search_hmm(&db, &model, &options) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        eprintln!("❌ Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                if !options.max {
+                    println!("   SSV filter: {} of {} sequences passed", result.passed_filter, db.len());
                 }
+                println!("   Search time: {:.2?}", start_search.elapsed());
+                print_hmm_hits(&db, &result, stitle);
+            }
+            if searched == 0 {
+                eprintln!("❌ No HMMER3 models in {:?}", hmm);
+                std::process::exit(1);
             }
         }
 
@@ -221,21 +997,28 @@ fn main() {
             task, n, k, 
             mutate, length, sub_rate, 
             indel_rate, sample_num, 
-            x_drop, min_diagonal, pattern, csv_path} => {
+            x_drop, min_diagonal, pattern, csv_path, truth} => {
+            if task == BenchTask::Parse {
+                experiment::run_parse_throughput(&args.database, 5);
+                return;
+            }
+            let db = load_database(&args.database, args.strict, Some(&residue_map));
             match task {
-                BenchTask::K => experiment::run_k_tradeoff(&db, n, mutate, length, sub_rate, indel_rate, sample_num, csv_path.as_deref()),
-                BenchTask::Filter => experiment::run_filter_comparison(&db, n, k,mutate, length, sub_rate, indel_rate, sample_num, min_diagonal, csv_path.as_deref()),
-                BenchTask::Ungap => experiment::run_ungapped_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, csv_path.as_deref()),
-                BenchTask::Indel => experiment::run_indel_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, csv_path.as_deref()),
-                BenchTask::Spaced => experiment::run_spaced_seed_test(&db, n, k, &pattern, sample_num, sub_rate, indel_rate, length, csv_path.as_deref()),
-                BenchTask::Stress => experiment::run_stress_all(&db, sample_num, n, csv_path.as_deref()),
+                BenchTask::K => experiment::run_k_tradeoff(&db, n, mutate, length, sub_rate, indel_rate, sample_num, truth, csv_path.as_deref()),
+                BenchTask::Filter => experiment::run_filter_comparison(&db, n, k,mutate, length, sub_rate, indel_rate, sample_num, min_diagonal, truth, csv_path.as_deref()),
+                BenchTask::Ungap => experiment::run_ungapped_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, truth, csv_path.as_deref()),
+                BenchTask::Indel => experiment::run_indel_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, truth, csv_path.as_deref()),
+                BenchTask::Spaced => experiment::run_spaced_seed_test(&db, n, k, &pattern, sample_num, sub_rate, indel_rate, length, truth, csv_path.as_deref()),
+                BenchTask::Stress => experiment::run_stress_all(&db, sample_num, n, truth, csv_path.as_deref()),
+                BenchTask::Parse => unreachable!("handled before loading"),
                 BenchTask::All => {
-                    experiment::run_k_tradeoff(&db, n, mutate, length, sub_rate, indel_rate, sample_num, csv_path.as_deref());
-                    experiment::run_filter_comparison(&db, n, k, mutate, length, sub_rate, indel_rate, sample_num, min_diagonal, csv_path.as_deref());
-                    experiment::run_ungapped_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, csv_path.as_deref());
-                    experiment::run_indel_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, csv_path.as_deref());
-                    experiment::run_spaced_seed_test(&db, n, k, &pattern, sample_num, sub_rate, indel_rate, length, csv_path.as_deref());
-                    experiment::run_stress_all(&db, sample_num, n, csv_path.as_deref());
+                    experiment::run_parse_throughput(&args.database, 5);
+                    experiment::run_k_tradeoff(&db, n, mutate, length, sub_rate, indel_rate, sample_num, truth, csv_path.as_deref());
+                    experiment::run_filter_comparison(&db, n, k, mutate, length, sub_rate, indel_rate, sample_num, min_diagonal, truth, csv_path.as_deref());
+                    experiment::run_ungapped_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, truth, csv_path.as_deref());
+                    experiment::run_indel_test(&db, sample_num, n, k, length, sub_rate, indel_rate, x_drop, truth, csv_path.as_deref());
+                    experiment::run_spaced_seed_test(&db, n, k, &pattern, sample_num, sub_rate, indel_rate, length, truth, csv_path.as_deref());
+                    experiment::run_stress_all(&db, sample_num, n, truth, csv_path.as_deref());
                 }
             }
         }
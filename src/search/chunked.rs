@@ -0,0 +1,135 @@
+// Streaming search for databases larger than RAM: read a chunk, index it,
+// run every query against it, merge into the running per-query top-N, drop it.
+//
+// Seed p-values and IDF weights are computed per chunk, so scores from those
+// options can differ slightly from a single in-memory search. Alignment scores
+// (auto and exhaustive modes) and plain vote counts do not depend on chunking.
+use std::mem;
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use crate::core::database::Database;
+use crate::core::fasta::READ_BUFFER_BYTES;
+use crate::core::residues::{count_mostly_lowercase, LOWERCASE_WARNING};
+use crate::core::validate::StreamCheck;
+use crate::filter::seed::seed_buffer_bytes;
+use crate::index::compressed::CompressedKmerIndex;
+use crate::index::kmer::{KmerIndex, ProteinId};
+use crate::index::spaced::SpacedIndex;
+use crate::search::pipeline::{search_query, SearchIndexes, SearchMode, SearchParams};
+
+/// A hit with its global database index and the header copied out of its chunk.
+#[derive(Debug, Clone)]
+pub struct GlobalHit {
+    pub id: usize,
+    pub score: f32,
+    pub accession: String,
+    pub title: String,
+}
+
+/// Upper bound on the peak bytes of searching one chunk of `proteins` records with
+/// `residues` residues and `header_bytes` of headers. Covers the `Database` (its vectors
+/// grow by doubling), the indexes `params` builds and the seed buffers of one query,
+/// assuming the query touches each posting at most once.
+pub fn chunk_bytes(params: &SearchParams, residues: usize, proteins: usize, header_bytes: usize) -> usize {
+    let per_protein = 2 * mem::size_of::<usize>() + mem::size_of::<(u32, u32)>(); // offsets, id_spans
+    let database = 2 * (residues + header_bytes + proteins * per_protein);
+    let contiguous = match params.mode {
+        SearchMode::Exhaustive => 0,
+        _ if params.compressed => CompressedKmerIndex::estimated_bytes(residues, proteins, params.k),
+        _ => KmerIndex::estimated_bytes(residues, proteins, params.k, &params.build),
+    };
+    let spaced = match params.mode {
+        SearchMode::Spaced => SpacedIndex::estimated_bytes(residues, proteins, params.pattern.matches('1').count()),
+        _ => 0,
+    };
+    let query = match params.mode {
+        SearchMode::Exhaustive => proteins * mem::size_of::<(ProteinId, i32)>(),
+        _ => seed_buffer_bytes(proteins, residues),
+    };
+    database + contiguous + spaced + query
+}
+
+/// Bytes held outside the chunks: the queries, their merged top-N hits and the FASTA
+/// reader's buffer.
+pub fn resident_bytes(queries: &[(String, Vec<u8>)], params: &SearchParams) -> usize {
+    let query_bytes: usize = queries.iter().map(|(id, seq)| id.len() + seq.len()).sum();
+    query_bytes + queries.len() * 2 * params.top_n * mem::size_of::<GlobalHit>() + READ_BUFFER_BYTES
+}
+
+/// Parses sizes like "512M", "4G" or a plain byte count.
+pub fn parse_memory(s: &str) -> Result<usize> {
+    let s = s.trim();
+    let (digits, scale) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&s[..s.len() - 1], 1usize << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        Some('T') => (&s[..s.len() - 1], 1 << 40),
+        _ => (s, 1),
+    };
+    let value: f64 = digits.trim().parse()
+        .map_err(|_| anyhow::anyhow!("Invalid memory size {:?}, expected e.g. 512M or 4G", s))?;
+    Ok((value * scale as f64) as usize)
+}
+
+fn merge_top_n(merged: &mut Vec<GlobalHit>, new_hits: Vec<GlobalHit>, top_n: usize) {
+    merged.extend(new_hits);
+    merged.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    merged.truncate(top_n);
+}
+
+/// Searches all `queries` against the FASTA at `path`, one chunk at a time, with
+/// chunks sized by `chunk_bytes` to keep the estimated peak under `max_memory` bytes.
+/// Returns the merged top-N per query. Fails when the budget does not cover the
+/// resident bytes or a record on its own.
+pub fn search_chunked<P: AsRef<Path>>(
+    path: P,
+    queries: &[(String, Vec<u8>)],
+    params: &SearchParams,
+    max_memory: usize,
+) -> Result<Vec<Vec<GlobalHit>>> {
+    let resident = resident_bytes(queries, params);
+    if resident >= max_memory {
+        bail!("--max-memory of {} MB does not cover the queries, their hits and the read buffer ({} MB)",
+            max_memory >> 20, resident.div_ceil(1 << 20));
+    }
+    let budget = max_memory - resident;
+    let sizing = params.clone();
+    let mut chunks = Database::chunks_within(path, move |residues, proteins, header_bytes| {
+        chunk_bytes(&sizing, residues, proteins, header_bytes) <= budget
+    })?.with_residue_policy(&params.residues);
+    let mut merged: Vec<Vec<GlobalHit>> = vec![Vec::new(); queries.len()];
+    let mut check = params.strict.then(StreamCheck::default);
+    let mut lowercase = 0;
+
+    while let Some(mut chunk) = chunks.next_chunk()
+        .with_context(|| format!("--max-memory leaves {} MB per chunk", budget >> 20))? {
+        if let Some(check) = &mut check {
+            check.check(&chunk.db, chunk.base)?;
+        }
+        lowercase += count_mostly_lowercase(&chunk.db);
+        params.seg.mask_database(&mut chunk.db);
+        let (residues, proteins) = (chunk.db.data.len(), chunk.db.len());
+        log::info!("Chunk {}..{} ({} residues, at most {} MB)", chunk.base, chunk.base + proteins, residues,
+            chunk_bytes(params, residues, proteins, chunk.db.headers.len()) >> 20);
+        let indexes = SearchIndexes::build(&chunk.db, params);
+        for (q_idx, (_, q_seq)) in queries.iter().enumerate() {
+            let hits = search_query(&chunk.db, &indexes, q_seq, params)
+                .into_iter()
+                .map(|(pid, score)| {
+                    let local = pid as usize;
+                    GlobalHit {
+                        id: chunk.base + local,
+                        score,
+                        accession: chunk.db.id(local).to_string(),
+                        title: chunk.db.title(local).unwrap().to_string(),
+                    }
+                })
+                .collect();
+            merge_top_n(&mut merged[q_idx], hits, params.top_n);
+        }
+    }
+    if lowercase > 0 {
+        log::warn!("{} records are mostly lowercase: {}", lowercase, LOWERCASE_WARNING);
+    }
+    Ok(merged)
+}
@@ -0,0 +1,92 @@
+// hmmsearch-style scan of the database with a profile HMM: the SSV filter on every
+// sequence, then Viterbi with traceback on the ones whose filter P-value is at or below
+// `f1`. E-values are P-values times the number of sequences in the database, for the
+// whole sequence and for each domain on its own (hmmsearch's "i-Evalue").
+use anyhow::{bail, Result};
+use crate::align::hmm::{Domain, Hmm};
+use crate::core::database::Database;
+
+/// Reporting thresholds and filter settings, named after hmmsearch's.
+#[derive(Debug, Clone, Copy, clap::Args)]
+#[command(about = None, long_about = None)]
+pub struct HmmSearchOptions {
+    /// Report sequences with an E-value at or below this
+    #[arg(long, default_value_t = 10.0)]
+    pub evalue: f64,
+    /// Use each model's Pfam gathering thresholds (GA) instead of --evalue
+    #[arg(long, conflicts_with = "evalue")]
+    pub cut_ga: bool,
+    /// P-value at or below which a sequence passes the SSV filter
+    #[arg(long, default_value_t = 0.02)]
+    pub f1: f64,
+    /// Turn the SSV filter off and align every sequence
+    #[arg(long)]
+    pub max: bool,
+}
+
+impl Default for HmmSearchOptions {
+    fn default() -> Self {
+        Self { evalue: 10.0, cut_ga: false, f1: 0.02, max: false }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DomainHit {
+    pub domain: Domain,
+    pub evalue: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HmmHit {
+    pub id: usize,
+    pub bits: f32,
+    pub evalue: f64,
+    /// In target order
+    pub domains: Vec<DomainHit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HmmSearch {
+    /// Best first
+    pub hits: Vec<HmmHit>,
+    /// Sequences that went on to Viterbi
+    pub passed_filter: usize,
+}
+
+/// Scans `db` with `hmm`. With `cut_ga`, a sequence is reported if it reaches the
+/// sequence threshold and has a domain reaching the domain threshold (only those are
+/// listed); otherwise all domains of sequences within `evalue` are.
+pub fn search_hmm(db: &Database, hmm: &Hmm, options: &HmmSearchOptions) -> Result<HmmSearch> {
+    let gathering = match (options.cut_ga, hmm.gathering) {
+        (true, None) => bail!("{} has no GA gathering thresholds for --cut-ga", hmm.name),
+        (true, ga) => ga,
+        (false, _) => None,
+    };
+    let z = db.len() as f64;
+    let mut passed_filter = 0;
+    let mut hits = Vec::new();
+    for id in 0..db.len() {
+        let (_, seq) = db.get(id).unwrap();
+        if !options.max && hmm.msv.pvalue(hmm.ssv_bits(seq)) > options.f1 {
+            continue;
+        }
+        passed_filter += 1;
+        let result = hmm.viterbi(seq);
+        let evalue = hmm.viterbi.pvalue(result.bits) * z;
+        let mut domains: Vec<DomainHit> = result.domains.into_iter()
+            .map(|domain| DomainHit { domain, evalue: hmm.viterbi.pvalue(domain.bits) * z })
+            .collect();
+        let reported = match gathering {
+            Some((sequence, domain)) => {
+                domains.retain(|d| d.domain.bits >= domain);
+                result.bits >= sequence
+            }
+            None => evalue <= options.evalue,
+        };
+        if reported && !domains.is_empty() {
+            hits.push(HmmHit { id, bits: result.bits, evalue, domains });
+        }
+    }
+    hits.sort_by(|a, b| b.bits.total_cmp(&a.bits).then(a.id.cmp(&b.id)));
+    Ok(HmmSearch { hits, passed_filter })
+}
@@ -0,0 +1,147 @@
+// PSI-BLAST-style iterative search. Each round seeds with the profile's neighborhood
+// words, ranks candidates by their best ungapped segment on the seeded diagonal, and
+// aligns the top `top_n` to the whole profile. Hits with an E-value at or below the
+// inclusion threshold make up the next round's PSSM. The first round's profile is the
+// query's BLOSUM62 rows, so it is an ordinary BLOSUM62 search with E-values. An MSA
+// query starts from the profile of its own rows instead, and keeps them in every
+// later profile. Rounds stop after `iterations` or once the included set no longer
+// changes.
+use crate::align::profile::{align_profile, evalue, ProfileAlignment, Pssm};
+use crate::align::ungapped::Scoring;
+use crate::core::database::Database;
+use crate::index::kmer::ProteinId;
+use crate::search::pipeline::{SearchIndexes, SearchParams};
+
+/// How profile rounds are run.
+#[derive(Debug, Clone, Copy, clap::Args)]
+#[command(about = None, long_about = None)]
+pub struct ProfileOptions {
+    /// Rounds of PSI-BLAST-style profile search (auto mode, BLOSUM62)
+    #[arg(long, value_name = "N")]
+    pub iterations: Option<usize>,
+    /// E-value at or below which a hit goes into the next round's PSSM
+    #[arg(long, default_value_t = 0.002)]
+    pub inclusion_ethresh: f64,
+    /// Neighborhood word score threshold for profile seeding [default: 11 per 3 residues of k]
+    #[arg(long)]
+    pub word_threshold: Option<i32>,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        Self { iterations: None, inclusion_ethresh: 0.002, word_threshold: None }
+    }
+}
+
+impl ProfileOptions {
+    /// BLAST's T = 11 for 3-mers, scaled to `k`.
+    pub fn word_threshold(&self, k: usize) -> i32 {
+        self.word_threshold.unwrap_or((11 * k as i32) / 3)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileHit {
+    pub id: ProteinId,
+    pub evalue: f64,
+    pub alignment: ProfileAlignment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Round {
+    pub hits: usize,
+    /// Hits at or below the inclusion threshold
+    pub included: usize,
+    /// Included hits that were not included in the round before
+    pub new: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IterativeSearch {
+    /// Hits of the last round, best first
+    pub hits: Vec<ProfileHit>,
+    pub rounds: Vec<Round>,
+    /// PSSM built from the last round's included hits
+    pub pssm: Pssm,
+    /// Whether the last round included the same targets as the one before
+    pub converged: bool,
+}
+
+/// One round: best first, at most `top_n`.
+pub fn search_profile(
+    db: &Database,
+    indexes: &SearchIndexes,
+    pssm: &Pssm,
+    params: &SearchParams,
+    word_threshold: i32,
+) -> Vec<ProfileHit> {
+    let scoring = Scoring::blosum62();
+    let words = pssm.neighborhood_words(params.k, word_threshold);
+    let candidates = indexes.contiguous.find_word_seeds(pssm.len(), &words, params.max_pvalue, params.min_diagonal);
+    let mut ranked: Vec<(ProteinId, i32)> = candidates.iter()
+        .filter_map(|c| {
+            let (_, target) = db.get(c.id as usize)?;
+            pssm.best_segment(target, c.best_diagonal).map(|ext| (c.id, ext.score))
+        })
+        .collect();
+    ranked.sort_unstable_by_key(|&(id, score)| (std::cmp::Reverse(score), id));
+    ranked.truncate(params.top_n);
+
+    let db_residues = db.data.len();
+    let mut hits: Vec<ProfileHit> = ranked.into_iter()
+        .map(|(id, _)| {
+            let (_, target) = db.get(id as usize).unwrap();
+            let alignment = align_profile(pssm, target, scoring.gap_open, scoring.gap_extend);
+            ProfileHit { id, evalue: evalue(alignment.score, pssm.len(), db_residues), alignment }
+        })
+        .filter(|h| h.alignment.score > 0)
+        .collect();
+    hits.sort_by_key(|h| (std::cmp::Reverse(h.alignment.score), h.id));
+    hits
+}
+
+/// Runs up to `options.iterations` rounds (at least one) for `q_seq`.
+pub fn search_iterative(
+    db: &Database,
+    indexes: &SearchIndexes,
+    q_seq: &[u8],
+    params: &SearchParams,
+    options: &ProfileOptions,
+) -> IterativeSearch {
+    let q_seq = params.seg.mask_query(q_seq);
+    let rows = vec![q_seq.iter().map(|&b| Some(b)).collect()];
+    search_iterative_rows(db, indexes, &q_seq, &rows, params, options)
+}
+
+/// `search_iterative` from the profile of `rows` (see `Pssm::from_rows`), e.g. an MSA's
+/// profile columns with their consensus as `query`. Hit coordinates are profile columns.
+pub fn search_iterative_rows(
+    db: &Database,
+    indexes: &SearchIndexes,
+    query: &[u8],
+    rows: &[Vec<Option<u8>>],
+    params: &SearchParams,
+    options: &ProfileOptions,
+) -> IterativeSearch {
+    let threshold = options.word_threshold(params.k);
+    let mut pssm = Pssm::from_rows(query, rows);
+    let mut included: Vec<ProteinId> = Vec::new();
+    let mut rounds = Vec::new();
+    loop {
+        let hits = search_profile(db, indexes, &pssm, params, threshold);
+        let passing: Vec<&ProfileHit> = hits.iter().filter(|h| h.evalue <= options.inclusion_ethresh).collect();
+        let mut now: Vec<ProteinId> = passing.iter().map(|h| h.id).collect();
+        now.sort_unstable();
+        let new = now.iter().filter(|id| included.binary_search(id).is_err()).count();
+        rounds.push(Round { hits: hits.len(), included: now.len(), new });
+
+        let mut alignment = rows.to_vec();
+        alignment.extend(passing.iter().map(|h| h.alignment.row(query.len())));
+        pssm = Pssm::from_rows(query, &alignment);
+        let converged = now == included;
+        if converged || rounds.len() >= options.iterations.unwrap_or(1) {
+            return IterativeSearch { hits, rounds, pssm, converged };
+        }
+        included = now;
+    }
+}
@@ -0,0 +1,8 @@
+pub mod pipeline;
+pub mod chunked;
+pub mod query_indexed;
+pub mod translated;
+pub mod iterative;
+pub mod hmmsearch;
+pub mod sensitivity;
+pub mod rbh;
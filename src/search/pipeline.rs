@@ -0,0 +1,319 @@
+// One query against one (in-memory) database: the stages wired together.
+use crate::core::database::Database;
+use crate::core::residues::ResiduePolicy;
+use crate::index::kmer::{BuildOptions, KmerIndex, ProteinId, SeedIndex};
+use crate::index::compressed::CompressedKmerIndex;
+use crate::index::spaced::SpacedIndex;
+use crate::index::weighting::Bm25;
+use crate::filter::seed::{self, Candidate};
+use crate::filter::seg::SegOptions;
+use crate::align::{ungapped, smith_waterman};
+use crate::align::composition::{CompAdjust, Composition, PairScoring};
+use crate::align::swipe::{align_scores, QueryProfile};
+use crate::align::ungapped::{Scoring, ScoringScheme};
+use crate::search::sensitivity::Sensitivity;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, Debug)]
+pub enum SearchMode {
+    /// K-mer Voting
+    Basic,
+    /// Diagonal Filtering
+    Diagonal,
+    /// Spaced Seeds
+    Spaced,
+    /// Full Pipeline (Index -> Filter -> Ungapped -> Smith-Waterman)
+    Auto,
+    /// Smith-Waterman against every sequence, no index (true top-N, slow)
+    Exhaustive,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, Debug)]
+pub enum Ranking {
+    /// +1 per shared k-mer
+    Votes,
+    /// Inverse document frequency per shared k-mer
+    Idf,
+    /// IDF with BM25 target-length normalization
+    Bm25,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    pub mode: SearchMode,
+    pub k: usize,
+    pub top_n: usize,
+    pub x_drop: i32,
+    pub pattern: String,
+    pub max_pvalue: Option<f64>,
+    pub ranking: Ranking,
+    pub build: BuildOptions,
+    pub compressed: bool,
+    /// Applied to records streamed from FASTA; in-memory databases should be loaded with the same policy
+    pub residues: ResiduePolicy,
+    /// Fail streamed searches on records `DatabaseReport::check` rejects; in-memory
+    /// databases are checked when loaded
+    pub strict: bool,
+    /// Low-complexity masking; the database side must be applied before indexing (`SegOptions::mask_database`)
+    pub seg: SegOptions,
+    /// Scoring for ungapped extension and SW (auto mode)
+    pub scoring: ScoringScheme,
+    /// Per-target composition-based matrix adjustment (needs a matrix scheme)
+    pub comp_adjust: CompAdjust,
+    /// Frameshift penalty for translated searches (auto mode); None aligns each frame on its own
+    pub frameshift: Option<i32>,
+    /// K-mer hits a diagonal needs to become a candidate (without `max_pvalue`)
+    pub min_diagonal: usize,
+    /// Targets that get a windowed Smith-Waterman alignment (auto mode)
+    pub sw_candidates: usize,
+}
+
+/// The search subcommand's defaults: auto mode with the `Default` sensitivity preset.
+impl Default for SearchParams {
+    fn default() -> Self {
+        let mut params = Self {
+            mode: SearchMode::Auto,
+            k: 0,
+            top_n: 10,
+            x_drop: 0,
+            pattern: String::new(),
+            max_pvalue: None,
+            ranking: Ranking::Votes,
+            build: BuildOptions::default(),
+            compressed: false,
+            residues: ResiduePolicy::default(),
+            strict: false,
+            seg: SegOptions::default(),
+            scoring: ScoringScheme::default(),
+            comp_adjust: CompAdjust::default(),
+            frameshift: None,
+            min_diagonal: 0,
+            sw_candidates: 0,
+        };
+        Sensitivity::Default.apply(&mut params);
+        params
+    }
+}
+
+/// Diagonal seeding: `min_diagonal` hits per diagonal, or p-value filtered when requested.
+pub fn find_seeds<I: SeedIndex>(index: &I, q_seq: &[u8], max_pvalue: Option<f64>, min_diagonal: usize) -> Vec<Candidate> {
+    match max_pvalue {
+        Some(p) => seed::find_significant_candidate(index, q_seq, p),
+        None => seed::find_candidate(index, q_seq, min_diagonal),
+    }
+}
+
+/// `find_seeds` for precomputed (query position, encoded k-mer) seeds.
+pub fn find_word_seeds<I: SeedIndex>(
+    index: &I,
+    query_len: usize,
+    words: &[(usize, u64)],
+    max_pvalue: Option<f64>,
+    min_diagonal: usize,
+) -> Vec<Candidate> {
+    let words = words.iter().copied();
+    match max_pvalue {
+        Some(p) => seed::find_significant_candidate_words(index, query_len, words, p),
+        None => seed::find_candidate_words(index, query_len, words, min_diagonal),
+    }
+}
+
+/// Contiguous k-mer index in either postings layout.
+pub enum ContiguousIndex {
+    Raw(KmerIndex),
+    Compressed(CompressedKmerIndex),
+}
+
+impl ContiguousIndex {
+    pub fn search_basic(&self, q_seq: &[u8], n: usize) -> Vec<(ProteinId, u32)> {
+        match self {
+            ContiguousIndex::Raw(idx) => idx.search_basic(q_seq, n),
+            ContiguousIndex::Compressed(idx) => idx.search_basic(q_seq, n),
+        }
+    }
+    pub fn search_idf(&self, q_seq: &[u8], n: usize, length_norm: Option<Bm25>) -> Vec<(ProteinId, f32)> {
+        match self {
+            ContiguousIndex::Raw(idx) => idx.search_idf(q_seq, n, length_norm),
+            ContiguousIndex::Compressed(idx) => idx.search_idf(q_seq, n, length_norm),
+        }
+    }
+    pub fn find_seeds(&self, q_seq: &[u8], max_pvalue: Option<f64>, min_diagonal: usize) -> Vec<Candidate> {
+        match self {
+            ContiguousIndex::Raw(idx) => find_seeds(idx, q_seq, max_pvalue, min_diagonal),
+            ContiguousIndex::Compressed(idx) => find_seeds(idx, q_seq, max_pvalue, min_diagonal),
+        }
+    }
+    pub fn find_word_seeds(&self, query_len: usize, words: &[(usize, u64)], max_pvalue: Option<f64>, min_diagonal: usize) -> Vec<Candidate> {
+        match self {
+            ContiguousIndex::Raw(idx) => find_word_seeds(idx, query_len, words, max_pvalue, min_diagonal),
+            ContiguousIndex::Compressed(idx) => find_word_seeds(idx, query_len, words, max_pvalue, min_diagonal),
+        }
+    }
+}
+
+/// Everything built over one database for a given set of search parameters.
+pub struct SearchIndexes {
+    pub contiguous: ContiguousIndex,
+    pub spaced: Option<SpacedIndex>,
+}
+
+impl SearchIndexes {
+    pub fn build(db: &Database, params: &SearchParams) -> Self {
+        // Exhaustive mode never looks anything up, so its index stays empty
+        let empty = Database::new();
+        let db = if params.mode == SearchMode::Exhaustive { &empty } else { db };
+        let contiguous = if params.compressed {
+            ContiguousIndex::Compressed(CompressedKmerIndex::build_with(db, params.k, &params.build))
+        } else {
+            ContiguousIndex::Raw(KmerIndex::build_with(db, params.k, &params.build))
+        };
+        let spaced = if params.mode == SearchMode::Spaced {
+            Some(SpacedIndex::build(db, &params.pattern))
+        } else {
+            None
+        };
+        Self { contiguous, spaced }
+    }
+}
+
+/// A full-pipeline hit with its local alignment, coordinates 0-based and half-open.
+#[derive(Debug, Clone)]
+pub struct AlignedHit {
+    pub id: ProteinId,
+    pub score: i32,
+    pub q_start: usize,
+    pub q_end: usize,
+    pub t_start: usize,
+    pub t_end: usize,
+    /// Identical residue pairs, and alignment columns including gaps
+    pub identities: usize,
+    pub length: usize,
+}
+
+fn as_scores<S: Into<i64>>(hits: Vec<(ProteinId, S)>) -> Vec<(ProteinId, f32)> {
+    hits.into_iter().map(|(id, s)| (id, s.into() as f32)).collect()
+}
+
+/// Runs one query; returns (local protein id, score), best first, at most `top_n`.
+pub fn search_query(
+    db: &Database,
+    indexes: &SearchIndexes,
+    q_seq: &[u8],
+    params: &SearchParams,
+) -> Vec<(ProteinId, f32)> {
+    let n = params.top_n;
+    let index = &indexes.contiguous;
+    let q_seq: &[u8] = &params.seg.mask_query(q_seq);
+    let mut results = match params.mode {
+        SearchMode::Basic => match params.ranking {
+            Ranking::Votes => as_scores(index.search_basic(q_seq, n)),
+            Ranking::Idf => index.search_idf(q_seq, n, None),
+            Ranking::Bm25 => index.search_idf(q_seq, n, Some(Bm25::default())),
+        },
+        SearchMode::Diagonal => {
+            let cands = index.find_seeds(q_seq, params.max_pvalue, params.min_diagonal);
+            cands.into_iter().take(n).map(|c| (c.id, c.score as f32)).collect()
+        },
+        SearchMode::Spaced => {
+            let idx = indexes.spaced.as_ref().expect("spaced index is built in spaced mode");
+            match params.ranking {
+                Ranking::Votes => as_scores(idx.search_basic(q_seq, n)),
+                Ranking::Idf => idx.search_idf(q_seq, n, None),
+                Ranking::Bm25 => idx.search_idf(q_seq, n, Some(Bm25::default())),
+            }
+        },
+        SearchMode::Auto => align_auto(db, indexes, q_seq, params)
+            .into_iter()
+            .map(|h| (h.id, h.score as f32))
+            .collect(),
+        SearchMode::Exhaustive => as_scores(search_exhaustive(db, q_seq, params)),
+    };
+    results.truncate(n);
+    results
+}
+
+/// Smith-Waterman scores of `q_seq` against every target under `params.scoring`:
+/// the true top `top_n`, best first, ties to the lower id. Zero scores are left out.
+pub fn search_exhaustive(db: &Database, q_seq: &[u8], params: &SearchParams) -> Vec<(ProteinId, i32)> {
+    let profile = QueryProfile::new(q_seq, &params.scoring.scoring());
+    let targets: Vec<&[u8]> = (0..db.len()).map(|i| db.get(i).unwrap().1).collect();
+    let mut hits: Vec<(ProteinId, i32)> = align_scores(&profile, &targets)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, score)| score > 0)
+        .map(|(id, score)| (id as ProteinId, score))
+        .collect();
+    hits.sort_unstable_by_key(|&(id, score)| (std::cmp::Reverse(score), id));
+    hits.truncate(params.top_n);
+    hits
+}
+
+/// Auto mode with alignment coordinates: best first, at most `top_n`.
+pub fn search_query_aligned(
+    db: &Database,
+    indexes: &SearchIndexes,
+    q_seq: &[u8],
+    params: &SearchParams,
+) -> Vec<AlignedHit> {
+    let q_seq: &[u8] = &params.seg.mask_query(q_seq);
+    let mut hits = align_auto(db, indexes, q_seq, params);
+    hits.truncate(params.top_n);
+    hits
+}
+
+/// Seed, ungapped extension, then Smith-Waterman in a window around the best
+/// ungapped segment of each of the top `sw_candidates` targets. `q_seq` is already masked.
+fn align_auto(db: &Database, indexes: &SearchIndexes, q_seq: &[u8], params: &SearchParams) -> Vec<AlignedHit> {
+    let candidates = indexes.contiguous.find_seeds(q_seq, params.max_pvalue, params.min_diagonal);
+    let pair = PairScoring::new(params.scoring.scoring(), params.comp_adjust, Composition::of(q_seq));
+    // Keep every Smith-Waterman candidate even when fewer hits are reported
+    let mut ungapped_hits = ungapped::refine_ungapped_adjusted(
+        q_seq, &candidates, db,
+        &pair, params.x_drop, params.top_n.max(params.sw_candidates)
+    );
+    // Per-target matrices cannot share one query profile
+    if ungapped_hits.len() > params.sw_candidates && !pair.is_active() {
+        ungapped_hits = rank_gapped(q_seq, ungapped_hits, &params.scoring.scoring(), |(id, ext, _)| {
+            let (_, t_full) = db.get(*id as usize).unwrap();
+            smith_waterman::extract_window(t_full, (ext.t_start + ext.t_end) / 2, 60).0
+        });
+    }
+
+    // Smith-Waterman Refinement
+    let mut final_hits = Vec::new();
+    for (id, ext, scoring) in ungapped_hits.into_iter().take(params.sw_candidates) {
+        let (_, t_full) = db.get(id as usize).unwrap();
+        let q_center = (ext.q_start + ext.q_end) / 2;
+        let t_center = (ext.t_start + ext.t_end) / 2;
+
+        let (q_sub, q_offset) = smith_waterman::extract_window(q_seq, q_center, 60);
+        let (t_sub, t_offset) = smith_waterman::extract_window(t_full, t_center, 60);
+
+        let align = smith_waterman::align_scored(q_sub, t_sub, &scoring);
+        let (identities, length) = smith_waterman::identities(&align, q_sub, t_sub);
+        final_hits.push(AlignedHit {
+            id,
+            score: align.score,
+            q_start: q_offset + align.xstart,
+            q_end: q_offset + align.xend,
+            t_start: t_offset + align.ystart,
+            t_end: t_offset + align.yend,
+            identities,
+            length,
+        });
+    }
+
+    final_hits.sort_by_key(|h| std::cmp::Reverse(h.score));
+    final_hits
+}
+
+/// Orders a long list of ungapped hits by the Smith-Waterman score of the query against
+/// each target's window, scored many targets at a time, so the windowed alignments go to
+/// the best gapped hits rather than the best ungapped segments. Ties keep their order.
+/// `window` gives the target window of a hit.
+pub(crate) fn rank_gapped<T, F: Fn(&T) -> &[u8]>(q_seq: &[u8], hits: Vec<T>, scoring: &Scoring, window: F) -> Vec<T> {
+    let windows: Vec<&[u8]> = hits.iter().map(window).collect();
+    let scores = align_scores(&QueryProfile::new(q_seq, scoring), &windows);
+    let mut ranked: Vec<_> = hits.into_iter().zip(scores).collect();
+    ranked.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    ranked.into_iter().map(|(hit, _)| hit).collect()
+}
@@ -0,0 +1,246 @@
+// Query-side indexing (DIAMOND-style): index the query batch, then scan the
+// database once. Each target's k-mers are looked up in the query index, so the
+// database can be streamed straight from FASTA and never held in memory.
+//
+// The roles are swapped but the stages are the same as `pipeline::search_query`
+// (diagonal filter -> ungapped extension -> SW on the top hits), and ties are
+// broken by target id in both, so the two paths report identical hits.
+use std::borrow::Cow;
+use std::path::Path;
+use anyhow::{bail, Result};
+use rustc_hash::FxHashMap;
+use smallvec::SmallVec;
+use crate::core::alphabet::{encode_kmer, encode_kmer_wildcard};
+use crate::core::database::Database;
+use crate::core::fasta::FastaReader;
+use crate::core::residues::{count_mostly_lowercase, LOWERCASE_WARNING};
+use crate::core::validate::StreamCheck;
+use crate::index::kmer::{KmerIndex, SeedIndex};
+use crate::filter::seed::best_diagonal;
+use crate::align::{ungapped, smith_waterman};
+use crate::align::ungapped::Scoring;
+use crate::align::composition::{Composition, PairScoring};
+use crate::search::chunked::GlobalHit;
+use crate::search::pipeline::{rank_gapped, Ranking, SearchMode, SearchParams};
+
+const SW_RADIUS: usize = 60;
+
+// A retained hit; auto mode keeps the target window for the final SW pass
+struct PendingHit {
+    score: i64,
+    target_id: usize,
+    accession: String,
+    title: String,
+    q_center: usize,
+    t_window: Vec<u8>,
+    // Composition-adjusted scoring for this target; None for the query's base scoring
+    scoring: Option<Scoring>,
+}
+
+pub struct QueryIndexedSearch<'a> {
+    queries: Database, // after query-side SEG masking
+    index: KmerIndex,
+    params: &'a SearchParams,
+    scoring: Vec<PairScoring>, // per query, for composition-based adjustment
+    hits: Vec<Vec<PendingHit>>,
+    diagonals: FxHashMap<u32, SmallVec<[i32; 8]>>,
+}
+
+impl<'a> QueryIndexedSearch<'a> {
+    pub fn new(queries: &'a Database, params: &'a SearchParams) -> Result<Self> {
+        if matches!(params.mode, SearchMode::Spaced | SearchMode::Exhaustive) {
+            bail!("Query-side indexing supports basic, diagonal and auto modes");
+        }
+        if params.ranking != Ranking::Votes || params.max_pvalue.is_some() {
+            bail!("Query-side indexing has no database k-mer statistics; IDF ranking and --max-pvalue are unavailable");
+        }
+        if params.build.max_postings.is_some() || params.build.max_percentile.is_some() {
+            bail!("Query-side indexing has no database k-mer statistics; --max-postings and --mask-percentile are unavailable");
+        }
+        let mut masked = Database::new();
+        for i in 0..queries.len() {
+            let (_, seq) = queries.get(i).unwrap();
+            masked.push(queries.title(i).unwrap(), &params.seg.mask_query(seq));
+        }
+        Ok(Self {
+            index: KmerIndex::build(&masked, params.k),
+            params,
+            scoring: (0..masked.len())
+                .map(|i| PairScoring::new(params.scoring.scoring(), params.comp_adjust, Composition::of(masked.get(i).unwrap().1)))
+                .collect(),
+            hits: (0..queries.len()).map(|_| Vec::new()).collect(),
+            diagonals: FxHashMap::default(),
+            queries: masked,
+        })
+    }
+
+    /// Scans one database sequence against all queries.
+    pub fn scan_target(&mut self, target_id: usize, accession: &str, title: &str, target: &[u8]) {
+        let target: &[u8] = &self.params.seg.mask_target(target);
+        let k = self.index.k;
+        if target.len() < k {
+            return;
+        }
+        self.diagonals.clear();
+        for (t_pos, window) in target.windows(k).enumerate() {
+            let mut lookup = |encoded: u64| {
+                let diagonals = &mut self.diagonals;
+                self.index.for_each_posting(encoded, |qid, q_pos| {
+                    diagonals.entry(qid).or_default().push(t_pos as i32 - q_pos as i32);
+                });
+            };
+            // Expand X on the target side, as the database index would
+            if self.params.build.wildcard_x {
+                encode_kmer_wildcard(window, lookup);
+            } else if let Some(encoded) = encode_kmer(window) {
+                lookup(encoded);
+            }
+        }
+
+        let kept = self.kept();
+        let t_comp = Composition::of(target);
+        for (&qid, diagonals) in self.diagonals.iter_mut() {
+            let (_, q_seq) = self.queries.get(qid as usize).unwrap();
+            let mut pending = PendingHit {
+                score: 0,
+                target_id,
+                accession: String::new(),
+                title: String::new(),
+                q_center: 0,
+                t_window: Vec::new(),
+                scoring: None,
+            };
+            match self.params.mode {
+                SearchMode::Basic => {
+                    if q_seq.len() < k {
+                        continue;
+                    }
+                    pending.score = diagonals.len() as i64;
+                }
+                SearchMode::Diagonal | SearchMode::Auto => {
+                    if q_seq.len() <= k || diagonals.len() < self.params.min_diagonal {
+                        continue;
+                    }
+                    let (diag, count) = best_diagonal(diagonals);
+                    if count < self.params.min_diagonal {
+                        continue;
+                    }
+                    pending.score = count as i64;
+                    if self.params.mode == SearchMode::Auto {
+                        let scoring = self.scoring[qid as usize].for_composition(&t_comp);
+                        let Some(ext) = ungapped::extend_diagonal(
+                            q_seq, target, diag, &scoring, self.params.x_drop
+                        ) else {
+                            continue;
+                        };
+                        if let Cow::Owned(scoring) = scoring {
+                            pending.scoring = Some(scoring);
+                        }
+                        let t_center = (ext.t_start + ext.t_end) / 2;
+                        pending.score = ext.score as i64;
+                        pending.q_center = (ext.q_start + ext.q_end) / 2;
+                        pending.t_window = smith_waterman::extract_window(target, t_center, SW_RADIUS).0.to_vec();
+                    }
+                }
+                SearchMode::Spaced | SearchMode::Exhaustive => unreachable!(),
+            }
+            pending.accession = accession.to_string();
+            pending.title = title.to_string();
+            let list = &mut self.hits[qid as usize];
+            list.push(pending);
+            if list.len() >= 2 * kept.max(1) {
+                keep_top(list, kept);
+            }
+        }
+    }
+
+    pub fn scan_database(&mut self, db: &Database) {
+        for i in 0..db.len() {
+            let (accession, seq) = db.get(i).unwrap();
+            self.scan_target(i, accession, db.title(i).unwrap(), seq);
+        }
+    }
+
+    /// Hits kept per query before the final ranking: in auto mode, enough for every
+    /// Smith-Waterman candidate, as in `pipeline::search_query`.
+    fn kept(&self) -> usize {
+        match self.params.mode {
+            SearchMode::Auto => self.params.top_n.max(self.params.sw_candidates),
+            _ => self.params.top_n,
+        }
+    }
+
+    /// Final ranking per query, in query order.
+    pub fn finish(mut self) -> Vec<Vec<GlobalHit>> {
+        let kept = self.kept();
+        let mut results = Vec::with_capacity(self.hits.len());
+        for (qid, mut list) in std::mem::take(&mut self.hits).into_iter().enumerate() {
+            keep_top(&mut list, kept);
+            if self.params.mode == SearchMode::Auto {
+                // Smith-Waterman Refinement on the best ungapped hits
+                let (_, q_seq) = self.queries.get(qid).unwrap();
+                if list.len() > self.params.sw_candidates && !self.scoring[qid].is_active() {
+                    // The gapped ranking of `pipeline::search_query`
+                    list = rank_gapped(q_seq, list, &self.params.scoring.scoring(), |h| h.t_window.as_slice());
+                }
+                list.truncate(self.params.sw_candidates);
+                for hit in list.iter_mut() {
+                    let (q_sub, _) = smith_waterman::extract_window(q_seq, hit.q_center, SW_RADIUS);
+                    let scoring = hit.scoring.as_ref().unwrap_or(self.scoring[qid].base());
+                    let align = smith_waterman::align_scored(q_sub, &hit.t_window, scoring);
+                    hit.score = align.score as i64;
+                }
+                list.sort_by_key(|h| std::cmp::Reverse(h.score));
+                list.truncate(self.params.top_n);
+            }
+            results.push(list.into_iter().map(|h| GlobalHit {
+                id: h.target_id,
+                score: h.score as f32,
+                accession: h.accession,
+                title: h.title,
+            }).collect());
+        }
+        results
+    }
+}
+
+fn keep_top(list: &mut Vec<PendingHit>, top_n: usize) {
+    list.sort_unstable_by_key(|h| (std::cmp::Reverse(h.score), h.target_id));
+    list.truncate(top_n);
+}
+
+/// Query-indexed search over an in-memory database.
+pub fn search_database(queries: &Database, db: &Database, params: &SearchParams) -> Result<Vec<Vec<GlobalHit>>> {
+    let mut search = QueryIndexedSearch::new(queries, params)?;
+    search.scan_database(db);
+    Ok(search.finish())
+}
+
+/// Query-indexed search streaming targets from a FASTA file, one record at a time.
+pub fn search_fasta<P: AsRef<Path>>(queries: &Database, path: P, params: &SearchParams) -> Result<Vec<Vec<GlobalHit>>> {
+    let mut search = QueryIndexedSearch::new(queries, params)?;
+    let mut reader = FastaReader::open(path)?;
+    let residues = params.residues.mapper();
+    let mut record = Database::new();
+    let mut target_id = 0;
+    let mut check = params.strict.then(StreamCheck::default);
+    let mut lowercase = 0;
+    while let Some(header) = reader.read_record(&mut record.data)? {
+        residues.finish_record(&mut record, 0, &header);
+        if let Some(check) = &mut check {
+            check.check(&record, target_id)?;
+        }
+        lowercase += count_mostly_lowercase(&record);
+        // Split fragments become consecutive targets, matching `Database::load_from_fasta_with`
+        for i in 0..record.len() {
+            let (accession, seq) = record.get(i).unwrap();
+            search.scan_target(target_id, accession, record.title(i).unwrap(), seq);
+            target_id += 1;
+        }
+        record = Database::new();
+    }
+    if lowercase > 0 {
+        log::warn!("{} records are mostly lowercase: {}", lowercase, LOWERCASE_WARNING);
+    }
+    Ok(search.finish())
+}
@@ -0,0 +1,120 @@
+// Reciprocal best hits between two proteomes, the usual first cut at orthologs: every
+// protein of A is searched against B and every protein of B against A with the full
+// pipeline (auto mode), and pairs that are each other's best hit are kept.
+//
+// The pipeline aligns a window around each hit's best segment, which says little about
+// how much of two proteins align, and its window scores need not rank hits the way full
+// alignments do. So every hit is realigned over the full sequences, and the best hit is
+// the one with the highest full-alignment score (ties by id) among those that pass the
+// identity and coverage cutoffs: a protein whose top hit is a short fragment can still
+// pair through another one. Reported scores, identities and coverages are those of the
+// full alignments.
+// Each side's index is built once by the caller and serves the direction that
+// searches against it.
+use crate::align::smith_waterman;
+use crate::core::database::Database;
+use crate::index::kmer::ProteinId;
+use crate::search::pipeline::{search_query_aligned, AlignedHit, SearchIndexes, SearchParams};
+
+/// Cutoffs for the hits a best hit is chosen from.
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+#[command(about = None, long_about = None)]
+pub struct RbhOptions {
+    /// Minimum percent identity of a hit (identical pairs over alignment columns)
+    #[arg(long, default_value_t = 0.0)]
+    pub min_identity: f64,
+    /// Minimum percent of both the query and the subject covered by a hit
+    #[arg(long, default_value_t = 0.0)]
+    pub min_coverage: f64,
+}
+
+/// A pair of reciprocal best hits, with the full alignment of each direction.
+#[derive(Debug, Clone)]
+pub struct RbhPair {
+    pub a: usize,
+    pub b: usize,
+    /// A's protein as the query, `id` in B
+    pub ab: AlignedHit,
+    /// B's protein as the query, `id` in A
+    pub ba: AlignedHit,
+}
+
+/// Percent identity of a hit's alignment.
+pub fn identity(hit: &AlignedHit) -> f64 {
+    if hit.length == 0 {
+        return 0.0;
+    }
+    100.0 * hit.identities as f64 / hit.length as f64
+}
+
+/// Percent of the query and of the subject a hit covers.
+pub fn coverage(hit: &AlignedHit, query_len: usize, subject_len: usize) -> (f64, f64) {
+    let percent = |aligned: usize, len: usize| if len == 0 { 0.0 } else { 100.0 * aligned as f64 / len as f64 };
+    (percent(hit.q_end - hit.q_start, query_len), percent(hit.t_end - hit.t_start, subject_len))
+}
+
+/// Smith-Waterman alignment of the whole of `query` and of `db` entry `id`.
+fn realign(query: &[u8], db: &Database, id: ProteinId, params: &SearchParams) -> AlignedHit {
+    let (_, target) = db.get(id as usize).unwrap();
+    let align = smith_waterman::align_scored(query, target, &params.scoring.scoring());
+    let (identities, length) = smith_waterman::identities(&align, query, target);
+    AlignedHit {
+        id,
+        score: align.score,
+        q_start: align.xstart,
+        q_end: align.xend,
+        t_start: align.ystart,
+        t_end: align.yend,
+        identities,
+        length,
+    }
+}
+
+/// Best hit in `db` of each protein of `queries`: of the pipeline's hits whose full
+/// alignment passes the cutoffs, the one with the highest full-alignment score, then
+/// the lowest id.
+pub fn best_hits(
+    queries: &Database,
+    db: &Database,
+    indexes: &SearchIndexes,
+    params: &SearchParams,
+    options: &RbhOptions,
+) -> Vec<Option<AlignedHit>> {
+    (0..queries.len())
+        .map(|q| {
+            let (_, q_seq) = queries.get(q).unwrap();
+            let hits = search_query_aligned(db, indexes, q_seq, params);
+            let q_seq = params.seg.mask_query(q_seq);
+            hits.into_iter()
+                .map(|hit| realign(&q_seq, db, hit.id, params))
+                .filter(|hit| {
+                    let (q_cov, t_cov) = coverage(hit, q_seq.len(), db.get(hit.id as usize).unwrap().1.len());
+                    identity(hit) >= options.min_identity && q_cov.min(t_cov) >= options.min_coverage
+                })
+                .min_by_key(|hit| (std::cmp::Reverse(hit.score), hit.id))
+        })
+        .collect()
+}
+
+/// Pairs of proteins of `a` and `b` that are each other's best hit, in `a` order.
+/// `a_indexes` and `b_indexes` are built over `a` and `b` with `params`.
+pub fn reciprocal_best_hits(
+    a: &Database,
+    a_indexes: &SearchIndexes,
+    b: &Database,
+    b_indexes: &SearchIndexes,
+    params: &SearchParams,
+    options: &RbhOptions,
+) -> Vec<RbhPair> {
+    let a_to_b = best_hits(a, b, b_indexes, params, options);
+    let mut b_to_a = best_hits(b, a, a_indexes, params, options);
+    a_to_b.into_iter()
+        .enumerate()
+        .filter_map(|(a_id, ab)| {
+            let ab = ab?;
+            let b_id = ab.id as usize;
+            let ba = b_to_a[b_id].take_if(|ba| ba.id as usize == a_id)?;
+            Some(RbhPair { a: a_id, b: b_id, ab, ba })
+        })
+        .collect()
+}
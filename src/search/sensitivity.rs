@@ -0,0 +1,180 @@
+// Sensitivity presets, after DIAMOND's: each one sets the k-mer size of the index (and
+// the spaced seed of spaced mode, one weight above k), whether postings are compressed,
+// the hits a diagonal needs, the X-drop of ungapped extension and how many targets get a
+// Smith-Waterman alignment. Smaller k finds more remote homologs at the cost of longer
+// postings lists and more candidates to extend; at k = 3 the lists are dense enough that
+// compression pays for its decoding, and a single seed hit makes a candidate.
+//
+// Iterated search (DIAMOND's --iterate, MMseqs2's --sens-steps) runs every query at the
+// least sensitive level first and re-runs only the queries without a significant hit at
+// each next level. A hit is significant when its BLOSUM62 Karlin-Altschul E-value
+// (`profile::evalue`) is at or below the threshold. Each round builds its own index, and
+// only if some query is left for it.
+use crate::align::profile::evalue;
+use crate::core::database::Database;
+use crate::search::pipeline::{search_query_aligned, AlignedHit, SearchIndexes, SearchParams};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Sensitivity {
+    Fast,
+    Default,
+    Sensitive,
+    VerySensitive,
+}
+
+/// The settings a preset bundles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    pub k: usize,
+    /// Spaced seed pattern for `SearchMode::Spaced`
+    pub pattern: &'static str,
+    /// Delta/varint compressed postings
+    pub compressed: bool,
+    pub min_diagonal: usize,
+    pub x_drop: i32,
+    pub sw_candidates: usize,
+}
+
+impl Sensitivity {
+    /// From least to most sensitive
+    pub const LEVELS: [Sensitivity; 4] = [Self::Fast, Self::Default, Self::Sensitive, Self::VerySensitive];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fast => "fast",
+            Self::Default => "default",
+            Self::Sensitive => "sensitive",
+            Self::VerySensitive => "very-sensitive",
+        }
+    }
+
+    /// `Default` is the search subcommand's own defaults.
+    pub fn preset(self) -> Preset {
+        match self {
+            Self::Fast => Preset {
+                k: 6, pattern: "111010111", compressed: false, min_diagonal: 3, x_drop: 10, sw_candidates: 10,
+            },
+            Self::Default => Preset {
+                k: 5, pattern: "11010111", compressed: false, min_diagonal: 2, x_drop: 10, sw_candidates: 20,
+            },
+            Self::Sensitive => Preset {
+                k: 4, pattern: "1101011", compressed: false, min_diagonal: 2, x_drop: 20, sw_candidates: 50,
+            },
+            Self::VerySensitive => Preset {
+                k: 3, pattern: "11011", compressed: true, min_diagonal: 1, x_drop: 30, sw_candidates: 100,
+            },
+        }
+    }
+
+    /// Sets the preset's fields of `params`.
+    pub fn apply(self, params: &mut SearchParams) {
+        let preset = self.preset();
+        params.k = preset.k;
+        params.pattern = preset.pattern.to_string();
+        params.compressed = preset.compressed;
+        params.min_diagonal = preset.min_diagonal;
+        params.x_drop = preset.x_drop;
+        params.sw_candidates = preset.sw_candidates;
+    }
+}
+
+/// Presets and iterated search.
+#[derive(Debug, Clone, Copy, clap::Args)]
+#[command(about = None, long_about = None)]
+pub struct SensitivityOptions {
+    /// Preset: k = 6, 3 hits per diagonal, Smith-Waterman on the top 10
+    #[arg(long, group = "sensitivity")]
+    pub fast: bool,
+    /// Preset: k = 4, X-drop 20, Smith-Waterman on the top 50
+    #[arg(long, group = "sensitivity")]
+    pub sensitive: bool,
+    /// Preset: k = 3, compressed index, 1 hit per diagonal, X-drop 30, Smith-Waterman on the top 100
+    #[arg(long, group = "sensitivity")]
+    pub very_sensitive: bool,
+    /// Search at each level from --fast up to the preset, re-running only the queries without a significant hit
+    #[arg(long)]
+    pub iterate: bool,
+    /// E-value at or below which a hit is significant for --iterate
+    #[arg(long, default_value_t = 0.001, requires = "iterate")]
+    pub iterate_evalue: f64,
+}
+
+impl Default for SensitivityOptions {
+    fn default() -> Self {
+        Self { fast: false, sensitive: false, very_sensitive: false, iterate: false, iterate_evalue: 0.001 }
+    }
+}
+
+impl SensitivityOptions {
+    /// The chosen preset, `Default` without one.
+    pub fn level(&self) -> Sensitivity {
+        if self.fast {
+            Sensitivity::Fast
+        } else if self.sensitive {
+            Sensitivity::Sensitive
+        } else if self.very_sensitive {
+            Sensitivity::VerySensitive
+        } else {
+            Sensitivity::Default
+        }
+    }
+
+    /// The levels searched in turn: all up to the preset with `iterate`, else the preset.
+    pub fn levels(&self) -> Vec<Sensitivity> {
+        let level = self.level();
+        if self.iterate {
+            Sensitivity::LEVELS.into_iter().filter(|&l| l <= level).collect()
+        } else {
+            vec![level]
+        }
+    }
+}
+
+/// One query's result from an iterated search.
+#[derive(Debug, Clone)]
+pub struct IteratedHits {
+    /// Index of the round that produced the hits: the first one with a significant
+    /// hit, or the last one
+    pub round: usize,
+    pub significant: bool,
+    /// Best first, with their E-values
+    pub hits: Vec<(AlignedHit, f64)>,
+}
+
+/// Searches `queries` in auto mode with each round's parameters in turn, the first
+/// round with every query and later ones with the queries that have no hit at or below
+/// `max_evalue` yet. Results are in query order.
+pub fn search_iterated(
+    db: &Database,
+    queries: &[&[u8]],
+    rounds: &[(Sensitivity, SearchParams)],
+    max_evalue: f64,
+) -> Vec<IteratedHits> {
+    let db_residues = db.data.len();
+    let mut results: Vec<IteratedHits> = queries.iter()
+        .map(|_| IteratedHits { round: 0, significant: false, hits: Vec::new() })
+        .collect();
+    let mut pending: Vec<usize> = (0..queries.len()).collect();
+    for (round, (level, params)) in rounds.iter().enumerate() {
+        if pending.is_empty() {
+            break;
+        }
+        let indexes = SearchIndexes::build(db, params);
+        let searched = pending.len();
+        pending.retain(|&q| {
+            let hits: Vec<(AlignedHit, f64)> = search_query_aligned(db, &indexes, queries[q], params)
+                .into_iter()
+                .map(|hit| {
+                    let e = evalue(hit.score, queries[q].len(), db_residues);
+                    (hit, e)
+                })
+                .collect();
+            let significant = hits.first().is_some_and(|&(_, e)| e <= max_evalue);
+            results[q] = IteratedHits { round, significant, hits };
+            !significant
+        });
+        log::info!("Round {} ({}, k={}): {} of {} queries found a significant hit",
+                round + 1, level.name(), params.k, searched - pending.len(), searched);
+    }
+    results
+}
@@ -0,0 +1,269 @@
+// Translated searches.
+//   - blastx: a nucleotide query is translated in all six frames and each frame runs
+//     through the full pipeline against the protein database. A target hit in several
+//     frames is reported once, with its best-scoring frame.
+//   - tblastn: a protein query against a `TranslatedDatabase`. Each frame of each
+//     nucleotide record is a target of its own, so one contig can hit on both strands.
+//
+// With a frameshift penalty, the per-frame HSPs of a target that lie on the same strand
+// within a protein's length of each other are re-aligned together with
+// `align_frameshift`, so a read with indel errors gives one alignment across frames
+// instead of several pieces.
+use std::ops::Range;
+use crate::align::frameshift::{align_frameshift, FrameshiftAlignment};
+use crate::core::database::Database;
+use crate::core::residues::ResidueMapper;
+use crate::core::translate::{forward_position, forward_range, six_frames, Frame, GeneticCode, TranslatedDatabase};
+use crate::search::pipeline::{search_query, search_query_aligned, AlignedHit, SearchIndexes, SearchMode, SearchParams};
+
+/// Extra strand context around stitched HSPs, in codons (the Smith-Waterman window radius)
+const STITCH_MARGIN: usize = 60;
+/// Most protein residues a stitched window reaches beyond its HSPs on each side
+const MAX_STITCH_PAD: usize = 1000;
+
+/// A frame change inside an alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frameshift {
+    /// 1-based forward-strand coordinate of the first base of the codon after the shift
+    pub position: usize,
+    /// +1 if a base was skipped, -1 if one was read twice
+    pub shift: i8,
+}
+
+/// A hit from one frame of a nucleotide query.
+#[derive(Debug, Clone)]
+pub struct TranslatedHit {
+    pub id: usize,
+    pub score: f32,
+    /// Frame the alignment starts in
+    pub frame: Frame,
+    /// Aligned query nucleotides, 1-based inclusive; start > end on reverse frames
+    pub q_start: usize,
+    pub q_end: usize,
+    /// Aligned target residues, 1-based inclusive
+    pub t_start: usize,
+    pub t_end: usize,
+    /// Empty unless frameshift alignment is on
+    pub frameshifts: Vec<Frameshift>,
+    pub accession: String,
+    pub title: String,
+}
+
+/// A nucleotide query ready to search: its six translations under the residue policy.
+/// Stops are left in place ('*' breaks seeds) so coordinates map straight back to the DNA.
+pub struct TranslatedQuery {
+    pub dna_len: usize,
+    pub frames: Vec<(Frame, Vec<u8>)>,
+}
+
+impl TranslatedQuery {
+    pub fn new(dna: &[u8], code: &GeneticCode, residues: &ResidueMapper) -> Self {
+        let mut frames = six_frames(dna, code);
+        for (_, protein) in frames.iter_mut() {
+            residues.normalize(protein);
+        }
+        Self { dna_len: dna.len(), frames }
+    }
+}
+
+/// One per-frame HSP in strand offsets and protein residues, 0-based half-open.
+struct Hsp {
+    strand: Range<usize>,
+    protein: Range<usize>,
+}
+
+/// Groups HSPs on one strand that are less than a protein length apart and aligns each
+/// group with frameshifts. The window reaches to the ends of the protein (up to
+/// `MAX_STITCH_PAD`) and the matching stretch of strand: per-frame HSPs are often short,
+/// and after two opposite shifts a gene is back in its first frame, so its last piece
+/// may have no HSP of its own.
+fn stitch(
+    frames: [&[u8]; 3],
+    strand_len: usize,
+    mut hsps: Vec<Hsp>,
+    protein: &[u8],
+    params: &SearchParams,
+    penalty: i32,
+) -> Vec<FrameshiftAlignment> {
+    let scoring = params.scoring.scoring();
+    let max_gap = 3 * protein.len();
+    hsps.sort_by_key(|h| h.strand.start);
+    let mut groups: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    for hsp in hsps {
+        match groups.last_mut() {
+            Some((strand, prot)) if hsp.strand.start <= strand.end + max_gap => {
+                strand.end = strand.end.max(hsp.strand.end);
+                prot.start = prot.start.min(hsp.protein.start);
+                prot.end = prot.end.max(hsp.protein.end);
+            }
+            _ => groups.push((hsp.strand, hsp.protein)),
+        }
+    }
+    groups.into_iter()
+        .map(|(strand, prot)| {
+            let left = prot.start.min(MAX_STITCH_PAD);
+            let right = (protein.len() - prot.end).min(MAX_STITCH_PAD);
+            let strand = strand.start.saturating_sub(3 * (left + STITCH_MARGIN))
+                ..(strand.end + 3 * (right + STITCH_MARGIN)).min(strand_len);
+            let prot = prot.start - left..prot.end + right;
+            align_frameshift(frames, strand, protein, prot, &scoring, penalty)
+        })
+        .collect()
+}
+
+fn frameshifts(aln: &FrameshiftAlignment, strand_len: usize, reverse: bool) -> Vec<Frameshift> {
+    aln.frameshifts.iter()
+        .map(|&(offset, shift)| Frameshift { position: forward_position(offset, strand_len, reverse), shift })
+        .collect()
+}
+
+/// Searches every frame of `query`; best target first, at most `top_n`.
+pub fn search_translated(
+    db: &Database,
+    indexes: &SearchIndexes,
+    query: &TranslatedQuery,
+    params: &SearchParams,
+) -> Vec<TranslatedHit> {
+    let mut per_frame: Vec<(Frame, AlignedHit)> = Vec::new();
+    for (frame, protein) in &query.frames {
+        for hit in search_query_aligned(db, indexes, protein, params) {
+            per_frame.push((*frame, hit));
+        }
+    }
+    let hit = |id: usize, score: i32, frame: Frame, q: (usize, usize), t: Range<usize>, frameshifts| TranslatedHit {
+        id,
+        score: score as f32,
+        frame,
+        q_start: q.0,
+        q_end: q.1,
+        t_start: t.start + 1,
+        t_end: t.end,
+        frameshifts,
+        accession: db.id(id).to_string(),
+        title: db.title(id).unwrap().to_string(),
+    };
+
+    let mut hits: Vec<TranslatedHit> = match params.frameshift {
+        None => per_frame.into_iter()
+            .map(|(frame, h)| {
+                let q = frame.nucleotide_range(h.q_start, h.q_end, query.dna_len);
+                hit(h.id as usize, h.score, frame, q, h.t_start..h.t_end, Vec::new())
+            })
+            .collect(),
+        Some(penalty) => {
+            // Frames as the pipeline saw them (masked), by strand and codon offset
+            let masked: Vec<Vec<u8>> = query.frames.iter().map(|(_, p)| params.seg.mask_query(p).into_owned()).collect();
+            per_frame.sort_by_key(|(frame, h)| (h.id, frame.is_reverse()));
+            let mut hits = Vec::new();
+            for group in per_frame.chunk_by(|a, b| (a.1.id, a.0.is_reverse()) == (b.1.id, b.0.is_reverse())) {
+                let id = group[0].1.id as usize;
+                let reverse = group[0].0.is_reverse();
+                let hsps = group.iter()
+                    .map(|(frame, h)| {
+                        let (from, to) = frame.strand_range(h.q_start, h.q_end);
+                        Hsp { strand: from..to, protein: h.t_start..h.t_end }
+                    })
+                    .collect();
+                let first = if reverse { 3 } else { 0 };
+                let frames = [0, 1, 2].map(|i| masked[first + i].as_slice());
+                let (_, target) = db.get(id).unwrap();
+                for aln in stitch(frames, query.dna_len, hsps, target, params, penalty) {
+                    let q = forward_range(aln.strand_start, aln.strand_end, query.dna_len, reverse);
+                    let frame = Frame::at_offset(aln.strand_start, reverse);
+                    let shifts = frameshifts(&aln, query.dna_len, reverse);
+                    hits.push(hit(id, aln.score, frame, q, aln.protein_start..aln.protein_end, shifts));
+                }
+            }
+            hits
+        }
+    };
+    // Best frame per target; ties go to the lower target id, then the earlier frame
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    let mut seen = vec![false; db.len()];
+    hits.retain(|h| !std::mem::replace(&mut seen[h.id], true));
+    hits.truncate(params.top_n);
+    hits
+}
+
+/// A hit on one frame of a nucleotide database record.
+#[derive(Debug, Clone)]
+pub struct GenomicHit {
+    /// Index of the nucleotide record
+    pub record: usize,
+    /// Frame the alignment starts in
+    pub frame: Frame,
+    pub score: f32,
+    /// Aligned query residues, 1-based inclusive (auto mode only)
+    pub query_range: Option<(usize, usize)>,
+    /// Aligned subject nucleotides, 1-based inclusive, start > end on the minus strand
+    /// (auto mode only)
+    pub subject_range: Option<(usize, usize)>,
+    /// Empty unless frameshift alignment is on
+    pub frameshifts: Vec<Frameshift>,
+    pub accession: String,
+    pub title: String,
+}
+
+/// Searches a protein query against a translated nucleotide database; best first, at
+/// most `top_n`. `indexes` are built over `db.proteins`. Alignment coordinates are
+/// only known in auto mode; the other modes report the record and frame.
+pub fn search_translated_db(
+    db: &TranslatedDatabase,
+    indexes: &SearchIndexes,
+    q_seq: &[u8],
+    params: &SearchParams,
+) -> Vec<GenomicHit> {
+    let hit = |id: usize, frame: Frame, score: f32, ranges: Option<((usize, usize), (usize, usize))>, frameshifts| GenomicHit {
+        record: db.origins[id].record,
+        frame,
+        score,
+        query_range: ranges.map(|r| r.0),
+        subject_range: ranges.map(|r| r.1),
+        frameshifts,
+        accession: db.proteins.id(id).to_string(),
+        title: db.proteins.title(id).unwrap().to_string(),
+    };
+    if params.mode != SearchMode::Auto {
+        return search_query(&db.proteins, indexes, q_seq, params)
+            .into_iter()
+            .map(|(id, score)| hit(id as usize, db.origins[id as usize].frame, score, None, Vec::new()))
+            .collect();
+    }
+    let aligned = search_query_aligned(&db.proteins, indexes, q_seq, params);
+    let Some(penalty) = params.frameshift else {
+        return aligned.into_iter()
+            .map(|h| {
+                let id = h.id as usize;
+                let subject = db.nucleotide_range(id, h.t_start, h.t_end);
+                hit(id, db.origins[id].frame, h.score as f32, Some(((h.q_start + 1, h.q_end), subject)), Vec::new())
+            })
+            .collect();
+    };
+
+    let q_seq = params.seg.mask_query(q_seq);
+    let mut aligned: Vec<(usize, AlignedHit)> = aligned.into_iter().map(|h| (h.id as usize, h)).collect();
+    let strand_of = |id: usize| (db.origins[id].record, db.origins[id].frame.is_reverse());
+    aligned.sort_by_key(|(id, _)| strand_of(*id));
+    let mut hits = Vec::new();
+    for group in aligned.chunk_by(|a, b| strand_of(a.0) == strand_of(b.0)) {
+        let (record, reverse) = strand_of(group[0].0);
+        let hsps = group.iter()
+            .map(|(id, h)| {
+                let (from, to) = db.origins[*id].frame.strand_range(h.t_start, h.t_end);
+                Hsp { strand: from..to, protein: h.q_start..h.q_end }
+            })
+            .collect();
+        let len = db.lengths[record];
+        for aln in stitch(db.strand_frames(record, reverse), len, hsps, &q_seq, params, penalty) {
+            let frame = Frame::at_offset(aln.strand_start, reverse);
+            // Report against the translated record of the frame the alignment starts in
+            let id = 6 * record + Frame::ALL.iter().position(|&f| f == frame).unwrap();
+            let subject = forward_range(aln.strand_start, aln.strand_end, len, reverse);
+            let query = (aln.protein_start + 1, aln.protein_end);
+            hits.push(hit(id, frame, aln.score as f32, Some((query, subject)), frameshifts(&aln, len, reverse)));
+        }
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.record.cmp(&b.record)).then(a.frame.cmp(&b.frame)));
+    hits.truncate(params.top_n);
+    hits
+}
@@ -0,0 +1,112 @@
+use pro_search::core::database::Database;
+use pro_search::search::chunked::{chunk_bytes, resident_bytes, search_chunked};
+use pro_search::search::pipeline::{search_query, ContiguousIndex, SearchIndexes, SearchMode, SearchParams};
+
+mod common;
+use common::Rng;
+
+/// 400 random proteins, every tenth a mutated copy of one of the queries.
+fn write_db(rng: &mut Rng, queries: &[(String, Vec<u8>)]) -> std::path::PathBuf {
+    let mut fasta = String::new();
+    for i in 0..400 {
+        let seq = if i % 10 == 3 {
+            rng.mutate(&queries[(i / 10) % queries.len()].1, 0.2)
+        } else {
+            let len = 60 + rng.below(300);
+            rng.sequence(len)
+        };
+        fasta.push_str(&format!(">P{:03} protein {}\n{}\n", i, i, String::from_utf8(seq).unwrap()));
+    }
+    let path = std::env::temp_dir().join(format!("pro_search_chunked_{}.fa", std::process::id()));
+    std::fs::write(&path, fasta).unwrap();
+    path
+}
+
+#[test]
+fn chunked_search_matches_in_memory_search() {
+    let mut rng = Rng(0xC4_0C);
+    let queries: Vec<(String, Vec<u8>)> = (0..4).map(|i| (format!("Q{}", i), rng.sequence(150))).collect();
+    let path = write_db(&mut rng, &queries);
+    let db = Database::load_from_fasta(&path).unwrap();
+
+    for mode in [SearchMode::Basic, SearchMode::Exhaustive, SearchMode::Auto] {
+        let params = SearchParams { mode, top_n: 15, ..Default::default() };
+        let indexes = SearchIndexes::build(&db, &params);
+
+        // A budget for about a quarter of the database, so it is read in several chunks
+        let chunk = chunk_bytes(&params, db.data.len() / 4, db.len() / 4, db.headers.len() / 4);
+        let budget = chunk + resident_bytes(&queries, &params);
+        assert!(chunk_bytes(&params, db.data.len(), db.len(), db.headers.len()) > 2 * chunk);
+        let chunked = search_chunked(&path, &queries, &params, budget).unwrap();
+
+        for ((_, q_seq), hits) in queries.iter().zip(&chunked) {
+            let mut expected: Vec<(usize, f32)> = search_query(&db, &indexes, q_seq, &params)
+                .into_iter()
+                .map(|(id, score)| (id as usize, score))
+                .collect();
+            let found: Vec<(usize, f32)> = hits.iter().map(|h| (h.id, h.score)).collect();
+            if mode == SearchMode::Auto {
+                // Each chunk aligns its own top candidates, so only the ten homologs must
+                // agree; merged hits break score ties by ID
+                expected.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                assert_eq!(found[..10], expected[..10]);
+            } else {
+                assert_eq!(found, expected, "{:?}", mode);
+            }
+            // Global IDs point at the same records as in the loaded database
+            for hit in hits {
+                assert_eq!(hit.accession, db.id(hit.id));
+                assert_eq!(Some(hit.title.as_str()), db.title(hit.id));
+            }
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn chunk_estimate_covers_the_built_index() {
+    let mut rng = Rng(0xE57);
+    let mut db = Database::new();
+    for i in 0..300 {
+        let len = 50 + rng.below(400);
+        db.push(&format!("P{}", i), &rng.sequence(len));
+    }
+    for compressed in [false, true] {
+        let params = SearchParams { mode: SearchMode::Basic, compressed, ..Default::default() };
+        let indexes = SearchIndexes::build(&db, &params);
+        let estimate = chunk_bytes(&params, db.data.len(), db.len(), db.headers.len());
+        let index_bytes = match &indexes.contiguous {
+            ContiguousIndex::Raw(index) => index.memory_usage(),
+            ContiguousIndex::Compressed(index) => index.memory_usage(),
+        };
+        assert!(index_bytes + db.data.len() < estimate, "{} vs {}", index_bytes, estimate);
+    }
+}
+
+#[test]
+fn budgets_that_cannot_be_kept_are_errors() {
+    let mut rng = Rng(0xB0D6);
+    let queries: Vec<(String, Vec<u8>)> = vec![("Q".to_string(), rng.sequence(100))];
+    let mut fasta = Vec::new();
+    for (i, len) in [100, 120, 5000, 90].into_iter().enumerate() {
+        fasta.extend_from_slice(format!(">P{}\n", i).as_bytes());
+        fasta.extend_from_slice(&rng.sequence(len));
+        fasta.push(b'\n');
+    }
+    let path = common::write_temp("chunked", "budget.fa", &fasta);
+    let params = SearchParams { mode: SearchMode::Basic, ..Default::default() };
+    let resident = resident_bytes(&queries, &params);
+
+    // Less than the queries, their hits and the read buffer
+    let err = search_chunked(&path, &queries, &params, resident / 2).unwrap_err();
+    assert!(format!("{:#}", err).contains("does not cover the queries"), "{:#}", err);
+
+    // Room for the short records, but not for P2 on its own
+    let budget = resident + chunk_bytes(&params, 1000, 4, 64);
+    assert!(chunk_bytes(&params, 5000, 1, 4) > budget - resident);
+    let err = search_chunked(&path, &queries, &params, budget).unwrap_err();
+    assert!(format!("{:#}", err).contains("Record 3 (P2, 5000 residues) does not fit"), "{:#}", err);
+
+    assert!(search_chunked(&path, &queries, &params, resident + chunk_bytes(&params, 6000, 4, 64)).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
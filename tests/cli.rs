@@ -0,0 +1,29 @@
+use std::process::Command;
+
+// clap only checks argument definitions (duplicate ids across flattened option groups,
+// etc.) when a command is built, so parse every subcommand's help once.
+#[test]
+fn every_subcommand_builds() {
+    for sub in ["search", "fetch", "stats", "rbh", "hmmsearch"] {
+        let out = Command::new(env!("CARGO_BIN_EXE_pro_search"))
+            .args([sub, "--help"])
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{} --help: {}", sub, String::from_utf8_lossy(&out.stderr));
+    }
+}
+
+// Doc comments on flattened option structs must not become a subcommand's about text
+#[test]
+fn option_groups_leave_the_about_text_alone() {
+    let out = Command::new(env!("CARGO_BIN_EXE_pro_search")).arg("--help").output().unwrap();
+    let help = String::from_utf8_lossy(&out.stdout);
+    for group in ["Presets and iterated search", "How profile rounds", "SEG is applied", "Cutoffs for the hits", "Reporting thresholds"] {
+        assert!(!help.contains(group), "{}", group);
+    }
+    for sub in ["search", "rbh", "hmmsearch"] {
+        let out = Command::new(env!("CARGO_BIN_EXE_pro_search")).args([sub, "--help"]).output().unwrap();
+        let help = String::from_utf8_lossy(&out.stdout);
+        assert!(!help.contains("Presets and iterated search") && !help.contains("Cutoffs for the hits"), "{}", sub);
+    }
+}
@@ -0,0 +1,65 @@
+// Helpers shared by the integration tests. Each test binary uses its own subset.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use pro_search::align::ungapped::ScoringScheme;
+use pro_search::search::pipeline::SearchParams;
+
+pub const ALPHABET: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+
+/// Xorshift64: deterministic random proteins without a dependency.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+    pub fn residue(&mut self) -> u8 {
+        ALPHABET[self.below(20)]
+    }
+    pub fn sequence(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.residue()).collect()
+    }
+    /// `seq` with each residue replaced by a random one with probability `rate`
+    pub fn mutate(&mut self, seq: &[u8], rate: f64) -> Vec<u8> {
+        seq.iter()
+            .map(|&aa| if (self.next() % 1000) as f64 / 1000.0 < rate { self.residue() } else { aa })
+            .collect()
+    }
+}
+
+/// `len` residues of `alphabet` from an xorshift stream seeded with `seed`.
+pub fn pseudo_random(len: usize, seed: u64, alphabet: &[u8]) -> Vec<u8> {
+    let mut rng = Rng(seed);
+    (0..len).map(|_| alphabet[rng.below(alphabet.len())]).collect()
+}
+
+/// One standard-code codon per residue
+pub fn back_translate(protein: &[u8]) -> Vec<u8> {
+    const CODONS: [&[u8; 3]; 20] = [
+        b"GCT", b"TGT", b"GAT", b"GAA", b"TTT", b"GGT", b"CAT", b"ATT", b"AAA", b"CTG",
+        b"ATG", b"AAT", b"CCG", b"CAG", b"CGT", b"AGC", b"ACC", b"GTG", b"TGG", b"TAT",
+    ];
+    protein.iter()
+        .flat_map(|aa| CODONS[ALPHABET.iter().position(|a| a == aa).unwrap()].iter().copied())
+        .collect()
+}
+
+/// Writes `contents` to a temp file named after the test binary's `prefix`, the
+/// process and `name`, so parallel test binaries do not collide.
+pub fn write_temp(prefix: &str, name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pro_search_{}_{}_{}", prefix, std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// BLOSUM62 search parameters with k-mer length `k`, keeping the top 50 hits.
+pub fn params(k: usize) -> SearchParams {
+    SearchParams { k, top_n: 50, scoring: ScoringScheme::Blosum62, ..Default::default() }
+}
@@ -0,0 +1,88 @@
+use pro_search::align::composition::{adjust_matrix, standard_lambda, CompAdjust, Composition, PairScoring};
+use pro_search::align::matrix::{ScoreMatrix, BACKGROUND};
+use pro_search::align::smith_waterman::align_scored;
+use pro_search::align::ungapped::{refine_ungapped, refine_ungapped_adjusted, Scoring};
+use pro_search::core::database::Database;
+use pro_search::filter::seed::Candidate;
+
+const ORDINARY: &[u8] = b"TQLPSGSELSLYDIAPVTPGVAVDLSHIPTAVKIKGFSGEDATPALEGADVVLISAGVARKPGMDRSDLFNVNAGIVKNLVQQVAKTCPKACIGIITNPVNTTVAIAAEVLKKAGVYDKNKLFGVTTLDIIRSNTFVAEL";
+
+#[test]
+fn blosum62_lambda_matches_published_value() {
+    let lambda = standard_lambda(&ScoreMatrix::blosum62()).unwrap();
+    assert!((lambda - 0.3176).abs() < 0.003, "lambda = {}", lambda);
+}
+
+#[test]
+fn background_composition_leaves_matrix_nearly_unchanged() {
+    let matrix = ScoreMatrix::blosum62();
+    let lambda = standard_lambda(&matrix).unwrap();
+    let sum: f64 = BACKGROUND.iter().sum();
+    let bg = BACKGROUND.map(|p| p / sum);
+    let adjusted = adjust_matrix(&matrix, lambda, &bg, &bg);
+    for i in 0..20 {
+        for j in 0..20 {
+            assert!((adjusted.get(i, j) - matrix.get(i, j)).abs() <= 1, "{} {}", i, j);
+        }
+    }
+}
+
+#[test]
+fn shared_bias_no_longer_inflates_scores() {
+    // Q/E-rich query and target that share nothing but their composition: every other
+    // residue comes from an unrelated ordinary sequence
+    let bias = |pattern: &[u8], skip: usize| -> Vec<u8> {
+        pattern.iter().cycle().zip(ORDINARY.iter().skip(skip).cycle())
+            .flat_map(|(&b, &o)| [b, o])
+            .take(150)
+            .collect()
+    };
+    let biased_q = bias(b"QEQQKEQQEQ", 0);
+    let biased_t = bias(b"EQQEQQQEQE", 71);
+    let base = Scoring::blosum62();
+    let raw = align_scored(&biased_q, &biased_t, &base).score;
+
+    for mode in [CompAdjust::Scale, CompAdjust::Conditional, CompAdjust::Full] {
+        let pair = PairScoring::new(base.clone(), mode, Composition::of(&biased_q));
+        assert!(pair.is_active());
+        let adjusted = align_scored(&biased_q, &biased_t, &pair.for_target(&biased_t)).score;
+        assert!(adjusted < raw * 3 / 4, "{:?}: {} vs {}", mode, adjusted, raw);
+    }
+
+    // An ordinary pair keeps most of its score: a real match is not penalized
+    let pair = PairScoring::new(base.clone(), CompAdjust::Conditional, Composition::of(ORDINARY));
+    let raw = align_scored(ORDINARY, ORDINARY, &base).score;
+    let adjusted = align_scored(ORDINARY, ORDINARY, &pair.for_target(ORDINARY)).score;
+    assert!(adjusted > raw * 3 / 4, "{} vs {}", adjusted, raw);
+
+    let off = PairScoring::new(base, CompAdjust::None, Composition::of(ORDINARY));
+    assert!(!off.is_active());
+}
+
+#[test]
+fn refined_hits_carry_their_adjusted_scoring() {
+    let mut db = Database::new();
+    db.push("ordinary", ORDINARY);
+    db.push("biased", &ORDINARY.iter().map(|&b| if b == b'A' { b'Q' } else { b }).collect::<Vec<u8>>());
+    db.push("short", &ORDINARY[..40]);
+    let candidates: Vec<Candidate> = (0..db.len() as u32)
+        .map(|id| Candidate { id, score: 5, best_diagonal: 0, pvalue: None })
+        .collect();
+    let base = Scoring::blosum62();
+
+    let pair = PairScoring::new(base.clone(), CompAdjust::Full, Composition::of(ORDINARY));
+    let hits = refine_ungapped_adjusted(ORDINARY, &candidates, &db, &pair, 20, 2);
+    assert_eq!(hits.len(), 2);
+    for (id, _, scoring) in &hits {
+        let expected = pair.for_target(db.get(*id as usize).unwrap().1);
+        assert_eq!(scoring.matrix.as_ref().unwrap().scores, expected.matrix.as_ref().unwrap().scores);
+    }
+
+    // Without adjustment, the hits and their order are those of `refine_ungapped`
+    let off = PairScoring::new(base.clone(), CompAdjust::None, Composition::of(ORDINARY));
+    let adjusted: Vec<(u32, i32)> = refine_ungapped_adjusted(ORDINARY, &candidates, &db, &off, 20, 2)
+        .into_iter().map(|(id, ext, _)| (id, ext.score)).collect();
+    let plain: Vec<(u32, i32)> = refine_ungapped(ORDINARY, &candidates, &db, &base, 20, 2)
+        .into_iter().map(|(id, ext)| (id, ext.score)).collect();
+    assert_eq!(adjusted, plain);
+}
@@ -0,0 +1,112 @@
+use pro_search::core::database::Database;
+use pro_search::filter::seed::{find_candidate, Candidate};
+use pro_search::index::compressed::{read_varint, write_varint, CompressedKmerIndex};
+use pro_search::index::kmer::{BuildOptions, KmerIndex, SeedIndex};
+use pro_search::index::weighting::Bm25;
+use pro_search::search::pipeline::{search_query, SearchIndexes, SearchParams};
+
+mod common;
+use common::Rng;
+
+#[test]
+fn varints_roundtrip() {
+    let values = [0, 1, 127, 128, 16_383, 16_384, u32::MAX as u64, u64::MAX];
+    let lens = [1, 1, 1, 2, 2, 3, 5, 10];
+    let mut bytes = Vec::new();
+    for (&value, &len) in values.iter().zip(&lens) {
+        let before = bytes.len();
+        write_varint(&mut bytes, value);
+        assert_eq!(bytes.len() - before, len, "{}", value);
+    }
+    let mut cursor = 0;
+    for &value in &values {
+        assert_eq!(read_varint(&bytes, &mut cursor), value);
+    }
+    assert_eq!(cursor, bytes.len());
+}
+
+/// Random proteins with in-protein repeats, an X-bearing one and one long enough for
+/// multi-byte positions.
+fn mixed_db() -> Database {
+    let mut rng = Rng(0xC0DE);
+    let mut db = Database::new();
+    for i in 0..300 {
+        let len = 80 + rng.below(200);
+        let mut seq = rng.sequence(len);
+        if i % 5 == 0 {
+            let repeat = seq[10..30].to_vec();
+            seq.extend_from_slice(&repeat);
+            seq.extend_from_slice(&repeat);
+        }
+        db.push(&format!("P{:03}", i), &seq);
+    }
+    let mut with_x = rng.sequence(100);
+    with_x[50] = b'X';
+    db.push("WITH_X", &with_x);
+    db.push("LONG", &rng.sequence(40_000));
+    db
+}
+
+#[test]
+fn built_postings_decode_to_the_raw_index() {
+    let db = mixed_db();
+    for options in [
+        BuildOptions::default(),
+        BuildOptions { wildcard_x: true, ..Default::default() },
+        BuildOptions { max_postings: Some(3), ..Default::default() },
+    ] {
+        let raw = KmerIndex::build_with(&db, 3, &options);
+        let compressed = CompressedKmerIndex::build_with(&db, 3, &options);
+        assert_eq!(compressed.keys.len(), raw.map.len());
+        assert_eq!(compressed.total_postings, raw.total_postings);
+        for (kmer, postings) in &raw.map {
+            assert_eq!(compressed.query(*kmer).unwrap(), postings.to_vec());
+        }
+        // Sized in one pass and encoded in another, the blob is byte for byte what
+        // compressing the built raw index gives
+        let from_raw = CompressedKmerIndex::from_index(&raw);
+        assert_eq!(compressed.keys, from_raw.keys);
+        assert_eq!(compressed.offsets, from_raw.offsets);
+        assert_eq!(compressed.blob, from_raw.blob);
+        assert!(compressed.memory_usage() < raw.memory_usage());
+
+        let stats = (raw.stats(), compressed.stats());
+        assert_eq!(stats.0.histogram, stats.1.histogram);
+        assert_eq!(stats.0.stopped_kmers, stats.1.stopped_kmers);
+    }
+}
+
+#[test]
+fn compressed_and_raw_searches_agree() {
+    let db = mixed_db();
+    let raw = KmerIndex::build(&db, 4);
+    let compressed = CompressedKmerIndex::build(&db, 4);
+    assert_eq!(compressed.seq_lens(), raw.seq_lens());
+    let params = SearchParams { k: 4, ..Default::default() };
+    let compressed_params = SearchParams { compressed: true, ..params.clone() };
+    let indexes = SearchIndexes::build(&db, &params);
+    let compressed_indexes = SearchIndexes::build(&db, &compressed_params);
+
+    let mut rng = Rng(0x5EA);
+    for i in 0..20 {
+        let (_, seq) = db.get(rng.below(db.len() - 1)).unwrap();
+        let start = rng.below(seq.len() / 2);
+        let query = rng.mutate(&seq[start..(start + 60).min(seq.len())], 0.2);
+
+        assert_eq!(compressed.search_basic(&query, 10), raw.search_basic(&query, 10), "query {}", i);
+        for norm in [None, Some(Bm25::default())] {
+            assert_eq!(compressed.search_idf(&query, 10, norm), raw.search_idf(&query, 10, norm));
+        }
+        let key = |c: &Candidate| (c.id, c.score, c.best_diagonal);
+        assert_eq!(
+            find_candidate(&compressed, &query, 2).iter().map(key).collect::<Vec<_>>(),
+            find_candidate(&raw, &query, 2).iter().map(key).collect::<Vec<_>>(),
+        );
+
+        // Through the whole pipeline
+        assert_eq!(
+            search_query(&db, &compressed_indexes, &query, &compressed_params),
+            search_query(&db, &indexes, &query, &params),
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::{Read, Write};
+use pro_search::core::database::Database;
+use pro_search::core::fasta::{open_input, Compression};
+
+mod common;
+use common::write_temp;
+
+const FASTA: &[u8] = b">sp|P1|A first protein\nMKVLLA\nGHW\n>P2\nMTTQISFV\n";
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn bzip2(data: &[u8]) -> Vec<u8> {
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn xz(data: &[u8]) -> Vec<u8> {
+    let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Loads `contents` from a file named `name` and checks it reads back as `FASTA`.
+fn assert_loads(name: &str, contents: &[u8], expected: Compression) {
+    assert_eq!(Compression::sniff(contents), expected, "{}", name);
+    let path = write_temp("compression", name, contents);
+    let mut text = Vec::new();
+    open_input(&path).unwrap().read_to_end(&mut text).unwrap();
+    let db = Database::load_from_fasta(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(text, FASTA, "{}", name);
+    assert_eq!(db.len(), 2);
+    assert_eq!(db.title(0), Some("sp|P1|A first protein"));
+    assert_eq!(db.data, b"MKVLLAGHWMTTQISFV");
+}
+
+// Each format is stored under a plain or a wrong extension: only the magic bytes count.
+#[test]
+fn gzip_is_detected_by_magic_bytes() {
+    let compressed = gzip(FASTA);
+    assert_loads("gzip.fa", &compressed, Compression::Gzip);
+    assert_loads("gzip.zst", &compressed, Compression::Gzip);
+
+    // Concatenated members (bgzip, `cat a.gz b.gz`) read as one stream
+    let (head, tail) = FASTA.split_at(23);
+    assert_loads("members.fa", &[gzip(head), gzip(tail)].concat(), Compression::Gzip);
+}
+
+#[test]
+fn zstd_is_detected_by_magic_bytes() {
+    let compressed = zstd::encode_all(FASTA, 3).unwrap();
+    assert_loads("zstd.fa", &compressed, Compression::Zstd);
+    assert_loads("zstd.gz", &compressed, Compression::Zstd);
+}
+
+#[test]
+fn bzip2_is_detected_by_magic_bytes() {
+    let compressed = bzip2(FASTA);
+    assert_loads("bzip2.fa", &compressed, Compression::Bzip2);
+    assert_loads("bzip2.xz", &compressed, Compression::Bzip2);
+}
+
+#[test]
+fn xz_is_detected_by_magic_bytes() {
+    let compressed = xz(FASTA);
+    assert_loads("xz.fa", &compressed, Compression::Xz);
+    assert_loads("xz.bz2", &compressed, Compression::Xz);
+}
+
+#[test]
+fn plain_text_with_a_compressed_extension_reads_as_is() {
+    assert_loads("plain.gz", FASTA, Compression::None);
+    assert_loads("plain.zst", FASTA, Compression::None);
+}
@@ -0,0 +1,159 @@
+use pro_search::align::smith_waterman::align_scored;
+use pro_search::align::swipe::{align_scores, align_scores_scalar, QueryProfile};
+use pro_search::align::ungapped::{Scoring, ScoringScheme};
+use pro_search::bench::query_gen::{sample_queries, GroundTruth, QueryConfig};
+use pro_search::core::database::Database;
+use pro_search::search::pipeline::{search_query, SearchIndexes, SearchMode, SearchParams};
+
+mod common;
+use common::Rng;
+
+fn params(scoring: ScoringScheme, top_n: usize) -> SearchParams {
+    SearchParams { mode: SearchMode::Exhaustive, top_n, scoring, ..Default::default() }
+}
+
+#[test]
+fn vector_scores_match_smith_waterman() {
+    let mut rng = Rng(0x51_11CE);
+    let ancestor = rng.sequence(150);
+    let query = {
+        let mut q = rng.mutate(&ancestor, 0.3);
+        q[10] = b'X';
+        q[20..30].make_ascii_lowercase();
+        q
+    };
+    // More targets than lanes, of very different lengths, some related, one empty
+    let mut targets: Vec<Vec<u8>> = (0..60)
+        .map(|i| match i % 4 {
+            0 => rng.mutate(&ancestor[(i % 50)..], 0.4),
+            1 => {
+                let len = 1 + (rng.next() % 400) as usize;
+                rng.sequence(len)
+            }
+            2 => [rng.sequence(30), rng.mutate(&ancestor, 0.5), b"*XB".to_vec()].concat(),
+            _ => rng.sequence(5),
+        })
+        .collect();
+    targets[7].clear();
+    targets[8].make_ascii_lowercase();
+    let targets: Vec<&[u8]> = targets.iter().map(|t| t.as_slice()).collect();
+
+    for scoring in [Scoring::default(), Scoring::blosum62()] {
+        let profile = QueryProfile::new(&query, &scoring);
+        let scores = align_scores(&profile, &targets);
+        assert_eq!(scores, align_scores_scalar(&profile, &targets));
+        for (score, target) in scores.iter().zip(&targets) {
+            assert_eq!(*score, align_scored(&query, target, &scoring).score);
+        }
+        assert_eq!(scores[7], 0);
+    }
+    assert_eq!(align_scores(&QueryProfile::new(b"", &Scoring::blosum62()), &targets), vec![0; targets.len()]);
+}
+
+#[test]
+fn saturated_lanes_are_rescored() {
+    let mut rng = Rng(0xB16);
+    let seq = rng.sequence(400);
+    let scoring = Scoring { match_score: 100, ..Scoring::default() };
+    let profile = QueryProfile::new(&seq, &scoring);
+    let other = rng.sequence(50);
+    let scores = align_scores(&profile, &[&seq, &other, &seq[..100]]);
+    assert_eq!(scores[0], 40_000);
+    assert_eq!(scores[1], align_scored(&seq, &other, &scoring).score);
+    assert_eq!(scores[2], 10_000);
+}
+
+#[test]
+fn byte_lanes_fall_back_at_their_limit() {
+    let mut rng = Rng(0xB7E);
+    let seq = rng.sequence(300);
+    // Identity scoring has a bias of 1, so byte lanes hold scores up to 253; interleave
+    // the prefixes around that with short random targets so the lanes get refilled
+    let noise: Vec<Vec<u8>> = (0..40).map(|_| rng.sequence(20)).collect();
+    let mut targets: Vec<&[u8]> = Vec::new();
+    for (len, other) in (245..265).zip(noise.chunks(2)) {
+        targets.extend([&seq[..len], other[0].as_slice(), other[1].as_slice()]);
+    }
+    for scoring in [Scoring::default(), Scoring { mismatch_score: -300, ..Scoring::default() }] {
+        let profile = QueryProfile::new(&seq, &scoring);
+        let scores = align_scores(&profile, &targets);
+        assert_eq!(scores, align_scores_scalar(&profile, &targets));
+        let prefixes: Vec<i32> = scores.iter().step_by(3).copied().collect();
+        assert_eq!(prefixes, (245..265).collect::<Vec<i32>>());
+    }
+}
+
+#[test]
+fn exhaustive_mode_returns_the_true_top_n() {
+    let mut rng = Rng(0xE4A);
+    let ancestor = rng.sequence(120);
+    let mut db = Database::new();
+    for i in 0..200 {
+        let len = 50 + (rng.next() % 250) as usize;
+        db.push(&format!("R{:03} unrelated", i), &rng.sequence(len));
+    }
+    for i in 0..10 {
+        db.push(&format!("F{} family", i), &rng.mutate(&ancestor, 0.2 + 0.05 * i as f64));
+    }
+    let query = rng.mutate(&ancestor, 0.3);
+    let params = params(ScoringScheme::Blosum62, 15);
+    let indexes = SearchIndexes::build(&db, &params);
+    let hits = search_query(&db, &indexes, &query, &params);
+
+    let scoring = Scoring::blosum62();
+    let mut expected: Vec<(u32, f32)> = (0..db.len())
+        .map(|i| (i as u32, align_scored(&query, db.get(i).unwrap().1, &scoring).score as f32))
+        .collect();
+    expected.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    expected.truncate(15);
+    assert_eq!(hits, expected);
+    assert!(hits.iter().take(10).all(|&(id, _)| db.id(id as usize).starts_with('F')));
+}
+
+#[test]
+fn auto_mode_aligns_the_best_gapped_hits() {
+    let mut rng = Rng(0x6A9);
+    let query = rng.sequence(240);
+    let mut db = Database::new();
+    // Decoys share the query's first 40 residues and nothing else; all 40 outscore the
+    // homolog in ungapped extension
+    for i in 0..40 {
+        db.push(&format!("D{:02} decoy", i), &[&query[..40], &rng.sequence(120)[..]].concat());
+    }
+    // The homolog has an indel every 12 residues, so its ungapped segments are short
+    let mut homolog = Vec::new();
+    for (i, chunk) in rng.mutate(&query, 0.05).chunks(12).enumerate() {
+        homolog.extend_from_slice(chunk);
+        if i % 2 == 0 {
+            homolog.push(rng.residue());
+        } else {
+            homolog.pop();
+        }
+    }
+    db.push("HOMOLOG", &homolog);
+
+    let params = SearchParams { mode: SearchMode::Auto, ..params(ScoringScheme::Blosum62, 100) };
+    let indexes = SearchIndexes::build(&db, &params);
+    let hits = search_query(&db, &indexes, &query, &params);
+    assert_eq!(hits[0].0 as usize, db.len() - 1, "{:?}", hits);
+}
+
+#[test]
+fn bench_ground_truth_is_the_best_alignment() {
+    let mut rng = Rng(0x7207);
+    let mut db = Database::new();
+    let base = rng.sequence(200);
+    for i in 0..30 {
+        // Near-copies of one sequence, so the best hit is often not the source
+        db.push(&format!("P{}", i), &rng.mutate(&base, 0.1));
+    }
+    let config = QueryConfig { length: 60, sub_rate: 0.3, indel_rate: 0.0, truth: GroundTruth::Exhaustive };
+    let queries = sample_queries(&db, 20, &config);
+    let scoring = Scoring::blosum62();
+    let score = |q: &[u8], id: u32| align_scored(q, db.get(id as usize).unwrap().1, &scoring).score;
+    for q in &queries {
+        let best = (0..db.len() as u32).map(|id| score(&q.sequence, id)).max().unwrap();
+        assert_eq!(score(&q.sequence, q.truth), best);
+        assert!(q.truth == q.original_pid || score(&q.sequence, q.original_pid) < best);
+    }
+}
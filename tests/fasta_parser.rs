@@ -0,0 +1,180 @@
+use std::io::{BufRead, Cursor, Read};
+use pro_search::core::database::Database;
+use pro_search::core::fasta::FastaReader;
+
+mod common;
+use common::{write_temp, Rng};
+
+fn records(db: &Database) -> Vec<(String, Vec<u8>)> {
+    (0..db.len())
+        .map(|i| (db.title(i).unwrap().to_string(), db.get(i).unwrap().1.to_vec()))
+        .collect()
+}
+
+#[test]
+fn parses_crlf_comments_empty_records_and_numbered_lines() {
+    let input = b"; file comment\r\n\
+        >sp|P1|A first protein\r\n\
+        MKV LLA\r\n\
+        \r\n\
+        ; inline comment\r\n\
+        GHW\r\n\
+        >empty\r\n\
+        >P3 numbered\n\
+        1 MKTAY 6 IAKQR\n\
+        \t11 QISFV\t\n";
+    let path = write_temp("parser", "parser", input);
+    let db = Database::load_from_fasta(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(records(&db), vec![
+        ("sp|P1|A first protein".to_string(), b"MKVLLAGHW".to_vec()),
+        ("empty".to_string(), Vec::new()),
+        ("P3 numbered".to_string(), b"MKTAYIAKQRQISFV".to_vec()),
+    ]);
+    assert_eq!((0..db.len()).map(|i| db.id(i)).collect::<Vec<_>>(), vec!["sp|P1|A", "empty", "P3"]);
+}
+
+#[test]
+fn line_endings_do_not_change_contents() {
+    let lf = b">a x\nMKV\nLLA\n>b\nGHW\n".to_vec();
+    let crlf: Vec<u8> = String::from_utf8(lf.clone()).unwrap().replace('\n', "\r\n").into_bytes();
+    let lf_path = write_temp("parser", "lf", &lf);
+    let crlf_path = write_temp("parser", "crlf", &crlf);
+    let a = Database::load_from_fasta(&lf_path).unwrap();
+    let b = Database::load_from_fasta(&crlf_path).unwrap();
+    std::fs::remove_file(&lf_path).unwrap();
+    std::fs::remove_file(&crlf_path).unwrap();
+    assert_eq!(records(&a), records(&b));
+    assert_eq!(a.data, b"MKVLLAGHW");
+}
+
+#[test]
+fn reports_error_location() {
+    let path = write_temp("parser", "bad_byte", b">a\nMKV\nLL\x01A\n");
+    let err = Database::load_from_fasta(&path).err().unwrap().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.ends_with(":3:3: unexpected byte 0x01 in sequence"), "{}", err);
+
+    let path = write_temp("parser", "no_header", b"\nMKV\nLL\n");
+    let err = Database::load_from_fasta(&path).err().unwrap().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.ends_with(":2:1: sequence data without a '>' header"), "{}", err);
+}
+
+/// The loader the byte-level parser replaced: `BufRead::lines`, each line trimmed,
+/// residue lines appended verbatim, residues before the first header kept as a prefix
+/// of the first record.
+fn line_based_records(input: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut records: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut prefix = Vec::new();
+    for line in input.lines() {
+        let line = line.unwrap();
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match (line.strip_prefix('>'), records.last_mut()) {
+            (Some(header), _) => records.push((header.to_string(), Vec::new())),
+            (None, Some(record)) => record.1.extend_from_slice(line.as_bytes()),
+            (None, None) => prefix.extend_from_slice(line.as_bytes()),
+        }
+    }
+    if let Some(first) = records.first_mut() {
+        first.1.splice(0..0, prefix);
+    }
+    records
+}
+
+/// Reads `input` one few bytes at a time, so lines straddle buffer refills.
+struct Trickle(Cursor<Vec<u8>>);
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(7);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+fn reader_records(mut reader: FastaReader) -> Vec<(String, Vec<u8>)> {
+    let mut records = Vec::new();
+    let mut seq = Vec::new();
+    while let Some(header) = reader.read_record(&mut seq).unwrap() {
+        records.push((header, std::mem::take(&mut seq)));
+    }
+    records
+}
+
+#[test]
+fn matches_the_line_based_loader() {
+    let mut rng = Rng(0xFA57A);
+    let mut inputs: Vec<Vec<u8>> = vec![
+        b">a x\nMKV\nLLA\n>b\nGHW".to_vec(),
+        b"MKV\r\nLL\r\n>first\r\nGHW\r\n\r\n>  spaced  header \t\r\n  mkvX*-BZ \t\n>empty\n".to_vec(),
+        ">sp|P1|ÄÖ Ünïcode header\nMKV\n\n\n>\nLL\n".as_bytes().to_vec(),
+        b"".to_vec(),
+        b"\n\n".to_vec(),
+    ];
+    // Random files: wrapped at varying widths, LF or CRLF, with blank lines and empty records
+    for _ in 0..20 {
+        let mut fasta = Vec::new();
+        for i in 0..1 + rng.below(30) {
+            fasta.extend_from_slice(format!(">P{} description {}", i, rng.below(1000)).as_bytes());
+            let len = rng.below(400);
+            let seq = rng.sequence(len);
+            let width = 1 + rng.below(120);
+            let eol: &[u8] = if rng.below(2) == 0 { b"\n" } else { b"\r\n" };
+            fasta.extend_from_slice(eol);
+            for line in seq.chunks(width) {
+                fasta.extend_from_slice(line);
+                fasta.extend_from_slice(eol);
+                if rng.below(10) == 0 {
+                    fasta.extend_from_slice(eol);
+                }
+            }
+        }
+        inputs.push(fasta);
+    }
+    // One line longer than the reader's 1 MB buffer
+    let long = rng.sequence(3 << 20);
+    inputs.push([b">long\n".as_slice(), &long, b"\n>tail\nMKV\n"].concat());
+
+    for input in &inputs {
+        let expected = line_based_records(input);
+        let path = write_temp("parser", "reference", input);
+        let db = Database::load_from_fasta(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records(&db), expected);
+
+        let trickled = FastaReader::from_reader(Box::new(Trickle(Cursor::new(input.clone()))), "trickle");
+        assert_eq!(reader_records(trickled), expected);
+    }
+}
+
+// The line-based loader copied these lines into the sequence verbatim: numbering,
+// spacing and comments became residues. The byte-level parser drops them.
+#[test]
+fn differs_from_the_line_based_loader_only_on_malformed_lines() {
+    let cases: [(&[u8], &[u8], &[u8]); 3] = [
+        // GenBank-style numbering: old kept digits and spaces
+        (b">a\n1 MKTAY 6 IAKQR\n", b"1 MKTAY 6 IAKQR", b"MKTAYIAKQR"),
+        // `;` comment lines: old appended the comment text
+        (b">a\nMKV\n; note\nLL\n", b"MKV; noteLL", b"MKVLL"),
+        // Inner tab: old kept it
+        (b">a\nMK\tV\n", b"MK\tV", b"MKV"),
+    ];
+    for (input, old, new) in cases {
+        assert_eq!(line_based_records(input)[0].1, old);
+        let reader = FastaReader::from_reader(Box::new(Cursor::new(input.to_vec())), "case");
+        assert_eq!(reader_records(reader)[0].1, new);
+    }
+
+    // Control bytes were stored as residues, and a file with residues but no header
+    // loaded them into no record. Both are now errors with a location.
+    let mut reader = FastaReader::from_reader(Box::new(Cursor::new(b">a\nMK\x07V\n".to_vec())), "bell");
+    let err = reader.read_record(&mut Vec::new()).err().unwrap().to_string();
+    assert_eq!(err, "bell:2:3: unexpected byte 0x07 in sequence");
+    assert!(line_based_records(b"MKV\n").is_empty());
+    let mut reader = FastaReader::from_reader(Box::new(Cursor::new(b"MKV\n".to_vec())), "bare");
+    assert!(reader.read_record(&mut Vec::new()).is_err());
+}
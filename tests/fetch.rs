@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+mod common;
+use common::{pseudo_random, write_temp, ALPHABET};
+
+fn run(db: &Path, args: &[&str]) -> Output {
+    let out = Command::new(env!("CARGO_BIN_EXE_pro_search"))
+        .args(["-d", db.to_str().unwrap()])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    out
+}
+
+fn stdout(out: Output) -> String {
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn test_db(name: &str) -> (PathBuf, Vec<u8>) {
+    let long = pseudo_random(130, 0xFE7C, ALPHABET);
+    let mut fasta = b">P1 kinase one\n".to_vec();
+    fasta.extend_from_slice(&long[..70]);
+    fasta.push(b'\n');
+    fasta.extend_from_slice(&long[70..]);
+    fasta.extend_from_slice(b"\n>P2\nGGGGSSSSGGGG\n>P3 other\nWWWWCCCCHHHH\n");
+    (write_temp("fetch", name, &fasta), long)
+}
+
+#[test]
+fn fetch_by_id_index_range_and_residues() {
+    let (db, long) = test_db("extract");
+
+    let by_id = stdout(run(&db, &["fetch", "--id", "P3,P1"]));
+    let mut expected = String::from(">P3 other\nWWWWCCCCHHHH\n>P1 kinase one\n");
+    for line in long.chunks(60) {
+        expected.push_str(std::str::from_utf8(line).unwrap());
+        expected.push('\n');
+    }
+    assert_eq!(by_id, expected);
+
+    let by_range = stdout(run(&db, &["fetch", "--index-range", "1-2"]));
+    assert_eq!(by_range, ">P2\nGGGGSSSSGGGG\n>P3 other\nWWWWCCCCHHHH\n");
+
+    // Headerless entries get no trailing space; ranges are clipped to the sequence
+    let sliced = stdout(run(&db, &["fetch", "--id", "P2", "--id", "P1", "--residues", "2-5"]));
+    let p1 = std::str::from_utf8(&long[1..5]).unwrap();
+    assert_eq!(sliced, format!(">P2:2-5\nGGGS\n>P1:2-5 kinase one\n{}\n", p1));
+    let clipped = stdout(run(&db, &["fetch", "--id", "P3", "--residues", "9-100"]));
+    assert_eq!(clipped, ">P3:9-12 other\nHHHH\n");
+
+    let id_file = write_temp("fetch", "ids", b"P2\n\n  NOPE \nP3\n");
+    let out = run(&db, &["fetch", "--id-file", id_file.to_str().unwrap()]);
+    assert_eq!(stdout(out.clone()), ">P2\nGGGGSSSSGGGG\n>P3 other\nWWWWCCCCHHHH\n");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("ID not found in database: NOPE"));
+
+    let missing = Command::new(env!("CARGO_BIN_EXE_pro_search"))
+        .args(["-d", db.to_str().unwrap(), "fetch", "--id", "NOPE"])
+        .output()
+        .unwrap();
+    assert!(!missing.status.success());
+
+    std::fs::remove_file(&id_file).unwrap();
+    std::fs::remove_file(&db).unwrap();
+}
+
+#[test]
+fn query_id_searches_database_entries() {
+    let (db, long) = test_db("query_id");
+    let out = run(&db, &["search", "--query-id", "P1", "--query-id", "NOPE"]);
+    let text = stdout(out.clone());
+    assert!(text.contains(&format!("Query: P1 (Length: {})", long.len())), "{}", text);
+    assert!(!text.contains("Query: NOPE"));
+    let hit = text.lines().find(|l| l.contains("1. [Score:")).unwrap();
+    assert!(hit.ends_with("] P1"), "{}", text);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Query ID not found in database: NOPE"));
+
+    // Same hits as passing the sequence itself
+    let direct = stdout(run(&db, &["search", "--query", std::str::from_utf8(&long).unwrap()]));
+    let hits = |s: &str| s.lines().filter(|l| l.contains("[Score:")).map(String::from).collect::<Vec<_>>();
+    assert_eq!(hits(&text), hits(&direct));
+    std::fs::remove_file(&db).unwrap();
+}
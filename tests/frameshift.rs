@@ -0,0 +1,111 @@
+use pro_search::align::frameshift::align_frameshift;
+use pro_search::align::smith_waterman::align_scored;
+use pro_search::align::ungapped::{Scoring, ScoringScheme};
+use pro_search::core::database::Database;
+use pro_search::core::residues::ResiduePolicy;
+use pro_search::core::translate::{reverse_complement, six_frames, GeneticCode, TranslatedDatabase};
+use pro_search::search::pipeline::{SearchIndexes, SearchParams};
+use pro_search::search::translated::{search_translated, search_translated_db, TranslatedQuery};
+
+mod common;
+use common::{back_translate, pseudo_random, ALPHABET};
+
+/// A 150-residue gene with one inserted base after codon 50 and one deleted base in
+/// codon 100, as a long read might have it; returns (gene, protein).
+fn gene_with_indels(seed: u64) -> (Vec<u8>, Vec<u8>) {
+    let protein = pseudo_random(150, seed, ALPHABET);
+    let mut gene = back_translate(&protein);
+    gene.remove(301);
+    gene.insert(150, b'G');
+    (gene, protein)
+}
+
+fn params(frameshift: Option<i32>) -> SearchParams {
+    SearchParams { k: 4, top_n: 5, scoring: ScoringScheme::Blosum62, frameshift, ..Default::default() }
+}
+
+#[test]
+fn three_frame_alignment_crosses_both_indels() {
+    let (gene, protein) = gene_with_indels(0x5EED);
+    let frames = six_frames(&gene, &GeneticCode::standard());
+    let forward = [frames[0].1.as_slice(), frames[1].1.as_slice(), frames[2].1.as_slice()];
+    let scoring = Scoring::blosum62();
+
+    let aln = align_frameshift(forward, 0..gene.len(), &protein, 0..protein.len(), &scoring, 15);
+    assert_eq!((aln.strand_start, aln.strand_end), (0, gene.len()));
+    assert_eq!((aln.protein_start, aln.protein_end), (0, protein.len()));
+    assert_eq!(aln.frameshifts.len(), 2, "{:?}", aln.frameshifts);
+    let (insertion, deletion) = (aln.frameshifts[0], aln.frameshifts[1]);
+    assert_eq!(insertion.1, 1);
+    assert!(insertion.0.abs_diff(151) <= 3, "{:?}", insertion);
+    assert_eq!(deletion.1, -1);
+    assert!(deletion.0.abs_diff(300) <= 3, "{:?}", deletion);
+
+    // Better than the best single frame, which covers at most one of the three pieces
+    let single = forward.iter().map(|f| align_scored(f, &protein, &scoring).score).max().unwrap();
+    assert!(aln.score > single + 100, "{} vs {}", aln.score, single);
+    // Without indels it is plain Smith-Waterman in frame +1
+    let clean = back_translate(&protein);
+    let frames = six_frames(&clean, &GeneticCode::standard());
+    let forward = [frames[0].1.as_slice(), frames[1].1.as_slice(), frames[2].1.as_slice()];
+    let aln = align_frameshift(forward, 0..clean.len(), &protein, 0..protein.len(), &scoring, 15);
+    assert!(aln.frameshifts.is_empty());
+    assert_eq!(aln.score, align_scored(forward[0], &protein, &scoring).score);
+}
+
+#[test]
+fn blastx_stitches_a_reverse_strand_read() {
+    let mut db = Database::new();
+    let (gene, protein) = gene_with_indels(0xF00D);
+    for i in 0..30u64 {
+        db.push(&format!("P{}", i), &pseudo_random(200, 0xBEEF + i, ALPHABET));
+    }
+    db.push("TARGET frameshifted gene", &protein);
+    let mut read = pseudo_random(40, 5, b"ACGT");
+    read.extend_from_slice(&gene);
+    read.extend(pseudo_random(40, 6, b"ACGT"));
+    let read = reverse_complement(&read);
+    let query = TranslatedQuery::new(&read, &GeneticCode::standard(), &ResiduePolicy::default().mapper());
+
+    let plain = params(None);
+    let indexes = SearchIndexes::build(&db, &plain);
+    let split = &search_translated(&db, &indexes, &query, &plain)[0];
+    assert_eq!(split.accession, "TARGET");
+    assert!(split.frameshifts.is_empty());
+
+    let stitched = &search_translated(&db, &indexes, &query, &params(Some(15)))[0];
+    assert_eq!(stitched.accession, "TARGET");
+    assert!(stitched.score > split.score + 100.0, "{} vs {}", stitched.score, split.score);
+    assert_eq!((stitched.t_start, stitched.t_end), (1, 150));
+    // The gene is at 41..=490 of the read, reversed
+    assert_eq!((stitched.q_start, stitched.q_end), (490, 41));
+    assert_eq!(stitched.frameshifts.len(), 2);
+    assert!(stitched.frameshifts.iter().all(|f| (41..=490).contains(&f.position)));
+    assert!(stitched.frameshifts[0].position > stitched.frameshifts[1].position);
+}
+
+#[test]
+fn tblastn_stitches_a_frameshifted_gene() {
+    let (gene, protein) = gene_with_indels(0xCAFE);
+    let mut contig = pseudo_random(500, 8, b"ACGT");
+    contig.extend_from_slice(&gene);
+    contig.extend(pseudo_random(300, 9, b"ACGT"));
+    let mut db = TranslatedDatabase::new(GeneticCode::standard());
+    db.push("noise", &pseudo_random(1200, 10, b"ACGT"), None);
+    db.push("contig_7 assembly", &contig, None);
+
+    let indexes = SearchIndexes::build(&db.proteins, &params(None));
+    let plain = search_translated_db(&db, &indexes, &protein, &params(None));
+    // Three pieces in three frames of the same record
+    let pieces = plain.iter().filter(|h| h.record == 1).count();
+    assert!(pieces >= 2, "{:?}", plain);
+
+    let stitched = search_translated_db(&db, &indexes, &protein, &params(Some(15)));
+    let best = &stitched[0];
+    assert_eq!((best.record, best.accession.as_str()), (1, "contig_7"));
+    assert_eq!(stitched.iter().filter(|h| h.record == 1).count(), 1);
+    assert_eq!(best.query_range, Some((1, 150)));
+    assert_eq!(best.subject_range, Some((501, 500 + gene.len())));
+    assert_eq!(best.frameshifts.len(), 2);
+    assert!(best.score > plain[0].score + 100.0);
+}
@@ -0,0 +1,60 @@
+use std::process::Command;
+use pro_search::core::database::Database;
+
+mod common;
+use common::write_temp;
+
+#[test]
+fn full_headers_split_into_id_and_description() {
+    let mut db = Database::new();
+    db.push("sp|Q8WZ42|TITIN_HUMAN Titin OS=Homo sapiens OX=9606 GN=TTN", b"MTTQ");
+    db.push("P2", b"MKV");
+    db.push("  P3 \t spaced   description ", b"MKV");
+    db.push("", b"MKV");
+    db.push("P2 second copy", b"GHW");
+
+    assert_eq!(db.len(), 5);
+    assert_eq!(db.id(0), "sp|Q8WZ42|TITIN_HUMAN");
+    assert_eq!(db.title(0), Some("sp|Q8WZ42|TITIN_HUMAN Titin OS=Homo sapiens OX=9606 GN=TTN"));
+    assert_eq!(db.description(0), Some("Titin OS=Homo sapiens OX=9606 GN=TTN"));
+
+    assert_eq!((db.id(1), db.title(1), db.description(1)), ("P2", Some("P2"), Some("")));
+    assert_eq!(db.id(2), "P3");
+    assert_eq!(db.description(2), Some("spaced   description "));
+    // A blank header keeps its (empty) title
+    assert_eq!((db.id(3), db.title(3), db.description(3)), ("unknown", Some(""), Some("")));
+    assert_eq!(db.title(5), None);
+
+    assert_eq!(db.get(4), Some(("P2", &b"GHW"[..])));
+    // The first of duplicate accessions wins
+    let accessions = db.accession_index();
+    assert_eq!(accessions["P2"], 1);
+    assert_eq!(accessions["sp|Q8WZ42|TITIN_HUMAN"], 0);
+
+    // One buffer holds all headers; IDs are slices of it
+    assert_eq!(db.headers.len(), db.header_offsets[db.len()]);
+    let id = db.id(0);
+    assert!(db.headers.as_bytes().as_ptr_range().contains(&id.as_ptr()));
+}
+
+#[test]
+fn stitle_prints_the_full_header() {
+    let path = write_temp("headers", "stitle", b">sp|P1|KIN1 Serine kinase OS=Yeast\nMKVLAAGHWQISFVDEPRT\n>P2 other\nGGGGSSSSGGGG\n");
+    let search = |stitle: bool| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_pro_search"));
+        cmd.args(["-d", path.to_str().unwrap(), "search", "--query", "MKVLAAGHWQISFVDEPRT"]);
+        if stitle {
+            cmd.arg("--stitle");
+        }
+        let out = cmd.output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let plain = search(false);
+    let full = search(true);
+    std::fs::remove_file(&path).unwrap();
+
+    let hit = |out: &str| out.lines().find(|l| l.contains("1. [Score:")).unwrap().to_string();
+    assert!(hit(&plain).ends_with("] sp|P1|KIN1"), "{}", plain);
+    assert!(hit(&full).ends_with("] sp|P1|KIN1 Serine kinase OS=Yeast"), "{}", full);
+}
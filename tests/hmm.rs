@@ -0,0 +1,152 @@
+use pro_search::align::hmm::Hmm;
+use pro_search::core::database::Database;
+use pro_search::search::hmmsearch::{search_hmm, HmmSearchOptions};
+
+mod common;
+use common::{Rng, ALPHABET};
+
+fn values(probs: &[f64]) -> String {
+    probs.iter()
+        .map(|&p| if p == 0.0 { "       *".to_string() } else { format!(" {:>8.5}", -p.ln()) })
+        .collect()
+}
+
+/// A HMMER3/f model emitting `consensus` with probability `conserved` at each node. The
+/// STATS lines are fitted to random sequences for the 0.6 models of the search test.
+fn model_text(name: &str, consensus: &[u8], conserved: f64, ga: Option<(f64, f64)>) -> String {
+    let uniform = [0.05; 20];
+    let middle = [0.95, 0.02, 0.03, 0.5, 0.5, 0.7, 0.3];
+    let mut text = format!("HMMER3/f [3.3.2 | Nov 2020]\nNAME  {}\nACC   PF99999.1\nDESC  Synthetic test family\n", name);
+    text += &format!("LENG  {}\nALPH  amino\nRF    no\nMM    no\nCONS  yes\nCS    no\nMAP   yes\nNSEQ  20\nEFFN  4.5\n", consensus.len());
+    if let Some((sequence, domain)) = ga {
+        text += &format!("GA    {:.1} {:.1};\nTC    {:.1} {:.1};\n", sequence, domain, sequence + 1.0, domain + 1.0);
+    }
+    text += "STATS LOCAL MSV       -5.0000  0.69315\nSTATS LOCAL VITERBI   -5.1000  0.69315\nSTATS LOCAL FORWARD   -2.5000  0.69315\n";
+    text += "HMM          A        C        D        E        F        G        H        I        K        L        M        N        P        Q        R        S        T        V        W        Y\n";
+    text += "            m->m     m->i     m->d     i->m     i->i     d->m     d->d\n";
+    text += &format!("  COMPO {}\n", values(&uniform));
+    text += &format!("        {}\n", values(&uniform));
+    text += &format!("        {}\n", values(&[0.97, 0.02, 0.01, 0.5, 0.5, 1.0, 0.0]));
+    for (k, &c) in consensus.iter().enumerate() {
+        let emissions: Vec<f64> = ALPHABET.iter()
+            .map(|&a| if a == c { conserved } else { (1.0 - conserved) / 19.0 })
+            .collect();
+        text += &format!("  {:>5} {} {:>6} {} - - -\n", k + 1, values(&emissions), k + 1, (c as char).to_ascii_lowercase());
+        text += &format!("        {}\n", values(&uniform));
+        let last = [0.99, 0.01, 0.0, 0.5, 0.5, 1.0, 0.0];
+        text += &format!("        {}\n", values(if k + 1 == consensus.len() { &last } else { &middle }));
+    }
+    text + "//\n"
+}
+
+fn load(text: &str) -> Vec<Hmm> {
+    Hmm::read_all(text.as_bytes(), "test.hmm").unwrap()
+}
+
+#[test]
+fn reads_hmmer3_models() {
+    let text = model_text("Tiny", b"WKC", 0.8, Some((25.0, 20.0))) + &model_text("Other", b"ACDEFGHIKL", 0.5, None);
+    let hmms = load(&text);
+    assert_eq!(hmms.len(), 2);
+    let tiny = &hmms[0];
+    assert_eq!((tiny.name.as_str(), tiny.len()), ("Tiny", 3));
+    assert_eq!(tiny.accession.as_deref(), Some("PF99999.1"));
+    assert_eq!(tiny.description.as_deref(), Some("Synthetic test family"));
+    assert_eq!(tiny.gathering, Some((25.0, 20.0)));
+    assert_eq!((tiny.msv.mu, tiny.viterbi.mu), (-5.0, -5.1));
+    assert_eq!(hmms[1].gathering, None);
+    // Conserved residues score well above background, the rest below; case does not
+    // matter and residues outside the alphabet are neutral
+    assert!(tiny.match_score(1, b'W') > 3.0);
+    assert!(tiny.match_score(1, b'A') < 0.0);
+    assert_eq!(tiny.match_score(2, b'k'), tiny.match_score(2, b'K'));
+    assert_eq!(tiny.match_score(3, b'X'), 0.0);
+
+    let truncated = text.replace("//\n", "");
+    let err = Hmm::read_all(truncated.as_bytes(), "test.hmm").unwrap_err().to_string();
+    assert!(err.contains("HMMER3 header") || err.contains("expected //"), "{}", err);
+    let dna = text.replace("ALPH  amino", "ALPH  DNA");
+    assert!(Hmm::read_all(dna.as_bytes(), "test.hmm").unwrap_err().to_string().contains("only amino"));
+    let uncalibrated = text.replace("STATS LOCAL MSV", "STATS LOCAL XXX");
+    assert!(Hmm::read_all(uncalibrated.as_bytes(), "test.hmm").unwrap_err().to_string().contains("hmmbuild"));
+}
+
+#[test]
+fn ssv_filter_matches_the_scalar_kernel() {
+    let mut rng = Rng(0xF00D);
+    // Lengths around the 16-node vectors, so the lane carry gets exercised
+    for len in [1, 15, 16, 17, 37, 64, 100] {
+        let consensus = rng.sequence(len);
+        let hmm = &load(&model_text("M", &consensus, 0.7, None))[0];
+        for _ in 0..20 {
+            let seq_len = 1 + (rng.next() % 300) as usize;
+            let mut seq = rng.sequence(seq_len);
+            if seq.len() > len + 10 {
+                let at = (rng.next() % (seq.len() - len) as u64) as usize;
+                seq[at..at + len].copy_from_slice(&rng.mutate(&consensus, 0.3));
+            }
+            seq[0] = b'X';
+            seq.iter_mut().step_by(7).for_each(|b| *b = b.to_ascii_lowercase());
+            assert_eq!(hmm.ssv_bits(&seq), hmm.ssv_bits_scalar(&seq));
+        }
+    }
+}
+
+#[test]
+fn finds_family_domains_with_coordinates() {
+    let mut rng = Rng(0x5EED_4646);
+    let consensus = rng.sequence(80);
+    let mut db = Database::new();
+    for i in 0..300 {
+        let len = 150 + (rng.next() % 150) as usize;
+        db.push(&format!("R{:03} unrelated", i), &rng.sequence(len));
+    }
+    for i in 0..5 {
+        let mut seq = rng.sequence(40 + 10 * i);
+        seq.extend(rng.mutate(&consensus, 0.4));
+        seq.extend(rng.sequence(30));
+        db.push(&format!("MEMBER{} family", i), &seq);
+    }
+    // Two copies, the second missing its first 20 nodes and with 4 inserted residues
+    let mut second = rng.mutate(&consensus[20..], 0.3);
+    second.splice(30..30, *b"GPGG");
+    let two = [rng.sequence(25), rng.mutate(&consensus, 0.3), rng.sequence(60), second, rng.sequence(15)].concat();
+    db.push("TWO repeat", &two);
+
+    let hmm = &load(&model_text("Fam", &consensus, 0.6, Some((40.0, 30.0))))[0];
+    let options = HmmSearchOptions::default();
+    let result = search_hmm(&db, hmm, &options).unwrap();
+    assert!(result.passed_filter < 60, "{} passed the filter", result.passed_filter);
+
+    let family: Vec<_> = result.hits.iter().filter(|h| !db.id(h.id).starts_with('R')).collect();
+    assert_eq!(family.len(), 6);
+    assert!(result.hits.iter().take(6).all(|h| !db.id(h.id).starts_with('R')));
+    assert!(family.iter().all(|h| h.evalue < 1e-10));
+    assert!(result.hits.iter().filter(|h| db.id(h.id).starts_with('R')).all(|h| h.evalue > 1e-3));
+
+    let member = family.iter().find(|h| db.id(h.id) == "MEMBER2").unwrap();
+    assert_eq!(member.domains.len(), 1);
+    let d = member.domains[0].domain;
+    assert!(d.seq_from.abs_diff(61) <= 3 && d.seq_to.abs_diff(140) <= 3, "{:?}", d);
+    assert!(d.hmm_from <= 4 && d.hmm_to >= 77, "{:?}", d);
+
+    let repeat = family.iter().find(|h| db.id(h.id) == "TWO").unwrap();
+    let domains: Vec<_> = repeat.domains.iter().map(|d| d.domain).collect();
+    assert_eq!(domains.len(), 2, "{:?}", domains);
+    assert!(domains[0].seq_from.abs_diff(26) <= 3 && domains[0].seq_to.abs_diff(105) <= 3, "{:?}", domains);
+    assert!(domains[1].seq_from.abs_diff(166) <= 3 && domains[1].seq_to.abs_diff(229) <= 3, "{:?}", domains);
+    assert!(domains[1].hmm_from.abs_diff(21) <= 3 && domains[1].hmm_to >= 77, "{:?}", domains);
+    // Both copies are significant alone, and the sequence scores above either
+    assert!(domains.iter().all(|d| d.bits > 40.0 && d.bits < repeat.bits));
+
+    // Without the filter the same family hits come out
+    let all = search_hmm(&db, hmm, &HmmSearchOptions { max: true, ..options }).unwrap();
+    assert_eq!(all.passed_filter, db.len());
+    assert_eq!(all.hits.iter().take(6).map(|h| h.id).collect::<Vec<_>>(), family.iter().map(|h| h.id).collect::<Vec<_>>());
+
+    // Gathering thresholds keep the family only
+    let ga = search_hmm(&db, hmm, &HmmSearchOptions { cut_ga: true, ..options }).unwrap();
+    assert_eq!(ga.hits.len(), 6);
+    let no_ga = &load(&model_text("Fam", &consensus, 0.6, None))[0];
+    assert!(search_hmm(&db, no_ga, &HmmSearchOptions { cut_ga: true, ..options }).is_err());
+}
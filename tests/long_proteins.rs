@@ -0,0 +1,42 @@
+use pro_search::align::ungapped::{refine_ungapped, Scoring};
+use pro_search::core::database::Database;
+use pro_search::filter::seed::find_candidate;
+use pro_search::index::compressed::CompressedKmerIndex;
+use pro_search::index::kmer::KmerIndex;
+
+mod common;
+use common::{pseudo_random, ALPHABET};
+
+// Deterministic pseudo-random protein, long enough to overflow a u16 position
+fn long_protein(len: usize) -> Vec<u8> {
+    pseudo_random(len, 0x2545_F491_4F6C_DD1D, ALPHABET)
+}
+
+fn single_protein_db(seq: Vec<u8>) -> Database {
+    let mut db = Database::new();
+    db.push("TITIN_LIKE Titin-like test protein", &seq);
+    db
+}
+
+#[test]
+fn diagonals_are_correct_past_u16_positions() {
+    let target = long_protein(70_000);
+    let q_start = 68_000;
+    let query = target[q_start..q_start + 60].to_vec();
+    let db = single_protein_db(target);
+
+    let index = KmerIndex::build(&db, 5);
+    let candidates = find_candidate(&index, &query, 2);
+    assert_eq!(candidates[0].id, 0);
+    assert_eq!(candidates[0].best_diagonal, q_start as i32);
+
+    let compressed = CompressedKmerIndex::build(&db, 5);
+    let candidates = find_candidate(&compressed, &query, 2);
+    assert_eq!(candidates[0].best_diagonal, q_start as i32);
+
+    let hits = refine_ungapped(&query, &candidates, &db, &Scoring::default(), 10, 1);
+    let (_, ext) = &hits[0];
+    assert_eq!(ext.t_start, q_start);
+    assert_eq!(ext.t_end, q_start + query.len() - 1);
+    assert_eq!(ext.score, query.len() as i32);
+}
@@ -0,0 +1,166 @@
+use pro_search::align::msa::{Msa, MsaFormat};
+use pro_search::align::profile::{Pssm, GAP};
+use pro_search::core::database::Database;
+use pro_search::search::iterative::{search_iterative, search_iterative_rows, ProfileOptions};
+use pro_search::search::pipeline::SearchIndexes;
+
+mod common;
+use common::{params, Rng};
+
+fn load(text: &str, name: &str) -> Msa {
+    let path = std::env::temp_dir().join(format!("pro_search_msa_{}_{}", std::process::id(), name));
+    std::fs::write(&path, text).unwrap();
+    let msa = Msa::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    msa.unwrap()
+}
+
+#[test]
+fn formats_agree_on_rows_and_profile_columns() {
+    // Column 4 is an insert column (one residue in four rows); seq3 is a fragment
+    let fasta = load(">seq1 first\nMKV-LAGH\n>seq2\nMRV-LSGH\n>seq3 fragment\n----IAGH\n>seq4\nMKVWLAG.\n", "aln.fa");
+    let a3m = load("#A3M#\n>seq1 first\nMKVLAGH\n>seq2\nMRVLSGH\n>seq3 fragment\n---IAGH\n>seq4\nMKVwLAG-\n", "aln.a3m");
+    let stockholm = load(
+        "# STOCKHOLM 1.0\n#=GF ID test\n\nseq1 MKV-\nseq2 MRV-\nseq3 ----\nseq4 MKVW\n#=GC RF xxx.\n\nseq1 LAGH\nseq2 LSGH\nseq3 IAGH\nseq4 LAG.\n//\nignored XXXX\n",
+        "aln.sto",
+    );
+    assert_eq!((fasta.format, a3m.format, stockholm.format), (MsaFormat::AlignedFasta, MsaFormat::A3m, MsaFormat::Stockholm));
+    assert_eq!(fasta.rows, stockholm.rows);
+    assert_eq!(fasta.names, vec!["seq1", "seq2", "seq3", "seq4"]);
+    assert_eq!(fasta.rows[3], b"MKVWLAG-");
+    assert_eq!(a3m.rows[3], b"MKVLAG-");
+
+    let profile = fasta.profile();
+    assert_eq!(profile.columns, vec![0, 1, 2, 4, 5, 6, 7]);
+    assert_eq!(profile.consensus, b"MKVLAGH");
+    // The fragment's missing start is outside its span, not a gap
+    assert_eq!(profile.rows[2], vec![None, None, None, Some(b'I'), Some(b'A'), Some(b'G'), Some(b'H')]);
+    assert_eq!(profile.rows[3][6], None);
+    assert_eq!(stockholm.profile().rows, profile.rows);
+    let a3m_profile = a3m.profile();
+    assert_eq!((a3m_profile.consensus, a3m_profile.rows), (profile.consensus.clone(), profile.rows.clone()));
+
+    // Rows that disagree in length are an error
+    let path = std::env::temp_dir().join(format!("pro_search_msa_{}_bad.sto", std::process::id()));
+    std::fs::write(&path, "# STOCKHOLM 1.0\na MKV\nb MK\n//\n").unwrap();
+    let err = Msa::load(&path).unwrap_err().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.contains("b has 2 aligned columns"), "{}", err);
+}
+
+#[test]
+fn profile_of_rows_matches_query_anchored_build() {
+    let query = b"MKVLAGH";
+    let hits = vec![
+        vec![Some(b'M'), Some(b'R'), Some(b'V'), Some(b'L'), Some(b'S'), Some(b'G'), Some(b'H')],
+        vec![None, Some(b'K'), Some(b'I'), Some(GAP), Some(b'A'), Some(b'G'), None],
+    ];
+    let mut rows = vec![query.iter().map(|&b| Some(b)).collect::<Vec<_>>()];
+    rows.extend(hits.clone());
+    assert_eq!(Pssm::from_rows(query, &rows).scores, Pssm::build(query, &hits).scores);
+    // A single sequence is its BLOSUM62 rows
+    assert_eq!(Pssm::from_rows(query, &rows[..1]).scores, Pssm::from_query(query).scores);
+}
+
+#[test]
+fn msa_query_finds_family_and_reports_msa_columns() {
+    let mut rng = Rng(0xA116E);
+    let ancestor = rng.sequence(150);
+    let mut db = Database::new();
+    for i in 0..300 {
+        db.push(&format!("R{:03} unrelated", i), &rng.sequence(180));
+    }
+    for i in 0..6 {
+        db.push(&format!("REMOTE{} family", i), &rng.mutate(&ancestor, 0.65));
+    }
+    // Eight members aligned without indels, plus a block of 10 insert columns after
+    // position 50 that only one member fills
+    let mut text = String::new();
+    for i in 0..8 {
+        let member = rng.mutate(&ancestor, 0.4);
+        let insert = if i == 0 { String::from_utf8(rng.sequence(10)).unwrap() } else { "-".repeat(10) };
+        text.push_str(&format!(">m{}\n{}{}{}\n", i,
+            std::str::from_utf8(&member[..50]).unwrap(), insert, std::str::from_utf8(&member[50..]).unwrap()));
+    }
+    let msa = load(&text, "family.fa");
+    let profile = msa.profile();
+    assert_eq!((msa.columns(), profile.columns.len()), (160, 150));
+
+    let params = params(3);
+    let indexes = SearchIndexes::build(&db, &params);
+    let options = ProfileOptions::default();
+    let result = search_iterative_rows(&db, &indexes, &profile.consensus, &profile.rows, &params, &options);
+    let remote = |hits: &[pro_search::search::iterative::ProfileHit]| hits.iter()
+        .filter(|h| h.evalue <= options.inclusion_ethresh && db.id(h.id as usize).starts_with("REMOTE"))
+        .count();
+    assert_eq!(result.rounds.len(), 1);
+    // Better than any single member alone
+    let single = msa.rows.iter()
+        .map(|row| {
+            let seq: Vec<u8> = row.iter().copied().filter(|&b| b != GAP).collect();
+            remote(&search_iterative(&db, &indexes, &seq, &params, &options).hits)
+        })
+        .max()
+        .unwrap();
+    assert!(remote(&result.hits) > single, "{} vs {}", remote(&result.hits), single);
+
+    // A hit spanning the insert block covers more MSA columns than profile columns
+    let best = &result.hits[0];
+    assert!(db.id(best.id as usize).starts_with("REMOTE"));
+    let aln = &best.alignment;
+    assert!(aln.q_start < 50 && aln.q_end > 50);
+    let (first, last) = (profile.columns[aln.q_start], profile.columns[aln.q_end - 1]);
+    assert_eq!(last - first, aln.q_end - 1 - aln.q_start + 10);
+}
+
+#[test]
+fn a3m_is_told_by_lowercase_or_its_marker_even_with_equal_rows() {
+    // Equal-length rows whose lowercase letters are insertions
+    let lowercase = load(">seq1\nMKVaLAGH\n>seq2\nMRV.LSGH\n", "equal.a3m");
+    assert_eq!(lowercase.format, MsaFormat::A3m);
+    assert_eq!(lowercase.rows, vec![b"MKVLAGH".to_vec(), b"MRVLSGH".to_vec()]);
+    let marked = load("#A3M#\n>seq1\nMKVLAGH\n>seq2\nMRVLSGH\n", "marked.a3m");
+    assert_eq!(marked.format, MsaFormat::A3m);
+    let fasta = load(">seq1\nMKV-LAGH\n>seq2\nMRVWLSGH\n", "upper.fa");
+    assert_eq!(fasta.format, MsaFormat::AlignedFasta);
+}
+
+#[test]
+fn out_pssm_works_with_an_alignment_query_alone() {
+    let mut rng = Rng(0x0755);
+    let ancestor = rng.sequence(120);
+    let dir = std::env::temp_dir();
+    let tag = std::process::id();
+    let (db_path, msa_path, pssm_path) = (
+        dir.join(format!("pro_search_msa_{}_db.fa", tag)),
+        dir.join(format!("pro_search_msa_{}_family.fa", tag)),
+        dir.join(format!("pro_search_msa_{}_family.pssm", tag)),
+    );
+    let mut db = String::new();
+    for i in 0..20 {
+        let seq = if i % 5 == 0 { rng.mutate(&ancestor, 0.3) } else { rng.sequence(150) };
+        db.push_str(&format!(">P{}\n{}\n", i, String::from_utf8(seq).unwrap()));
+    }
+    std::fs::write(&db_path, db).unwrap();
+    let msa: String = (0..4)
+        .map(|i| format!(">m{}\n{}\n", i, String::from_utf8(rng.mutate(&ancestor, 0.2)).unwrap()))
+        .collect();
+    std::fs::write(&msa_path, msa).unwrap();
+
+    let run = |extra: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_pro_search"))
+        .args(["-d", db_path.to_str().unwrap(), "search", "-k", "3", "--scoring", "blosum62"])
+        .args(extra)
+        .args(["--out-pssm", pssm_path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    let out = run(&["--query-msa", msa_path.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(std::fs::read_to_string(&pssm_path).unwrap().contains("Last position-specific scoring matrix"));
+
+    // A plain query still needs --iterations
+    let out = run(&["--query", "MKVLAAGHWQ"]);
+    assert!(!out.status.success());
+    for path in [db_path, msa_path, pssm_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use pro_search::align::matrix::ScoreMatrix;
+use pro_search::align::profile::{align_profile, Pssm, GAP};
+use pro_search::core::alphabet::encode_kmer;
+use pro_search::core::database::Database;
+use pro_search::search::iterative::{search_iterative, search_profile, ProfileOptions};
+use pro_search::search::pipeline::SearchIndexes;
+
+mod common;
+use common::{params, Rng, ALPHABET};
+
+#[test]
+fn first_profile_is_blosum62_and_hits_sharpen_it() {
+    let blosum = ScoreMatrix::blosum62();
+    let query = b"MKWVLAGHCE";
+    let pssm = Pssm::from_query(query);
+    for (pos, &q) in query.iter().enumerate() {
+        for &aa in ALPHABET {
+            assert_eq!(pssm.score(pos, aa), blosum.score(q, aa));
+        }
+    }
+    assert!(pssm.relative_weight.iter().all(|&w| w == 0.0));
+
+    // Position 2 (W) is conserved in every hit; position 3 (V) varies among I, L, V, M;
+    // position 5 (A) is only reached by one hit; the last two are never aligned
+    let rows: Vec<Vec<Option<u8>>> = ["MKWIL", "MRWLV", "MKWMI", "AKWVLS", "LKWLI"]
+        .iter()
+        .map(|hit| {
+            let mut row: Vec<Option<u8>> = hit.bytes().map(Some).collect();
+            row.resize(query.len(), None);
+            row
+        })
+        .collect();
+    let pssm = Pssm::build(query, &rows);
+    assert!(pssm.score(2, b'W') >= blosum.score(b'W', b'W'));
+    assert!(pssm.score(3, b'L') > blosum.score(b'V', b'L'));
+    assert!(pssm.score(3, b'M') > blosum.score(b'V', b'M'));
+    assert!(pssm.score(3, b'V') <= blosum.score(b'V', b'V'));
+    assert!(pssm.score(3, b'D') < 0);
+    assert!(pssm.relative_weight[0] > 0.0);
+    assert!(pssm.information[2] > pssm.information[3]);
+    for (pos, &q) in query.iter().enumerate().skip(6) {
+        assert_eq!(pssm.score(pos, b'A'), blosum.score(q, b'A'));
+        assert_eq!(pssm.relative_weight[pos], 0.0);
+    }
+    // Gaps count for the weights but not for the frequencies: a column where the hits
+    // only have gaps keeps its matrix row
+    let gapped = vec![vec![Some(b'L'), Some(GAP), Some(b'W'), None, None, None, None, None, None, None]];
+    let pssm = Pssm::build(query, &gapped);
+    assert_eq!(pssm.frequencies[1][11], 1.0); // K, the query's residue
+    assert_eq!(pssm.relative_weight[1], 0.0);
+    assert_eq!(pssm.score(1, b'R'), blosum.score(b'K', b'R'));
+    assert!(pssm.relative_weight[0] > 0.0);
+}
+
+#[test]
+fn neighborhood_words_cover_the_query_and_similar_words() {
+    let pssm = Pssm::from_query(b"WWHKLmMC");
+    let words = pssm.neighborhood_words(3, 11);
+    let at = |pos: usize| words.iter().filter(|w| w.0 == pos).map(|w| w.1).collect::<Vec<u64>>();
+    // W-W scores 11 on its own: WWH has neighbors, and its own word is there once
+    let own = encode_kmer(b"WWH").unwrap();
+    assert_eq!(at(0).iter().filter(|&&w| w == own).count(), 1);
+    assert!(at(0).contains(&encode_kmer(b"WWY").unwrap()));
+    assert!(!at(0).contains(&encode_kmer(b"AAA").unwrap()));
+    // HKL scores 8 + 5 + 4 = 17 and keeps its own word even with a high threshold
+    assert_eq!(pssm.neighborhood_words(3, 40).iter().filter(|w| w.0 == 1).count(), 1);
+    // Windows over the soft-masked m never seed
+    assert!(at(3).is_empty() && at(4).is_empty() && at(5).is_empty());
+    // Every word reaches the threshold or is the query's own
+    let code = |w: u64, i: usize| ALPHABET[((w >> (5 * (2 - i))) & 31) as usize];
+    for &(pos, w) in &words {
+        let score: i32 = (0..3).map(|i| pssm.score(pos + i, code(w, i))).sum();
+        assert!(score >= 11 || encode_kmer(&pssm.query[pos..pos + 3]) == Some(w));
+    }
+}
+
+#[test]
+fn profile_alignment_matches_smith_waterman_on_blosum_rows() {
+    use pro_search::align::smith_waterman::align_scored;
+    use pro_search::align::ungapped::Scoring;
+    let mut rng = Rng(0xACE);
+    let ancestor = rng.sequence(120);
+    let query = rng.mutate(&ancestor, 0.3);
+    let mut target = rng.sequence(30);
+    let mut middle = rng.mutate(&ancestor, 0.3);
+    middle.drain(50..53);
+    middle.splice(80..80, b"GGPPG".iter().copied());
+    target.extend(middle);
+    target.extend(rng.sequence(20));
+
+    let pssm = Pssm::from_query(&query);
+    let aln = align_profile(&pssm, &target, -11, -1);
+    let sw = align_scored(&query, &target, &Scoring::blosum62());
+    assert_eq!(aln.score, sw.score);
+    assert_eq!((aln.q_start, aln.q_end, aln.t_start, aln.t_end), (sw.xstart, sw.xend, sw.ystart, sw.yend));
+    assert_eq!(aln.columns.len(), aln.q_end - aln.q_start);
+    // One column per aligned query position; the deleted residues show up as gaps
+    let row = aln.row(query.len());
+    assert!(row.contains(&Some(GAP)));
+    assert_eq!(row.iter().filter(|c| c.is_some()).count(), aln.columns.len());
+}
+
+#[test]
+fn later_rounds_find_remote_family_members() {
+    let mut rng = Rng(0x5EED_1234);
+    let ancestor = rng.sequence(200);
+    let mut db = Database::new();
+    for i in 0..300 {
+        let len = 150 + (rng.next() % 100) as usize;
+        db.push(&format!("R{:03} unrelated", i), &rng.sequence(len));
+    }
+    // Close members bridge to the remote ones through the ancestor
+    let query = rng.mutate(&ancestor, 0.35);
+    for i in 0..12 {
+        db.push(&format!("CLOSE{:02} family", i), &rng.mutate(&ancestor, 0.35));
+    }
+    for i in 0..8 {
+        db.push(&format!("REMOTE{} family", i), &rng.mutate(&ancestor, 0.7));
+    }
+
+    let params = params(3);
+    let indexes = SearchIndexes::build(&db, &params);
+    let options = ProfileOptions { iterations: Some(5), ..Default::default() };
+    let family = |hits: &[pro_search::search::iterative::ProfileHit], prefix: &str| hits.iter()
+        .filter(|h| h.evalue <= options.inclusion_ethresh && db.id(h.id as usize).starts_with(prefix))
+        .count();
+
+    let first = search_profile(&db, &indexes, &Pssm::from_query(&query), &params, options.word_threshold(3));
+    let result = search_iterative(&db, &indexes, &query, &params, &options);
+    // The first round misses some remote members; the profile of the others finds them
+    assert_eq!(result.rounds[0].included, family(&first, "CLOSE") + family(&first, "REMOTE"));
+    assert!(family(&first, "REMOTE") < 8);
+    assert_eq!((family(&result.hits, "CLOSE"), family(&result.hits, "REMOTE")), (12, 8));
+    assert!(result.rounds[1].new > 0);
+    assert!(result.converged);
+    assert_eq!(result.rounds.last().unwrap().new, 0);
+    // Nothing unrelated is ever included
+    assert_eq!(result.rounds.last().unwrap().included, 20);
+
+    let mut ascii = Vec::new();
+    result.pssm.write_ascii(&mut ascii).unwrap();
+    let text = String::from_utf8(ascii).unwrap();
+    let rows: Vec<&str> = text.lines().filter(|l| l.trim_start().starts_with(|c: char| c.is_ascii_digit())).collect();
+    assert_eq!(rows.len(), query.len());
+    // Position, residue, 20 scores, 20 percentages, information, relative weight
+    assert_eq!(rows[0].split_whitespace().count(), 44);
+    assert!(text.contains("Last position-specific scoring matrix computed"));
+}
@@ -0,0 +1,178 @@
+use pro_search::align::composition::CompAdjust;
+use pro_search::align::ungapped::ScoringScheme;
+use pro_search::core::database::Database;
+use pro_search::filter::seg::SegTarget;
+use pro_search::index::kmer::BuildOptions;
+use pro_search::search::pipeline::{search_query, SearchIndexes, SearchMode, SearchParams};
+use pro_search::search::query_indexed;
+
+mod common;
+use common::{Rng, ALPHABET};
+
+fn random_db(rng: &mut Rng, n: usize) -> Database {
+    let mut db = Database::new();
+    for i in 0..n {
+        let len = 40 + rng.below(300);
+        // Small alphabet slice on some proteins so ties and repeats show up
+        let alphabet = if i % 7 == 0 { &ALPHABET[..4] } else { ALPHABET };
+        let seq: Vec<u8> = (0..len).map(|_| alphabet[rng.below(alphabet.len())]).collect();
+        db.push(&format!("T{:04} target {}", i, i), &seq);
+    }
+    db
+}
+
+fn mutated_queries(rng: &mut Rng, db: &Database, n: usize) -> Database {
+    let mut queries = Database::new();
+    for i in 0..n {
+        let (_, seq) = db.get(rng.below(db.len())).unwrap();
+        let len = 30.min(seq.len());
+        let start = rng.below(seq.len() - len + 1);
+        let mut q = seq[start..start + len].to_vec();
+        for aa in q.iter_mut() {
+            if rng.below(10) == 0 {
+                *aa = ALPHABET[rng.below(ALPHABET.len())];
+            }
+        }
+        queries.push(&format!("Q{}", i), &q);
+    }
+    queries
+}
+
+fn params(mode: SearchMode) -> SearchParams {
+    SearchParams { mode, k: 3, pattern: "11011".to_string(), ..Default::default() }
+}
+
+#[test]
+fn query_indexed_search_matches_database_indexed_search() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let db = random_db(&mut rng, 300);
+    let queries = mutated_queries(&mut rng, &db, 25);
+
+    let fasta_path = std::env::temp_dir().join(format!("pro_search_qidx_{}.fa", std::process::id()));
+    let mut fasta = String::new();
+    for i in 0..db.len() {
+        let (_, seq) = db.get(i).unwrap();
+        fasta.push_str(&format!(">{}\n{}\n", db.title(i).unwrap(), std::str::from_utf8(seq).unwrap()));
+    }
+    std::fs::write(&fasta_path, fasta).unwrap();
+
+    let cases = [
+        (SearchMode::Basic, SegTarget::Query, CompAdjust::None),
+        (SearchMode::Diagonal, SegTarget::Query, CompAdjust::None),
+        (SearchMode::Auto, SegTarget::Query, CompAdjust::None),
+        (SearchMode::Auto, SegTarget::Both, CompAdjust::None),
+        (SearchMode::Auto, SegTarget::Query, CompAdjust::Conditional),
+    ];
+    for (mode, seg, comp_adjust) in cases {
+        let mut params = params(mode);
+        params.seg.target = seg;
+        if comp_adjust != CompAdjust::None {
+            params.scoring = ScoringScheme::Blosum62;
+            params.comp_adjust = comp_adjust;
+        }
+        // The database-indexed path masks the database before indexing it
+        let mut indexed_db = Database::new();
+        for i in 0..db.len() {
+            indexed_db.push(db.title(i).unwrap(), db.get(i).unwrap().1);
+        }
+        params.seg.mask_database(&mut indexed_db);
+        let indexes = SearchIndexes::build(&indexed_db, &params);
+        let from_db = query_indexed::search_database(&queries, &db, &params).unwrap();
+        let from_fasta = query_indexed::search_fasta(&queries, &fasta_path, &params).unwrap();
+        assert!(from_db.iter().filter(|hits| !hits.is_empty()).count() > queries.len() / 2);
+
+        for q in 0..queries.len() {
+            let (_, q_seq) = queries.get(q).unwrap();
+            let expected: Vec<(usize, f32)> = search_query(&indexed_db, &indexes, q_seq, &params)
+                .into_iter()
+                .map(|(id, score)| (id as usize, score))
+                .collect();
+            let got: Vec<(usize, f32)> = from_db[q].iter().map(|h| (h.id, h.score)).collect();
+            assert_eq!(got, expected, "{:?} mode, SEG on {:?}, {:?}, query {}", mode, seg, comp_adjust, q);
+            let streamed: Vec<(usize, f32)> = from_fasta[q].iter().map(|h| (h.id, h.score)).collect();
+            assert_eq!(streamed, expected, "{:?} mode, SEG on {:?}, {:?}, query {} (streamed)", mode, seg, comp_adjust, q);
+        }
+    }
+    std::fs::remove_file(&fasta_path).unwrap();
+}
+
+#[test]
+fn query_indexed_search_ranks_many_candidates_like_database_indexed_search() {
+    let mut rng = Rng(0x51DE);
+    let mut random = |len: usize| -> Vec<u8> { (0..len).map(|_| ALPHABET[rng.below(20)]).collect() };
+    let query = random(200);
+    // More ungapped hits than get a Smith-Waterman alignment: partial copies of the
+    // query, some with an indel every few residues
+    let mut db = Database::new();
+    for i in 0..60 {
+        let start = (i * 7) % 150;
+        let mut seq = random(30);
+        for (j, chunk) in query[start..start + 50].chunks(5 + i % 10).enumerate() {
+            seq.extend_from_slice(chunk);
+            if i % 3 == 0 && j % 2 == 0 {
+                seq.extend(random(1));
+            }
+        }
+        seq.extend(random(30));
+        db.push(&format!("T{:02}", i), &seq);
+    }
+    let mut queries = Database::new();
+    queries.push("Q", &query);
+
+    let params = SearchParams { k: 4, top_n: 40, ..params(SearchMode::Auto) };
+    let indexes = SearchIndexes::build(&db, &params);
+    let expected: Vec<(usize, f32)> = search_query(&db, &indexes, &query, &params)
+        .into_iter()
+        .map(|(id, score)| (id as usize, score))
+        .collect();
+    assert_eq!(expected.len(), 20);
+    let got: Vec<(usize, f32)> = query_indexed::search_database(&queries, &db, &params).unwrap()[0]
+        .iter()
+        .map(|h| (h.id, h.score))
+        .collect();
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn postings_caps_are_refused_rather_than_ignored() {
+    let mut rng = Rng(0xCA95);
+    let db = random_db(&mut rng, 80);
+    let queries = mutated_queries(&mut rng, &db, 10);
+    let uncapped = params(SearchMode::Diagonal);
+    let indexes = SearchIndexes::build(&db, &uncapped);
+    let from_query_index = query_indexed::search_database(&queries, &db, &uncapped).unwrap();
+    assert!(from_query_index.iter().any(|hits| !hits.is_empty()));
+
+    for build in [
+        BuildOptions { max_postings: Some(5), ..Default::default() },
+        BuildOptions { max_percentile: Some(50.0), ..Default::default() },
+    ] {
+        let capped = SearchParams { build, ..params(SearchMode::Diagonal) };
+        // The database path stop-lists k-mers by their database counts, so its hits change
+        let capped_indexes = SearchIndexes::build(&db, &capped);
+        let changed = (0..queries.len()).any(|q| {
+            let q_seq = queries.get(q).unwrap().1;
+            search_query(&db, &capped_indexes, q_seq, &capped) != search_query(&db, &indexes, q_seq, &uncapped)
+        });
+        assert!(changed, "{:?}", build);
+        // A query index cannot apply those counts, so it must not return its uncapped hits
+        let err = query_indexed::search_database(&queries, &db, &capped).err().unwrap().to_string();
+        assert!(err.contains("--max-postings and --mask-percentile"), "{}", err);
+    }
+}
+
+#[test]
+fn query_index_rejects_database_index_options() {
+    let path = common::write_temp("qidx", "options.fa", b">T1\nMKVLAAGHWQISFVDEPRT\n>T2\nMKVLAAGHWQ\n");
+    let search = |extra: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_pro_search"))
+        .args(["-d", path.to_str().unwrap(), "search", "--query", "MKVLAAGHWQISFVDEPRT", "--query-index"])
+        .args(extra)
+        .output()
+        .unwrap();
+    assert!(search(&[]).status.success());
+    for extra in [&["--compressed"][..], &["--very-sensitive"], &["--max-memory", "64M"], &["--max-postings", "5"]] {
+        let out = search(extra);
+        assert!(!out.status.success(), "{:?}", extra);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
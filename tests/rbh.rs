@@ -0,0 +1,98 @@
+use pro_search::core::database::Database;
+use pro_search::search::pipeline::{search_query_aligned, SearchIndexes, SearchParams};
+use pro_search::search::rbh::{best_hits, coverage, identity, reciprocal_best_hits, RbhOptions};
+
+mod common;
+use common::{params, Rng};
+
+#[test]
+fn pairs_reciprocal_best_hits_with_cutoffs() {
+    let mut rng = Rng(0x0A7B);
+    let (mut a, mut b) = (Database::new(), Database::new());
+    // Orthologs at 80% identity, longer than an alignment window
+    for i in 0..20 {
+        let ancestor = rng.sequence(200 + 10 * i);
+        a.push(&format!("A{:02}", i), &rng.mutate(&ancestor, 0.1));
+        b.push(&format!("B{:02}", i), &rng.mutate(&ancestor, 0.1));
+    }
+    // Two paralogs in A, one copy in B closer to the first
+    let paralog = rng.sequence(250);
+    a.push("A_PARALOG1", &paralog);
+    a.push("A_PARALOG2", &rng.mutate(&paralog, 0.3));
+    b.push("B_PARALOG", &rng.mutate(&paralog, 0.1));
+    // An exact fragment in B outscores the distant full-length copy even over the full
+    // alignments, until coverage counts
+    let full = rng.sequence(300);
+    a.push("A_FULL", &full);
+    b.push("B_FRAGMENT", &full[90..230]);
+    b.push("B_DISTANT", &rng.mutate(&full, 0.5));
+    for i in 0..30 {
+        a.push(&format!("A_OTHER{}", i), &rng.sequence(150));
+        b.push(&format!("B_OTHER{}", i), &rng.sequence(150));
+    }
+
+    let params = SearchParams { x_drop: 20, ..params(4) };
+    let (a_indexes, b_indexes) = (SearchIndexes::build(&a, &params), SearchIndexes::build(&b, &params));
+    let names = |options: &RbhOptions| -> Vec<(String, String)> {
+        reciprocal_best_hits(&a, &a_indexes, &b, &b_indexes, &params, options)
+            .into_iter()
+            .map(|p| (a.id(p.a).to_string(), b.id(p.b).to_string()))
+            .collect()
+    };
+
+    let pairs = reciprocal_best_hits(&a, &a_indexes, &b, &b_indexes, &params, &RbhOptions::default());
+    let found = names(&RbhOptions::default());
+    for i in 0..20 {
+        assert!(found.contains(&(format!("A{:02}", i), format!("B{:02}", i))), "{:?}", found);
+    }
+    assert!(found.contains(&("A_PARALOG1".to_string(), "B_PARALOG".to_string())));
+    assert!(found.iter().all(|(x, _)| x != "A_PARALOG2"));
+    assert!(found.contains(&("A_FULL".to_string(), "B_FRAGMENT".to_string())), "{:?}", found);
+
+    // Full-length alignments: orthologs cover nearly all of both proteins
+    let ortholog = pairs.iter().find(|p| a.id(p.a) == "A19").unwrap();
+    let (a_cov, b_cov) = coverage(&ortholog.ab, a.get(ortholog.a).unwrap().1.len(), b.get(ortholog.b).unwrap().1.len());
+    assert!(a_cov > 90.0 && b_cov > 90.0, "{} {}", a_cov, b_cov);
+    assert!(identity(&ortholog.ab) > 70.0 && identity(&ortholog.ab) < 95.0);
+    assert!(ortholog.ab.score > 0 && ortholog.ba.score > 0);
+    let fragment = pairs.iter().find(|p| a.id(p.a) == "A_FULL").unwrap();
+    assert_eq!(identity(&fragment.ab), 100.0);
+    assert_eq!((fragment.ab.q_start, fragment.ab.q_end), (90, 230));
+
+    // Covering under half of A_FULL is not enough; the distant copy takes its place
+    let covered = names(&RbhOptions { min_coverage: 50.0, ..Default::default() });
+    assert!(covered.contains(&("A_FULL".to_string(), "B_DISTANT".to_string())), "{:?}", covered);
+    assert!(covered.iter().all(|(_, y)| y != "B_FRAGMENT"));
+    // Unrelated proteins can be each other's best chance hit, but only over a short stretch
+    assert!(covered.iter().all(|(x, y)| !x.starts_with("A_OTHER") && !y.starts_with("B_OTHER")), "{:?}", covered);
+    // and an identity cutoff above it leaves A_FULL unpaired
+    let strict = names(&RbhOptions { min_coverage: 50.0, min_identity: 80.0 });
+    assert!(strict.iter().all(|(x, _)| x != "A_FULL"));
+    assert!(strict.contains(&("A00".to_string(), "B00".to_string())));
+}
+
+#[test]
+fn best_hit_has_the_best_full_alignment_not_the_best_window() {
+    let mut rng = Rng(0xB357);
+    let query = rng.sequence(400);
+    let mut db = Database::new();
+    for i in 0..50 {
+        db.push(&format!("OTHER{}", i), &rng.sequence(300));
+    }
+    // An exact 120-residue piece wins the windowed alignment; the full-length copy
+    // scores far higher over the whole protein
+    db.push("PIECE", &query[140..260]);
+    db.push("COPY", &rng.mutate(&query, 0.15));
+    let mut queries = Database::new();
+    queries.push("Q", &query);
+
+    let params = SearchParams { x_drop: 20, ..params(4) };
+    let indexes = SearchIndexes::build(&db, &params);
+    let windowed = search_query_aligned(&db, &indexes, &query, &params);
+    assert_eq!(db.id(windowed[0].id as usize), "PIECE");
+
+    let best = best_hits(&queries, &db, &indexes, &params, &RbhOptions::default());
+    let best = best[0].as_ref().unwrap();
+    assert_eq!(db.id(best.id as usize), "COPY");
+    assert!(best.q_end - best.q_start > 350);
+}
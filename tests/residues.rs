@@ -0,0 +1,107 @@
+use pro_search::align::simd::{extend_direction_simd, same_residue};
+use pro_search::align::ungapped::Scoring;
+use pro_search::core::alphabet::{encode_kmer, get_score};
+use pro_search::core::database::Database;
+use pro_search::core::residues::{count_mostly_lowercase, is_mostly_lowercase, ResiduePolicy, StopResidue, UnknownResidue};
+use pro_search::index::kmer::{BuildOptions, KmerIndex};
+
+#[test]
+fn case_and_rare_residues_are_normalized() {
+    let policy = ResiduePolicy { map_rare: true, map_ambiguous: true, ..Default::default() };
+    let mapper = policy.mapper();
+    let mut seq = b"mkUoBzJxAc".to_vec();
+    mapper.normalize(&mut seq);
+    // Case survives: lowercase is a soft mask
+    assert_eq!(seq, b"mkCkDeLxAc");
+
+    let upper = ResiduePolicy { map_rare: true, ignore_lowercase: true, ..Default::default() }.mapper();
+    let mut seq = b"mkUoBzJxAc".to_vec();
+    upper.normalize(&mut seq);
+    assert_eq!(seq, b"MKCKBZJXAC");
+
+    let mut raw = b"MKUOBZJX".to_vec();
+    ResiduePolicy::default().mapper().normalize(&mut raw);
+    assert_eq!(raw, b"MKUOBZJX");
+
+    for (&lower, &upper) in b"arndcqeghilkmfpstwyvbzx".iter().zip(b"ARNDCQEGHILKMFPSTWYVBZX") {
+        assert_eq!(get_score(lower, b'W'), get_score(upper, b'W'));
+        assert_eq!(get_score(lower, lower), get_score(upper, upper));
+    }
+}
+
+#[test]
+fn stops_split_records_into_fragments() {
+    let mapper = ResiduePolicy { stop: StopResidue::Split, ..Default::default() }.mapper();
+    let mut db = Database::new();
+    mapper.push(&mut db, "P1 first protein", b"MKV*GHW**LL*");
+    mapper.push(&mut db, "P2 trailing stop", b"ACD*");
+    mapper.push(&mut db, "P3", b"*");
+
+    let titles: Vec<&str> = (0..db.len()).map(|i| db.title(i).unwrap()).collect();
+    assert_eq!(titles, vec![
+        "P1_1 first protein", "P1_2 first protein", "P1_3 first protein",
+        "P2 trailing stop", "P3",
+    ]);
+    let seqs: Vec<&[u8]> = (0..db.len()).map(|i| db.get(i).unwrap().1).collect();
+    assert_eq!(seqs, vec![&b"MKV"[..], b"GHW", b"LL", b"ACD", b""]);
+
+    let kept = ResiduePolicy::default().mapper().fragments("P1", b"MKV*GHW");
+    assert_eq!(kept, vec![("P1".to_string(), b"MKV*GHW".to_vec())]);
+}
+
+#[test]
+fn wildcard_x_seeds_match_any_residue() {
+    let mut db = Database::new();
+    db.push("T1", b"MKVXLGH");
+    let policy = ResiduePolicy { unknown: UnknownResidue::Wildcard, ..Default::default() };
+
+    let breaking = KmerIndex::build(&db, 3);
+    let wildcard = KmerIndex::build_with(&db, 3, &BuildOptions {
+        wildcard_x: policy.unknown == UnknownResidue::Wildcard,
+        ..Default::default()
+    });
+    let kmer = encode_kmer(b"VWL").unwrap();
+    assert!(breaking.query(kmer).is_none());
+    assert_eq!(wildcard.query(kmer).unwrap().as_slice(), &[(0, 2)]);
+    // MKV and LGH, plus each of the three X windows under 20 residues
+    assert_eq!(wildcard.map.len(), 2 + 3 * 20);
+
+    // Two X in one k-mer still break the seed
+    let mut db = Database::new();
+    db.push("T2", b"KXXL");
+    let wildcard = KmerIndex::build_with(&db, 3, &BuildOptions { wildcard_x: true, ..Default::default() });
+    assert!(wildcard.map.is_empty());
+}
+
+#[test]
+fn only_letters_compare_case_insensitively() {
+    assert!(same_residue(b'm', b'M'));
+    assert!(same_residue(b'K', b'k'));
+    assert!(!same_residue(b'@', b'`'));
+    assert!(!same_residue(b'[', b'{'));
+    assert!(!same_residue(b'*', b'\n'));
+
+    // The AVX2 right extension folds case the same way as the scalar score
+    let scoring = Scoring::default();
+    let upper = b"MKVLAAGHWQISFVDEPRTAMKVLAAGHWQISFVDEPRTA";
+    let lower = upper.to_ascii_lowercase();
+    let (score, q_end, _) = extend_direction_simd(upper, &lower, &scoring, 0, 0, 1, 20);
+    assert_eq!((score, q_end), (scoring.match_score * upper.len() as i32, upper.len() - 1));
+    let at = [b'@'; 40];
+    let backtick = [b'`'; 40];
+    assert_eq!(extend_direction_simd(&at, &backtick, &scoring, 0, 0, 1, 20).0, 0);
+}
+
+#[test]
+fn mostly_lowercase_records_are_counted() {
+    assert!(is_mostly_lowercase(b"mkvlaGH"));
+    assert!(!is_mostly_lowercase(b"mkvLAGH"));
+    assert!(!is_mostly_lowercase(b"MKVLA**"));
+    assert!(!is_mostly_lowercase(b""));
+
+    let mut db = Database::new();
+    db.push("P1", b"mkvlaaghwq");
+    db.push("P2", b"MKVLAaghwq");
+    db.push("P3", b"MKVLAAGHWQ");
+    assert_eq!(count_mostly_lowercase(&db), 1);
+}
@@ -0,0 +1,58 @@
+use pro_search::align::smith_waterman::align_sw;
+use pro_search::align::ungapped::{extend_ungapped, Scoring};
+use pro_search::core::database::Database;
+use pro_search::filter::seg::{MaskMode, Seg};
+use pro_search::index::kmer::KmerIndex;
+
+const COMPLEX: &[u8] = b"TQLPSGSELSLYDIAPVTPGVAVDLSHIPTAVKIKGFSGEDATPALEGADVVLISAGVARKPGMDRSDLFNVNAGIVKNLVQQVAKTCPKACIGIITNPVNTTVAIAAEVLKKAGVYDKNKLFGVTTLDIIRSNTFVAELKGKQPGEVEVPVIGGHSGVTILPLLSQVPGVSFTEQEVADLTKRIQNAGTEVVEAKAGGGSATLSMGQAAARFGLSLVRALQGEQGVVECAYVEGDGQYARFFSQPLLLGKNGVEERKSIGTLSAFEQNALEGMLDTLKKDIALGQEFVNK";
+
+#[test]
+fn masks_low_complexity_but_not_ordinary_sequence() {
+    let seg = Seg::default();
+    assert!(seg.segments(COMPLEX).is_empty());
+
+    let mut seq = COMPLEX[..60].to_vec();
+    seq.extend_from_slice(&[b'Q'; 30]);
+    seq.extend_from_slice(&COMPLEX[60..120]);
+    let segments = seg.segments(&seq);
+    assert_eq!(segments.len(), 1);
+    let (start, end) = segments[0];
+    // Trimming drops the flanking windows: any other residue makes the run more probable
+    assert_eq!((start, end), (60, 90));
+
+    let mut hard = seq.clone();
+    seg.mask(&mut hard, MaskMode::Hard);
+    assert!(hard[60..90].iter().all(|&b| b == b'X'));
+    assert_eq!(&hard[..start], &seq[..start]);
+}
+
+#[test]
+fn matches_ncbi_seg_segments() {
+    // Expected ranges follow NCBI seg.c (segseq, trim, getprob, mergesegs) at 12 / 2.2 / 2.5,
+    // converted to 0-based half-open. In the first sequence the trigger window of the
+    // poly-Q segment is trimmed off, and the G-rich run left of it is found on a second pass.
+    let check = |seq: &[u8], expected: &[(usize, usize)]| {
+        assert_eq!(Seg::default().segments(seq), expected, "{}", String::from_utf8_lossy(seq));
+    };
+    check(b"KNPRAHVQHEMDDDRGCSIECGVWSQSWIHCEIGTHGSGGNGGSCHQQQHQQQHTQQHQQGHQQHHQQQHQQQPDIIIMPMRAEGGGDPLHFAWPPMDRSVSGSGSCSGSGSSSGSGSGMAGNEAELYKESRSRSRSRSRSRSRSRSRSRYELMQTTSVMS",
+        &[(33, 43), (45, 73), (99, 119), (130, 150)]);
+    check(b"SCWNGDKQGGSNRRTMRSRSRSRSRSRSRSRSLNICYTWQFWIVIINYYLPHHPPGPPGKPIEPGPKG", &[(16, 32), (50, 68)]);
+    check(b"KTFYGTMLVVFRKRSRSRSRSRSRSRSRSYSRSRSRTYSRSRSRMMYAFMNDDAYEQQQQHQQQQQQQQVS", &[(13, 44), (56, 69)]);
+    check(b"PLKMITRQYDVPRNQQQQYQQQQHQDIEGGQKLHSGSGSGSGSGSGSGSGSGSGSGPGSGSGSGSGSCSHISP", &[(14, 25), (34, 67)]);
+}
+
+#[test]
+fn soft_mask_blocks_seeds_but_not_alignment() {
+    let mut masked = COMPLEX[..80].to_vec();
+    masked[20..50].make_ascii_lowercase();
+
+    let mut db = Database::new();
+    db.push("T", &masked);
+    let index = KmerIndex::build(&db, 4);
+    assert_eq!(index.total_postings, (80 - 4 + 1) - (30 + 4 - 1));
+
+    let scoring = Scoring::default();
+    let soft = extend_ungapped(&COMPLEX[..80], &masked, &scoring, 0, 0, 10);
+    assert_eq!((soft.score, soft.q_end), (80, 79));
+    assert_eq!(align_sw(&COMPLEX[..80], &masked, -10, -1, 1, -1).score, 80);
+}
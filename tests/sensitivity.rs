@@ -0,0 +1,93 @@
+use pro_search::align::ungapped::ScoringScheme;
+use pro_search::core::database::Database;
+use pro_search::search::pipeline::SearchParams;
+use pro_search::search::sensitivity::{search_iterated, Sensitivity, SensitivityOptions};
+
+mod common;
+use common::Rng;
+
+fn params(level: Sensitivity) -> SearchParams {
+    let mut params = SearchParams { top_n: 5, scoring: ScoringScheme::Blosum62, ..Default::default() };
+    level.apply(&mut params);
+    params
+}
+
+#[test]
+fn presets_order_and_iterated_levels() {
+    let default = Sensitivity::Default.preset();
+    assert_eq!((default.k, default.min_diagonal, default.x_drop, default.sw_candidates), (5, 2, 10, 20));
+    let ks: Vec<usize> = Sensitivity::LEVELS.iter().map(|l| l.preset().k).collect();
+    assert_eq!(ks, [6, 5, 4, 3]);
+    // Spaced seeds are one weight above k; only the densest index is compressed
+    for level in Sensitivity::LEVELS {
+        let preset = level.preset();
+        assert_eq!(preset.pattern.bytes().filter(|&b| b == b'1').count(), preset.k + 1, "{:?}", level);
+        assert_eq!(preset.compressed, level == Sensitivity::VerySensitive);
+    }
+    let min_diagonals: Vec<usize> = Sensitivity::LEVELS.iter().map(|l| l.preset().min_diagonal).collect();
+    assert_eq!(min_diagonals, [3, 2, 2, 1]);
+    assert_eq!(SearchParams::default().pattern, "11010111");
+
+    let none = SensitivityOptions::default();
+    assert_eq!((none.level(), none.levels()), (Sensitivity::Default, vec![Sensitivity::Default]));
+    let sensitive = SensitivityOptions { sensitive: true, ..none };
+    assert_eq!(sensitive.levels(), [Sensitivity::Sensitive]);
+    let iterated = SensitivityOptions { iterate: true, ..sensitive };
+    assert_eq!(iterated.levels(), [Sensitivity::Fast, Sensitivity::Default, Sensitivity::Sensitive]);
+    let fast = SensitivityOptions { fast: true, iterate: true, ..none };
+    assert_eq!(fast.levels(), [Sensitivity::Fast]);
+}
+
+#[test]
+fn iterated_search_reruns_queries_without_a_significant_hit() {
+    let mut rng = Rng(0x1736);
+    let mut db = Database::new();
+    for i in 0..300 {
+        let len = 100 + (rng.next() % 200) as usize;
+        db.push(&format!("R{:03} unrelated", i), &rng.sequence(len));
+    }
+    let close = rng.sequence(150);
+    db.push("CLOSE homolog", &close);
+    // Every fifth residue differs, so no 5-mer is shared but plenty of 4-mers are
+    let distant_query = rng.sequence(150);
+    let distant: Vec<u8> = distant_query.iter().enumerate()
+        .map(|(i, &aa)| if i % 5 == 4 { if aa == b'W' { b'A' } else { b'W' } } else { aa })
+        .collect();
+    db.push("DISTANT homolog", &distant);
+    let orphan = rng.sequence(150);
+    let queries: [&[u8]; 3] = [&close, &distant_query, &orphan];
+
+    let rounds: Vec<_> = Sensitivity::LEVELS.iter().map(|&l| (l, params(l))).collect();
+    let results = search_iterated(&db, &queries, &rounds, 1e-3);
+    assert_eq!(results.len(), 3);
+    assert_eq!((results[0].round, results[0].significant), (0, true));
+    assert_eq!(db.id(results[0].hits[0].0.id as usize), "CLOSE");
+    assert!(results[0].hits[0].1 < 1e-50);
+    // Found with 4-mers, and by then the query is not searched again
+    assert_eq!((results[1].round, results[1].significant), (2, true));
+    assert_eq!(db.id(results[1].hits[0].0.id as usize), "DISTANT");
+    assert_eq!((results[2].round, results[2].significant), (3, false));
+    assert!(results[2].hits.iter().all(|&(_, e)| e > 1e-3));
+
+    // A single fast round leaves the distant query without a significant hit
+    let fast = search_iterated(&db, &queries[1..2], &rounds[..1], 1e-3);
+    assert_eq!((fast[0].round, fast[0].significant), (0, false));
+}
+
+#[test]
+fn iterate_rejects_explicit_seed_settings() {
+    let path = std::env::temp_dir().join(format!("pro_search_iterate_{}.fa", std::process::id()));
+    std::fs::write(&path, b">P1\nMKVLAAGHWQISFVDEPRT\n>P2\nMKVLAAGHWQ\n").unwrap();
+    let search = |extra: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_pro_search"))
+        .args(["-d", path.to_str().unwrap(), "search", "--query", "MKVLAAGHWQISFVDEPRT", "--scoring", "blosum62", "--iterate"])
+        .args(extra)
+        .output()
+        .unwrap();
+    assert!(search(&[]).status.success());
+    for extra in [&["-k", "4"][..], &["-x", "15"], &["--min-diagonal", "1"]] {
+        let out = search(extra);
+        assert!(!out.status.success(), "{:?}", extra);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("cannot be given with it"), "{:?}", extra);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
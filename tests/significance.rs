@@ -0,0 +1,77 @@
+use pro_search::core::database::Database;
+use pro_search::filter::seed::{find_candidate, find_significant_candidate};
+use pro_search::filter::significance::{diagonal_pvalue, expected_hits, poisson_upper_tail};
+use pro_search::index::kmer::KmerIndex;
+
+mod common;
+use common::{write_temp, Rng};
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * b.abs().max(1e-300)
+}
+
+#[test]
+fn poisson_tail_matches_closed_forms() {
+    assert_eq!(poisson_upper_tail(0, 3.0), 1.0);
+    assert_eq!(poisson_upper_tail(4, 0.0), 0.0);
+    // P(X >= 1) = 1 - e^-mu
+    assert!(close(poisson_upper_tail(1, 0.5), 1.0 - (-0.5f64).exp()));
+    // P(X >= 3) = 1 - e^-mu (1 + mu + mu^2 / 2)
+    assert!(close(poisson_upper_tail(3, 2.0), 1.0 - 5.0 * (-2.0f64).exp()));
+    // Far in the tail the first term dominates, with no 1 - CDF cancellation to zero
+    let mu: f64 = 1e-6;
+    let first = (-mu).exp() * mu.powi(5) / 120.0;
+    let tail = poisson_upper_tail(5, mu);
+    assert!(tail > 0.0 && (tail / first - 1.0).abs() < 1e-5, "{} vs {}", tail, first);
+}
+
+#[test]
+fn diagonal_pvalue_follows_counts_and_kmer_frequency() {
+    assert_eq!(expected_hits(100, 0, 50), 0.0);
+    assert!(close(expected_hits(200, 10_000, 300), 6.0));
+
+    let p = |count, query_postings| diagonal_pvalue(count, 100, 300, query_postings, 1_000_000);
+    // More hits on the diagonal: less likely by chance
+    assert!(p(3, 500) < p(2, 500));
+    assert!(p(8, 500) < 1e-12);
+    // The same hits from common k-mers (longer postings lists) count for less
+    assert!(p(3, 50_000) > p(3, 500));
+    // Longer targets have more chances
+    assert!(diagonal_pvalue(3, 100, 3_000, 500, 1_000_000) > p(3, 500));
+    // Bonferroni over all diagonals is capped at 1
+    assert_eq!(p(1, 50_000), 1.0);
+}
+
+#[test]
+fn significant_seeds_keep_the_homolog_and_drop_chance_hits() {
+    let mut rng = Rng(0x5160);
+    let mut fasta = Vec::new();
+    for i in 0..50 {
+        fasta.extend_from_slice(format!(">R{:02}\n", i).as_bytes());
+        fasta.extend_from_slice(&rng.sequence(300));
+        fasta.push(b'\n');
+    }
+    let homolog = rng.sequence(300);
+    fasta.extend_from_slice(b">HOMOLOG\n");
+    fasta.extend_from_slice(&homolog);
+    let path = write_temp("significance", "db.fa", &fasta);
+    let db = Database::load_from_fasta(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let query = rng.mutate(&homolog[100..200], 0.2);
+    let index = KmerIndex::build(&db, 3);
+
+    // With k = 3 every random target shares a few k-mers with the query
+    let chance = find_candidate(&index, &query, 1);
+    assert!(chance.len() > 20, "{} candidates", chance.len());
+
+    // P-values are per target: with 51 of them a chance 3-hit diagonal can reach 1e-4
+    let significant = find_significant_candidate(&index, &query, 1e-6);
+    assert_eq!(significant.iter().map(|c| c.id).collect::<Vec<_>>(), vec![50]);
+    assert!(significant[0].pvalue.unwrap() < 1e-10);
+
+    let relaxed = find_significant_candidate(&index, &query, 1.0);
+    assert_eq!(relaxed.len(), chance.len());
+    assert_eq!(relaxed[0].id, 50);
+    assert!(relaxed.windows(2).all(|w| w[0].pvalue <= w[1].pvalue));
+    assert!(chance.iter().all(|c| c.pvalue.is_none()));
+}
@@ -0,0 +1,81 @@
+use pro_search::core::alphabet::encode_kmer;
+use pro_search::core::database::Database;
+use pro_search::filter::seed::find_candidate;
+use pro_search::index::compressed::CompressedKmerIndex;
+use pro_search::index::kmer::{BuildOptions, KmerIndex};
+
+mod common;
+use common::Rng;
+
+/// Random proteins, every fourth with a poly-Q tract so QQQQ is by far the commonest word.
+fn polyq_db() -> Database {
+    let mut rng = Rng(0x570B);
+    let mut db = Database::new();
+    for i in 0..200 {
+        let mut seq = rng.sequence(120);
+        if i % 4 == 0 {
+            seq.splice(50..50, std::iter::repeat_n(b'Q', 12));
+        }
+        db.push(&format!("P{:03}", i), &seq);
+    }
+    db
+}
+
+#[test]
+fn max_postings_stop_lists_common_kmers() {
+    let db = polyq_db();
+    let full = KmerIndex::build(&db, 4);
+    let options = BuildOptions { max_postings: Some(20), ..Default::default() };
+    let capped = KmerIndex::build_with(&db, 4, &options);
+
+    let qqqq = encode_kmer(b"QQQQ").unwrap();
+    // 50 tracts of 9 QQQQ windows, plus any chance ones
+    assert!(full.map[&qqqq].len() >= 450);
+    assert_eq!(capped.stop_list.get(&qqqq), Some(&(full.map[&qqqq].len() as u32)));
+    assert!(!capped.map.contains_key(&qqqq));
+
+    // Kept k-mers have exactly the postings of the uncapped index
+    for (kmer, postings) in &full.map {
+        match capped.map.get(kmer) {
+            Some(kept) => assert_eq!(kept, postings),
+            None => assert_eq!(capped.stop_list[kmer] as usize, postings.len()),
+        }
+    }
+    assert!(capped.map.values().all(|p| p.len() <= 20));
+    assert!(capped.stop_list.values().all(|&c| c > 20));
+    assert_eq!(capped.total_postings + capped.dropped_postings, full.total_postings);
+    assert!(capped.memory_usage() < full.memory_usage());
+
+    // Stop-listed words seed nothing
+    let query = b"QQQQQQQQQQQQQQQQ";
+    assert!(!find_candidate(&full, query, 2).is_empty());
+    assert!(find_candidate(&capped, query, 2).is_empty());
+}
+
+#[test]
+fn percentile_cap_keeps_the_shorter_lists() {
+    let db = polyq_db();
+    let full = KmerIndex::build(&db, 3);
+    let options = BuildOptions { max_percentile: Some(99.0), ..Default::default() };
+    let capped = KmerIndex::build_with(&db, 3, &options);
+
+    let limit = options.postings_limit(full.map.values().map(|p| p.len())).unwrap();
+    assert!(!capped.stop_list.is_empty());
+    assert_eq!(capped.map.len() + capped.stop_list.len(), full.map.len());
+    assert!(capped.map.values().all(|p| p.len() <= limit));
+    assert!(capped.stop_list.values().all(|&c| c as usize > limit));
+    // At most 1% of the words are stopped
+    assert!(capped.stop_list.len() * 100 <= full.map.len());
+}
+
+#[test]
+fn compressed_index_stop_lists_the_same_kmers() {
+    let db = polyq_db();
+    let options = BuildOptions { max_postings: Some(20), ..Default::default() };
+    let raw = KmerIndex::build_with(&db, 4, &options);
+    let compressed = CompressedKmerIndex::build_with(&db, 4, &options);
+    assert_eq!(compressed.stop_list, raw.stop_list);
+    assert_eq!(compressed.dropped_postings, raw.dropped_postings);
+    assert_eq!(compressed.total_postings, raw.total_postings);
+    assert_eq!(compressed.keys.len(), raw.map.len());
+}
@@ -0,0 +1,157 @@
+use pro_search::core::alphabet::encode_kmer;
+use pro_search::core::database::Database;
+use pro_search::core::residues::ResiduePolicy;
+use pro_search::core::translate::{
+    reverse_complement, six_frames, translate, Frame, GeneticCode, TranslatedDatabase,
+};
+use pro_search::index::kmer::KmerIndex;
+use pro_search::search::pipeline::{SearchIndexes, SearchMode, SearchParams};
+use pro_search::search::translated::{search_translated, search_translated_db, TranslatedQuery};
+
+mod common;
+use common::{back_translate, pseudo_random, ALPHABET};
+
+fn params(mode: SearchMode) -> SearchParams {
+    SearchParams { mode, k: 4, top_n: 5, pattern: "11011".to_string(), ..Default::default() }
+}
+
+#[test]
+fn translates_six_frames_and_maps_coordinates_back() {
+    let code = GeneticCode::standard();
+    let dna = b"ATGGCCtgaTTAcgN";
+    assert_eq!(translate(dna, Frame::new(1).unwrap(), &code), b"MA*Lx");
+    assert_eq!(translate(dna, Frame::new(2).unwrap(), &code), b"Wpdy");
+    assert_eq!(reverse_complement(b"ATGCNrY"), b"RyNGCAT");
+
+    let frames = six_frames(dna, &code);
+    assert_eq!(frames.len(), 6);
+    for (frame, protein) in &frames {
+        let (start, end) = frame.nucleotide_range(0, protein.len(), dna.len());
+        assert_eq!(start.abs_diff(end) + 1, 3 * protein.len(), "frame {}", frame);
+    }
+    // -1 reads the reverse complement from the last base
+    assert_eq!(Frame::new(-1).unwrap().nucleotide_range(0, 1, dna.len()), (15, 13));
+    assert_eq!(Frame::new(-3).unwrap().nucleotide_range(1, 2, dna.len()), (10, 8));
+    assert_eq!(Frame::new(2).unwrap().nucleotide_range(1, 3, dna.len()), (5, 10));
+}
+
+#[test]
+fn genetic_codes_differ_where_ncbi_says() {
+    let standard = GeneticCode::ncbi(1).unwrap();
+    let mito = GeneticCode::ncbi(2).unwrap();
+    let ciliate = GeneticCode::ncbi(6).unwrap();
+    assert_eq!(standard.translate_codon(b"TGA"), b'*');
+    assert_eq!(mito.translate_codon(b"TGA"), b'W');
+    assert_eq!(mito.translate_codon(b"AGA"), b'*');
+    assert_eq!(mito.translate_codon(b"ATA"), b'M');
+    assert_eq!(ciliate.translate_codon(b"TAA"), b'Q');
+    assert_eq!(standard.translate_codon(b"AUG"), b'M');
+    assert!(GeneticCode::ncbi(7).is_err());
+}
+
+#[test]
+fn reverse_strand_contig_finds_its_protein() {
+    let mut db = Database::new();
+    for i in 0..50 {
+        let seq = pseudo_random(150 + i, 0x1234_5678 + i as u64, ALPHABET);
+        db.push(&format!("P{:03} protein {}", i, i), &seq);
+    }
+    let target = 17;
+    let (_, protein) = db.get(target).unwrap();
+    let gene = back_translate(&protein[20..120]);
+
+    // 101 nt of flank puts the gene in a different frame on each strand
+    let mut contig = pseudo_random(101, 42, b"ACGT");
+    contig.extend_from_slice(&gene);
+    contig.extend(pseudo_random(57, 7, b"ACGT"));
+    let contig = reverse_complement(&contig);
+
+    let params = params(SearchMode::Auto);
+    let indexes = SearchIndexes::build(&db, &params);
+    let query = TranslatedQuery::new(&contig, &GeneticCode::standard(), &ResiduePolicy::default().mapper());
+    let hits = search_translated(&db, &indexes, &query, &params);
+
+    let best = &hits[0];
+    assert_eq!(best.id, target);
+    assert_eq!(best.frame, Frame::new(-3).unwrap());
+    // The reverse-complemented gene sits at 58..=357 of the contig, read from 357 down
+    let (hi, lo) = (57 + gene.len(), 58);
+    assert!(best.q_start > best.q_end);
+    assert!(best.q_start <= hi && best.q_end >= lo, "{:?} outside {}..{}", (best.q_start, best.q_end), hi, lo);
+    assert_eq!((hi - best.q_start) % 3, 0);
+    // Subject range matches the same codons
+    assert_eq!(best.t_start - 21, (hi - best.q_start) / 3);
+    assert_eq!(best.q_start - best.q_end + 1, 3 * (best.t_end - best.t_start + 1));
+    // Every target appears once
+    let mut ids: Vec<usize> = hits.iter().map(|h| h.id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), hits.len());
+}
+
+#[test]
+fn stop_codons_break_database_seeds() {
+    let mut db = TranslatedDatabase::new(GeneticCode::standard());
+    // +1 reads M A * G W
+    db.push("chr1 test", b"ATGGCCTAAGGCTGG", None);
+    assert_eq!(db.records(), 1);
+    assert_eq!(db.proteins.len(), 6);
+    assert_eq!(db.proteins.get(0).unwrap().1, b"MA*GW");
+    assert_eq!(db.origins[3].frame, Frame::new(-1).unwrap());
+
+    let index = KmerIndex::build(&db.proteins, 2);
+    let (mut windows, mut without_stop) = (0, 0);
+    for i in 0..db.proteins.len() {
+        for w in db.proteins.get(i).unwrap().1.windows(2) {
+            windows += 1;
+            without_stop += usize::from(!w.contains(&b'*'));
+        }
+    }
+    assert!(without_stop < windows);
+    assert_eq!(index.total_postings, without_stop);
+    assert!(index.query(encode_kmer(b"MA").unwrap()).is_some());
+    // The mitochondrial code reads TAA as a stop too, but TGA as W
+    let mut mito = TranslatedDatabase::new(GeneticCode::ncbi(2).unwrap());
+    mito.push("chrM", b"ATGTGAGGC", None);
+    assert_eq!(mito.proteins.get(0).unwrap().1, b"MWG");
+}
+
+#[test]
+fn protein_query_finds_gene_on_minus_strand_of_contig() {
+    let proteins: Vec<Vec<u8>> = (0..3).map(|i| pseudo_random(120, 0xABCD + i, ALPHABET)).collect();
+    // contig_0 is noise; contig_1 carries proteins[1] on the minus strand after 100 nt of flank
+    let mut genome = String::new();
+    genome.push_str(&format!(">contig_0 noise\n{}\n", String::from_utf8(pseudo_random(900, 3, b"ACGT")).unwrap()));
+    let mut contig = pseudo_random(100, 11, b"ACGT");
+    contig.extend(reverse_complement(&back_translate(&proteins[1])));
+    contig.extend(pseudo_random(200, 12, b"ACGT"));
+    genome.push_str(&format!(">contig_1 assembly\n{}\n", String::from_utf8(contig.clone()).unwrap()));
+
+    let path = std::env::temp_dir().join(format!("pro_search_tblastn_{}.fna", std::process::id()));
+    std::fs::write(&path, genome).unwrap();
+    let db = TranslatedDatabase::load_from_fasta(&path, GeneticCode::standard(), None).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(db.records(), 2);
+    assert_eq!(db.lengths, vec![900, contig.len()]);
+
+    let query = &proteins[1][10..100];
+    for mode in [SearchMode::Auto, SearchMode::Diagonal, SearchMode::Spaced] {
+        let params = params(mode);
+        let indexes = SearchIndexes::build(&db.proteins, &params);
+        let hits = search_translated_db(&db, &indexes, query, &params);
+        let best = &hits[0];
+        assert_eq!((best.record, best.accession.as_str()), (1, "contig_1"), "{:?}", mode);
+        assert!(best.frame.is_reverse());
+        if mode != SearchMode::Auto {
+            assert!(best.subject_range.is_none());
+            continue;
+        }
+        // The gene is at 101..=460 of the contig, read from 460 down; residue r of the
+        // query (1-based) is protein residue r + 10, so its codon ends at 460 - 3 * (r + 9)
+        let (q_start, q_end) = best.query_range.unwrap();
+        let (s_start, s_end) = best.subject_range.unwrap();
+        assert!(s_start > s_end);
+        assert_eq!(s_start, 460 - 3 * (q_start + 9));
+        assert_eq!(s_start - s_end + 1, 3 * (q_end - q_start + 1));
+    }
+}
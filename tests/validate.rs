@@ -0,0 +1,120 @@
+use std::process::Command;
+use pro_search::core::database::Database;
+use pro_search::core::validate::{classify, DatabaseReport, ResidueClass, StreamCheck};
+use pro_search::index::compressed::CompressedKmerIndex;
+use pro_search::index::kmer::KmerIndex;
+
+mod common;
+use common::Rng;
+
+fn messy_db() -> Database {
+    let mut db = Database::new();
+    db.push("P1 clean", b"MKVLA");
+    db.push("P2 ambiguous", b"MKXBZ");
+    db.push("P3 invalid", b"MK1-\x07V");
+    db.push("P1 again", b"GHW");
+    db.push("P5 empty", b"");
+    db.push("P6 masked", b"mkvLAGHWQISFVDEPRTA");
+    db
+}
+
+#[test]
+fn report_counts_composition_lengths_and_problems() {
+    assert_eq!(classify(b'A'), ResidueClass::Standard);
+    assert_eq!(classify(b'a'), ResidueClass::Standard);
+    assert_eq!(classify(b'X'), ResidueClass::Ambiguous);
+    assert_eq!(classify(b'u'), ResidueClass::Ambiguous);
+    assert_eq!(classify(b'*'), ResidueClass::Invalid);
+
+    let db = messy_db();
+    let report = DatabaseReport::build(&db);
+    assert_eq!((report.sequences, report.residues), (6, 38));
+    assert_eq!((report.min_len, report.max_len), (0, 19));
+    assert_eq!(report.composition[b'K' as usize], 3);
+    assert_eq!(report.composition[b'k' as usize], 1);
+    assert_eq!(report.composition[0x07], 1);
+    // 3 -> [2, 4), 5 and 5 and 6 -> [4, 8), 19 -> [16, 32); the empty record is left out
+    assert_eq!(report.length_histogram, vec![0, 1, 3, 0, 1]);
+
+    let issues: Vec<(usize, usize, usize)> = report.issues.iter().map(|s| (s.index, s.ambiguous, s.invalid)).collect();
+    assert_eq!(issues, vec![(1, 3, 0), (2, 0, 3)]);
+    assert_eq!((report.ambiguous_residues(), report.invalid_residues()), (3, 3));
+    assert_eq!(report.duplicates, vec![("P1".to_string(), vec![0, 3])]);
+    assert_eq!(report.empty, vec![4]);
+
+    let err = report.check().unwrap_err().to_string();
+    assert_eq!(err, "Database failed validation: 3 invalid residues in 1 sequences, 1 duplicate accessions, 1 empty records");
+}
+
+#[test]
+fn ambiguous_residues_pass_the_check() {
+    let mut db = Database::new();
+    db.push("P1", b"MKVLA");
+    db.push("P2", b"MKXBZUO");
+    db.push("P3", b"mkvla");
+    assert!(DatabaseReport::build(&db).check().is_ok());
+    assert!(DatabaseReport::build(&Database::new()).check().is_ok());
+}
+
+#[test]
+fn stream_check_catches_problems_across_pieces() {
+    let piece = |records: &[(&str, &[u8])]| {
+        let mut db = Database::new();
+        for (header, seq) in records {
+            db.push(header, seq);
+        }
+        db
+    };
+    let mut check = StreamCheck::default();
+    check.check(&piece(&[("P1", b"MKV"), ("P2", b"LLA")]), 0).unwrap();
+    check.check(&piece(&[("P3", b"GHW")]), 2).unwrap();
+    let err = check.check(&piece(&[("P4", b"MKV"), ("P1", b"GHW")]), 3).unwrap_err().to_string();
+    assert_eq!(err, "Database failed validation: duplicate accession P1 at record 4");
+
+    let mut check = StreamCheck::default();
+    let err = check.check(&piece(&[("P1", b"MKV"), ("P2", b"")]), 10).unwrap_err().to_string();
+    assert_eq!(err, "Database failed validation: 1 empty records (records 10-11)");
+}
+
+#[test]
+fn predicted_raw_memory_matches_the_built_index() {
+    let mut rng = Rng(0x57A7);
+    let mut db = Database::new();
+    for i in 0..300 {
+        let len = rng.below(500);
+        let mut seq = rng.sequence(len);
+        if i % 7 == 0 {
+            seq.extend(std::iter::repeat_n(b'Q', 40));
+        }
+        db.push(&format!("P{}", i), &seq);
+    }
+    db.push("tiny", b"MK");
+    for k in [2, 3, 4, 5] {
+        let raw = KmerIndex::build(&db, k);
+        let compressed = CompressedKmerIndex::build(&db, k);
+        let predicted = KmerIndex::predicted_memory_usage(compressed.postings_lens(), db.data.len(), db.len());
+        assert_eq!(predicted, raw.memory_usage(), "k={}", k);
+        assert_eq!(compressed.raw_memory_usage(), predicted, "k={}", k);
+    }
+}
+
+#[test]
+fn strict_fails_streamed_searches() {
+    let path = std::env::temp_dir().join(format!("pro_search_strict_{}.fa", std::process::id()));
+    std::fs::write(&path, b">P1\nMKVLAAGHWQISFVDEPRT\n>P2\nMKVLAAGHWQ\n>P1\nGHWQISFVDEPRT\n").unwrap();
+    let search = |extra: &[&str], strict: bool| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_pro_search"));
+        cmd.args(["-d", path.to_str().unwrap(), "search", "--query", "MKVLAAGHWQISFVDEPRT"]).args(extra);
+        if strict {
+            cmd.arg("--strict");
+        }
+        cmd.output().unwrap()
+    };
+    for extra in [&["--max-memory", "64M"][..], &["--query-index"], &[]] {
+        assert!(search(extra, false).status.success(), "{:?}", extra);
+        let out = search(extra, true);
+        assert!(!out.status.success(), "{:?}", extra);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("duplicate accession"), "{:?}", extra);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,86 @@
+use pro_search::core::database::Database;
+use pro_search::index::kmer::KmerIndex;
+use pro_search::index::spaced::SpacedIndex;
+use pro_search::index::weighting::{Bm25, WeightedScorer};
+
+mod common;
+use common::Rng;
+
+#[test]
+fn document_frequency_counts_proteins_not_occurrences() {
+    let seq_lens = vec![100; 10];
+    let mut scorer = WeightedScorer::new(&seq_lens, None);
+    // One protein with a 50-fold repeat: a rare word, however often it occurs there
+    let postings: Vec<(u32, u32)> = (0..50).map(|pos| (3, pos)).collect();
+    scorer.add_postings(&postings);
+    let expected = 50.0 * scorer.idf(1);
+    assert_eq!(scorer.finish(10), vec![(3, expected)]);
+
+    // Three proteins, one of them twice
+    let mut scorer = WeightedScorer::new(&seq_lens, None);
+    scorer.add_postings(&[(1, 0), (1, 9), (4, 2), (7, 5)]);
+    let idf = scorer.idf(3);
+    let mut hits = scorer.finish(10);
+    hits.sort_by_key(|&(id, _)| id);
+    assert_eq!(hits, vec![(1, 2.0 * idf), (4, idf), (7, idf)]);
+}
+
+#[test]
+fn ties_keep_the_lowest_ids() {
+    let seq_lens = vec![100; 40];
+    let mut scorer = WeightedScorer::new(&seq_lens, None);
+    // One k-mer in 30 proteins, listed in id order as postings are: all tie
+    let postings: Vec<(u32, u32)> = (0..30).map(|pid| (pid, 0)).collect();
+    scorer.add_postings(&postings);
+    let ids: Vec<u32> = scorer.finish(5).into_iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+}
+
+/// 100 proteins with a poly-Q tract, a Q-rich decoy and a target sharing a short rare
+/// segment with a query that is mostly poly-Q.
+fn polyq_db(rng: &mut Rng) -> (Database, Vec<u8>) {
+    let mut db = Database::new();
+    for i in 0..100 {
+        let mut seq = rng.sequence(150);
+        seq.splice(60..60, std::iter::repeat_n(b'Q', 8));
+        db.push(&format!("P{:03}", i), &seq);
+    }
+    let mut decoy = rng.sequence(100);
+    decoy.splice(20..20, std::iter::repeat_n(b'Q', 40));
+    db.push("DECOY", &decoy);
+    let target = rng.sequence(200);
+    db.push("TARGET", &target);
+
+    let mut query = vec![b'Q'; 30];
+    query.extend_from_slice(&target[80..95]);
+    (db, query)
+}
+
+#[test]
+fn idf_ranks_rare_shared_kmers_above_common_ones() {
+    let (db, query) = polyq_db(&mut Rng(0x1DF));
+    let (decoy, target) = (100, 101);
+    let index = KmerIndex::build(&db, 4);
+
+    // Votes: every shared QQQQ counts, so the Q-rich decoy wins
+    assert_eq!(index.search_basic(&query, 1)[0].0, decoy);
+    // IDF: QQQQ is in every protein and worth almost nothing
+    assert_eq!(index.search_idf(&query, 1, None)[0].0, target);
+    assert_eq!(index.search_idf(&query, 1, Some(Bm25::default()))[0].0, target);
+
+    let spaced = SpacedIndex::build(&db, "11011");
+    assert_eq!(spaced.search_basic(&query, 1)[0].0, decoy);
+    assert_eq!(spaced.search_idf(&query, 1, None)[0].0, target);
+}
+
+#[test]
+fn bm25_saturates_term_frequency() {
+    let (db, query) = polyq_db(&mut Rng(0xB25));
+    let index = KmerIndex::build(&db, 4);
+    let score = |hits: Vec<(u32, f32)>, id: u32| hits.into_iter().find(|h| h.0 == id).unwrap().1;
+    let idf = index.search_idf(&query, 102, None);
+    let bm25 = index.search_idf(&query, 102, Some(Bm25::default()));
+    // The decoy's 37 QQQQ occurrences count 37 times under IDF but about k1 + 1 under BM25
+    let (decoy, target) = (100, 101);
+    assert!(score(bm25.clone(), decoy) / score(bm25, target) < score(idf.clone(), decoy) / score(idf, target));
+}